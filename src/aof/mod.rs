@@ -0,0 +1,4 @@
+mod reader;
+mod replay;
+
+pub use replay::{AofPosition, AofReplayer};
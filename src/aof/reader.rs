@@ -0,0 +1,201 @@
+use std::fs;
+use std::io::BufRead;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Result};
+
+// 逐条读取 AOF 中 RESP multibulk 格式的命令，同时返回命令在文件中的起始偏移量
+pub struct AofReader<R: BufRead> {
+    reader: R,
+    offset: u64,
+    done: bool,
+}
+
+impl<R: BufRead> AofReader<R> {
+    // offset 为 reader 当前位置在文件中的偏移量，带 RDB 前导的 AOF 从 RDB 结束处开始
+    pub fn new(reader: R, offset: u64) -> Self {
+        Self {
+            reader,
+            offset,
+            done: false,
+        }
+    }
+
+    fn read_line(&mut self) -> Result<Option<Vec<u8>>> {
+        let mut line = vec![];
+        let n = self.reader.read_until(b'\n', &mut line)?;
+        if n == 0 {
+            return Ok(None);
+        }
+        self.offset += n as u64;
+        if !line.ends_with(b"\r\n") {
+            return Err(anyhow!("unexpected end of aof at {}", self.offset));
+        }
+        line.truncate(line.len() - 2);
+        Ok(Some(line))
+    }
+
+    fn read_number(&mut self, prefix: u8) -> Result<Option<usize>> {
+        let line = match self.read_line()? {
+            Some(l) => l,
+            None => return Ok(None),
+        };
+        if line.first() != Some(&prefix) {
+            return Err(anyhow!(
+                "invalid aof format before {}: {}",
+                self.offset,
+                String::from_utf8_lossy(&line)
+            ));
+        }
+        let n = std::str::from_utf8(&line[1..])?
+            .parse()
+            .map_err(|_| anyhow!("invalid aof length before {}", self.offset))?;
+        Ok(Some(n))
+    }
+
+    fn next_command(&mut self) -> Result<Option<(u64, Vec<Vec<u8>>)>> {
+        let start = self.offset;
+        let count = match self.read_number(b'*')? {
+            Some(c) => c,
+            None => return Ok(None),
+        };
+        let mut args = Vec::with_capacity(count);
+        for _ in 0..count {
+            let len = self
+                .read_number(b'$')?
+                .ok_or_else(|| anyhow!("unexpected end of aof at {}", self.offset))?;
+            let mut arg = vec![0u8; len + 2];
+            self.reader.read_exact(&mut arg)?;
+            self.offset += arg.len() as u64;
+            arg.truncate(len);
+            args.push(arg);
+        }
+        Ok(Some((start, args)))
+    }
+}
+
+impl<R: BufRead> Iterator for AofReader<R> {
+    type Item = Result<(u64, Vec<Vec<u8>>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        match self.next_command() {
+            Ok(Some(cmd)) => Some(Ok(cmd)),
+            Ok(None) => None,
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+// Redis 7 multi-part AOF 清单中的文件
+#[derive(Debug, Clone, PartialEq)]
+pub struct ManifestFile {
+    pub name: String,
+    pub seq: u64,
+    // b: base，h: history，i: incr
+    pub file_type: String,
+}
+
+// 解析 manifest，返回需要按顺序回放的 base 与 incr 文件，history 文件已被 base 包含
+pub fn parse_manifest(content: &str) -> Result<Vec<ManifestFile>> {
+    let mut base = vec![];
+    let mut incr = vec![];
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let mut file = ManifestFile {
+            name: "".to_string(),
+            seq: 0,
+            file_type: "".to_string(),
+        };
+        for kv in fields.chunks(2) {
+            match kv {
+                ["file", v] => file.name = v.trim_matches('"').to_string(),
+                ["seq", v] => file.seq = v.parse()?,
+                ["type", v] => file.file_type = v.to_string(),
+                _ => {}
+            }
+        }
+        if file.name.is_empty() {
+            return Err(anyhow!("invalid manifest line: {}", line));
+        }
+        match file.file_type.as_str() {
+            "b" => base.push(file),
+            "i" => incr.push(file),
+            _ => {}
+        }
+    }
+    incr.sort_by_key(|f| f.seq);
+    base.append(&mut incr);
+    Ok(base)
+}
+
+// path 为 AOF 文件、manifest 文件或 appenddirname 目录，返回按顺序回放的文件
+pub fn aof_files(path: &str) -> Result<Vec<PathBuf>> {
+    let p = Path::new(path);
+    let manifest = match p.is_dir() {
+        true => fs::read_dir(p)?
+            .filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .find(|f| f.to_string_lossy().ends_with(".manifest"))
+            .ok_or_else(|| anyhow!("no manifest file in {}", path))?,
+        false => p.to_path_buf(),
+    };
+    if !manifest.to_string_lossy().ends_with(".manifest") {
+        return Ok(vec![manifest]);
+    }
+
+    let dir = manifest.parent().unwrap_or(Path::new("."));
+    let content = fs::read_to_string(&manifest)?;
+    Ok(parse_manifest(&content)?
+        .into_iter()
+        .map(|f| dir.join(f.name))
+        .collect())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    //cargo test aof::reader::test::test_aof_reader --  --nocapture
+    #[test]
+    fn test_aof_reader() {
+        let aof = b"*2\r\n$6\r\nSELECT\r\n$1\r\n0\r\n*3\r\n$3\r\nSET\r\n$1\r\nk\r\n$3\r\na\nb\r\n";
+        let cmds: Vec<(u64, Vec<Vec<u8>>)> = AofReader::new(aof.as_slice(), 0)
+            .map(|c| c.unwrap())
+            .collect();
+        assert_eq!(cmds.len(), 2);
+        assert_eq!(cmds[1].0, 23);
+        assert_eq!(cmds[1].1[2], b"a\nb".to_vec());
+
+        // 截断的命令返回错误
+        let mut truncated = AofReader::new(b"*2\r\n$3\r\nDEL".as_slice(), 0);
+        assert!(truncated.next().unwrap().is_err());
+
+        let manifest = "file appendonly.aof.2.incr.aof seq 2 type i\n\
+            file appendonly.aof.1.base.rdb seq 1 type b\n\
+            file appendonly.aof.0.incr.aof seq 0 type h\n\
+            file appendonly.aof.1.incr.aof seq 1 type i\n";
+        let names: Vec<String> = parse_manifest(manifest)
+            .unwrap()
+            .into_iter()
+            .map(|f| f.name)
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                "appendonly.aof.1.base.rdb",
+                "appendonly.aof.1.incr.aof",
+                "appendonly.aof.2.incr.aof"
+            ]
+        );
+    }
+}
@@ -0,0 +1,846 @@
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::rdb::RdbReader;
+use crate::util::{now_ms, MemEntry, MemValue, MemoryConnection};
+
+use super::reader::{aof_files, AofReader};
+
+// 最后一次修改 key 的 AOF 命令位置，RDB base 或前导中的 key 偏移量为 0
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AofPosition {
+    pub file: String,
+    pub offset: u64,
+}
+
+// 统计读取的字节数，用于计算 RDB 前导之后 AOF 命令的偏移量
+struct CountingReader<R: Read> {
+    inner: R,
+    count: u64,
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.count += n as u64;
+        Ok(n)
+    }
+}
+
+fn wrong_type() -> anyhow::Error {
+    anyhow!("WRONGTYPE Operation against a key holding the wrong kind of value")
+}
+
+fn parse_i64(arg: &[u8]) -> Result<i64> {
+    std::str::from_utf8(arg)?
+        .parse()
+        .map_err(|_| anyhow!("value is not an integer: {}", String::from_utf8_lossy(arg)))
+}
+
+fn parse_f64(arg: &[u8]) -> Result<f64> {
+    let s = std::str::from_utf8(arg)?;
+    match s.to_lowercase().as_str() {
+        "inf" | "+inf" => Ok(f64::INFINITY),
+        "-inf" => Ok(f64::NEG_INFINITY),
+        _ => s
+            .parse()
+            .map_err(|_| anyhow!("value is not a valid float: {}", s)),
+    }
+}
+
+// ZREMRANGEBYSCORE 的区间端点，( 前缀表示开区间
+fn parse_score_bound(arg: &[u8]) -> Result<(f64, bool)> {
+    match arg.strip_prefix(b"(") {
+        Some(rest) => Ok((parse_f64(rest)?, true)),
+        None => Ok((parse_f64(arg)?, false)),
+    }
+}
+
+// LRANGE 语义的下标区间
+fn index_range(len: usize, start: i64, stop: i64) -> Option<(usize, usize)> {
+    let len = len as i64;
+    let s = if start < 0 {
+        (len + start).max(0)
+    } else {
+        start
+    };
+    let e = if stop < 0 {
+        len + stop
+    } else {
+        stop.min(len - 1)
+    };
+    if len == 0 || s > e || s >= len {
+        return None;
+    }
+    Some((s as usize, e as usize))
+}
+
+// 将 AOF 回放到内存中的各个 DB，并记录每个 key 最后一次被修改的位置
+#[derive(Debug, Default)]
+pub struct AofReplayer {
+    pub dbs: BTreeMap<usize, MemoryConnection>,
+    pub touched: BTreeMap<(usize, Vec<u8>), AofPosition>,
+    // 无法回放的命令及次数
+    pub unsupported: BTreeMap<String, usize>,
+    db: usize,
+}
+
+impl AofReplayer {
+    // 回放 AOF 文件、manifest 或 appenddirname 目录
+    pub fn load(&mut self, path: &str) -> Result<()> {
+        for file in aof_files(path)? {
+            self.load_file(&file)?;
+        }
+        Ok(())
+    }
+
+    fn load_file(&mut self, path: &Path) -> Result<()> {
+        let name = path.to_string_lossy().to_string();
+        let mut reader =
+            BufReader::new(File::open(path).map_err(|e| anyhow!("open aof file {}: {}", name, e))?);
+        // 每个文件从 DB 0 开始
+        self.db = 0;
+
+        let mut offset = 0;
+        if reader.fill_buf()?.starts_with(b"REDIS") {
+            // base rdb 或带 RDB 前导的 AOF
+            let mut counting = CountingReader {
+                inner: &mut reader,
+                count: 0,
+            };
+            let mut rdb = RdbReader::new(&mut counting)?;
+            let position = AofPosition {
+                file: name.clone(),
+                offset: 0,
+            };
+            for entry in rdb.by_ref() {
+                let entry = entry?;
+                self.touched
+                    .insert((entry.db, entry.key.clone()), position.clone());
+                self.dbs.entry(entry.db).or_default().insert(
+                    entry.key,
+                    entry.value,
+                    entry.expire_at_ms,
+                );
+            }
+            let version = rdb.version;
+            let counting = rdb.into_inner();
+            // 版本 5 起 EOF 后带 8 字节校验和
+            if version >= 5 {
+                let mut checksum = [0u8; 8];
+                counting.read_exact(&mut checksum)?;
+            }
+            offset = counting.count;
+        }
+
+        for cmd in AofReader::new(reader, offset) {
+            let (offset, args) = match cmd {
+                Ok(c) => c,
+                Err(e) => {
+                    // 与 aof-load-truncated 一致，忽略末尾不完整的命令
+                    log::error!("{}: {}", name, e);
+                    break;
+                }
+            };
+            let position = AofPosition {
+                file: name.clone(),
+                offset,
+            };
+            if let Err(e) = self.apply(&args, &position) {
+                log::error!("{} offset {}: {}", name, offset, e);
+            }
+        }
+        Ok(())
+    }
+
+    fn db_mut(&mut self) -> &mut MemoryConnection {
+        self.dbs.entry(self.db).or_default()
+    }
+
+    fn touch(&mut self, key: &[u8], position: &AofPosition) {
+        self.touched
+            .insert((self.db, key.to_vec()), position.clone());
+    }
+
+    fn entry_mut(&mut self, key: &[u8]) -> Option<&mut MemEntry> {
+        self.db_mut().entries.get_mut(key)
+    }
+
+    fn exists(&mut self, key: &[u8]) -> bool {
+        self.db_mut().entries.contains_key(key)
+    }
+
+    fn remove(&mut self, key: &[u8]) -> Option<MemEntry> {
+        self.db_mut().entries.remove(key)
+    }
+
+    // 获取指定类型的 value，key 不存在时以 empty 创建
+    fn value_mut(&mut self, key: &[u8], empty: MemValue) -> Result<&mut MemValue> {
+        let entry = self
+            .db_mut()
+            .entries
+            .entry(key.to_vec())
+            .or_insert(MemEntry {
+                value: empty.clone(),
+                expire_at_ms: None,
+            });
+        if entry.value.type_name() != empty.type_name() {
+            return Err(wrong_type());
+        }
+        Ok(&mut entry.value)
+    }
+
+    // 集合类型元素为空时删除 key
+    fn remove_if_empty(&mut self, key: &[u8]) {
+        let empty = match self.db_mut().entries.get(key) {
+            Some(MemEntry { value, .. }) => match value {
+                MemValue::String(_) => false,
+                MemValue::List(l) => l.is_empty(),
+                MemValue::Set(s) => s.is_empty(),
+                MemValue::ZSet(z) => z.is_empty(),
+                MemValue::Hash(h) => h.is_empty(),
+            },
+            None => false,
+        };
+        if empty {
+            self.remove(key);
+        }
+    }
+
+    fn list_mut(&mut self, key: &[u8]) -> Result<&mut VecDeque<Vec<u8>>> {
+        match self.value_mut(key, MemValue::List(VecDeque::new()))? {
+            MemValue::List(l) => Ok(l),
+            _ => Err(wrong_type()),
+        }
+    }
+
+    fn set_mut(&mut self, key: &[u8]) -> Result<&mut BTreeSet<Vec<u8>>> {
+        match self.value_mut(key, MemValue::Set(BTreeSet::new()))? {
+            MemValue::Set(s) => Ok(s),
+            _ => Err(wrong_type()),
+        }
+    }
+
+    fn zset_mut(&mut self, key: &[u8]) -> Result<&mut BTreeMap<Vec<u8>, f64>> {
+        match self.value_mut(key, MemValue::ZSet(BTreeMap::new()))? {
+            MemValue::ZSet(z) => Ok(z),
+            _ => Err(wrong_type()),
+        }
+    }
+
+    fn hash_mut(&mut self, key: &[u8]) -> Result<&mut BTreeMap<Vec<u8>, Vec<u8>>> {
+        match self.value_mut(key, MemValue::Hash(BTreeMap::new()))? {
+            MemValue::Hash(h) => Ok(h),
+            _ => Err(wrong_type()),
+        }
+    }
+
+    fn string_mut(&mut self, key: &[u8]) -> Result<&mut Vec<u8>> {
+        match self.value_mut(key, MemValue::String(vec![]))? {
+            MemValue::String(s) => Ok(s),
+            _ => Err(wrong_type()),
+        }
+    }
+
+    fn set_string(&mut self, key: &[u8], value: Vec<u8>, expire_at_ms: Option<i64>) {
+        self.db_mut()
+            .insert(key.to_vec(), MemValue::String(value), expire_at_ms);
+    }
+
+    fn set_expire(&mut self, key: &[u8], expire_at_ms: Option<i64>) {
+        if let Some(entry) = self.entry_mut(key) {
+            entry.expire_at_ms = expire_at_ms;
+        }
+    }
+
+    fn incr_by(&mut self, key: &[u8], delta: i64) -> Result<()> {
+        let s = self.string_mut(key)?;
+        let current = match s.is_empty() {
+            true => 0,
+            false => parse_i64(s)?,
+        };
+        *s = (current + delta).to_string().into_bytes();
+        Ok(())
+    }
+
+    // 回放一条命令，修改的 key 记录为 position
+    pub fn apply(&mut self, args: &[Vec<u8>], position: &AofPosition) -> Result<()> {
+        let r = self.apply_command(args, position);
+        // 出错时可能遗留新建的空集合
+        if let Some(key) = args.get(1) {
+            self.remove_if_empty(key);
+        }
+        r
+    }
+
+    fn apply_command(&mut self, args: &[Vec<u8>], position: &AofPosition) -> Result<()> {
+        let name = match args.first() {
+            Some(n) => String::from_utf8_lossy(n).to_lowercase(),
+            None => return Ok(()),
+        };
+        let arg = |i: usize| -> Result<&[u8]> {
+            args.get(i)
+                .map(|a| a.as_slice())
+                .ok_or_else(|| anyhow!("wrong number of arguments for {}", name))
+        };
+
+        // 除 select、flush 外第一个参数均为 key
+        match name.as_str() {
+            "multi" | "exec" => return Ok(()),
+            "select" => {
+                self.db = parse_i64(arg(1)?)? as usize;
+                return Ok(());
+            }
+            "flushdb" | "flushall" => {
+                let dbs: Vec<usize> = match name.as_str() {
+                    "flushdb" => vec![self.db],
+                    _ => self.dbs.keys().copied().collect(),
+                };
+                for db in dbs {
+                    if let Some(conn) = self.dbs.remove(&db) {
+                        for key in conn.entries.into_keys() {
+                            self.touched.insert((db, key), position.clone());
+                        }
+                    }
+                }
+                return Ok(());
+            }
+            "swapdb" => {
+                let a = parse_i64(arg(1)?)? as usize;
+                let b = parse_i64(arg(2)?)? as usize;
+                let a_conn = self.dbs.remove(&a).unwrap_or_default();
+                let b_conn = self.dbs.remove(&b).unwrap_or_default();
+                for (db, conn) in [(a, &b_conn), (b, &a_conn)] {
+                    for key in conn.entries.keys() {
+                        self.touched.insert((db, key.clone()), position.clone());
+                    }
+                }
+                self.dbs.insert(a, b_conn);
+                self.dbs.insert(b, a_conn);
+                return Ok(());
+            }
+            _ => {}
+        }
+
+        let key = arg(1)?.to_vec();
+
+        match name.as_str() {
+            // string
+            "set" => {
+                let mut expire_at_ms = None;
+                let mut keep_ttl = false;
+                let mut nx = false;
+                let mut xx = false;
+                let mut i = 3;
+                while i < args.len() {
+                    let opt = String::from_utf8_lossy(&args[i]).to_lowercase();
+                    match opt.as_str() {
+                        "ex" | "px" | "exat" | "pxat" => {
+                            let v = parse_i64(arg(i + 1)?)?;
+                            expire_at_ms = Some(match opt.as_str() {
+                                "ex" => now_ms() + v * 1000,
+                                "px" => now_ms() + v,
+                                "exat" => v * 1000,
+                                _ => v,
+                            });
+                            i += 1;
+                        }
+                        "keepttl" => keep_ttl = true,
+                        "nx" => nx = true,
+                        "xx" => xx = true,
+                        _ => {}
+                    }
+                    i += 1;
+                }
+                let exists = self.exists(&key);
+                if (nx && exists) || (xx && !exists) {
+                    return Ok(());
+                }
+                if keep_ttl {
+                    expire_at_ms = self.entry_mut(&key).and_then(|e| e.expire_at_ms);
+                }
+                self.set_string(&key, arg(2)?.to_vec(), expire_at_ms);
+            }
+            "setnx" => {
+                if !self.exists(&key) {
+                    self.set_string(&key, arg(2)?.to_vec(), None);
+                }
+            }
+            "setex" | "psetex" => {
+                let v = parse_i64(arg(2)?)?;
+                let ms = match name.as_str() {
+                    "setex" => v * 1000,
+                    _ => v,
+                };
+                self.set_string(&key, arg(3)?.to_vec(), Some(now_ms() + ms));
+            }
+            "getset" => self.set_string(&key, arg(2)?.to_vec(), None),
+            "getdel" => {
+                self.remove(&key);
+            }
+            "mset" | "msetnx" => {
+                let pairs: Vec<(Vec<u8>, Vec<u8>)> = args[1..]
+                    .chunks(2)
+                    .filter(|kv| kv.len() == 2)
+                    .map(|kv| (kv[0].clone(), kv[1].clone()))
+                    .collect();
+                if name == "msetnx" && pairs.iter().any(|(k, _)| self.exists(k)) {
+                    return Ok(());
+                }
+                for (k, v) in pairs {
+                    self.touch(&k, position);
+                    self.set_string(&k, v, None);
+                }
+            }
+            "append" => self.string_mut(&key)?.extend_from_slice(arg(2)?),
+            "setrange" => {
+                let offset = parse_i64(arg(2)?)? as usize;
+                let value = arg(3)?.to_vec();
+                let s = self.string_mut(&key)?;
+                if s.len() < offset + value.len() {
+                    s.resize(offset + value.len(), 0);
+                }
+                s[offset..offset + value.len()].copy_from_slice(&value);
+            }
+            "incr" => self.incr_by(&key, 1)?,
+            "decr" => self.incr_by(&key, -1)?,
+            "incrby" => self.incr_by(&key, parse_i64(arg(2)?)?)?,
+            "decrby" => self.incr_by(&key, -parse_i64(arg(2)?)?)?,
+            "incrbyfloat" => {
+                let delta = parse_f64(arg(2)?)?;
+                let s = self.string_mut(&key)?;
+                let current = match s.is_empty() {
+                    true => 0.0,
+                    false => parse_f64(s)?,
+                };
+                *s = (current + delta).to_string().into_bytes();
+            }
+
+            // keyspace
+            "del" | "unlink" => {
+                for k in &args[1..] {
+                    self.touch(k, position);
+                    self.remove(k);
+                }
+            }
+            "expire" | "pexpire" | "expireat" | "pexpireat" => {
+                let v = parse_i64(arg(2)?)?;
+                let at = match name.as_str() {
+                    "expire" => now_ms() + v * 1000,
+                    "pexpire" => now_ms() + v,
+                    "expireat" => v * 1000,
+                    _ => v,
+                };
+                self.set_expire(&key, Some(at));
+            }
+            "persist" => self.set_expire(&key, None),
+            "rename" | "renamenx" => {
+                let dst = arg(2)?.to_vec();
+                if name == "renamenx" && self.exists(&dst) {
+                    return Ok(());
+                }
+                self.touch(&dst, position);
+                if let Some(entry) = self.remove(&key) {
+                    self.db_mut().entries.insert(dst, entry);
+                }
+            }
+            "move" => {
+                let db = parse_i64(arg(2)?)? as usize;
+                if self
+                    .dbs
+                    .get(&db)
+                    .map_or(false, |c| c.entries.contains_key(&key))
+                {
+                    return Ok(());
+                }
+                if let Some(entry) = self.remove(&key) {
+                    self.touched.insert((db, key.clone()), position.clone());
+                    self.dbs
+                        .entry(db)
+                        .or_default()
+                        .entries
+                        .insert(key.clone(), entry);
+                }
+            }
+            "copy" => {
+                let dst = arg(2)?.to_vec();
+                let mut db = self.db;
+                let mut replace = false;
+                let mut i = 3;
+                while i < args.len() {
+                    match String::from_utf8_lossy(&args[i]).to_lowercase().as_str() {
+                        "db" => {
+                            db = parse_i64(arg(i + 1)?)? as usize;
+                            i += 1;
+                        }
+                        "replace" => replace = true,
+                        _ => {}
+                    }
+                    i += 1;
+                }
+                let entry = match self.db_mut().entries.get(&key) {
+                    Some(e) => e.clone(),
+                    None => return Ok(()),
+                };
+                let target = self.dbs.entry(db).or_default();
+                if !replace && target.entries.contains_key(&dst) {
+                    return Ok(());
+                }
+                target.entries.insert(dst.clone(), entry);
+                self.touched.insert((db, dst), position.clone());
+            }
+
+            // list
+            "rpush" | "lpush" | "rpushx" | "lpushx" => {
+                if name.ends_with('x') && !self.exists(&key) {
+                    return Ok(());
+                }
+                let list = self.list_mut(&key)?;
+                for v in &args[2..] {
+                    match name.starts_with('r') {
+                        true => list.push_back(v.clone()),
+                        false => list.push_front(v.clone()),
+                    }
+                }
+            }
+            "lpop" | "rpop" => {
+                let count = match args.get(2) {
+                    Some(c) => parse_i64(c)? as usize,
+                    None => 1,
+                };
+                if self.exists(&key) {
+                    let list = self.list_mut(&key)?;
+                    for _ in 0..count {
+                        match name.as_str() {
+                            "lpop" => list.pop_front(),
+                            _ => list.pop_back(),
+                        };
+                    }
+                }
+            }
+            "lset" => {
+                let index = parse_i64(arg(2)?)?;
+                let value = arg(3)?.to_vec();
+                let list = self.list_mut(&key)?;
+                if let Some((i, _)) = index_range(list.len(), index, index) {
+                    list[i] = value;
+                }
+            }
+            "ltrim" => {
+                let (start, stop) = (parse_i64(arg(2)?)?, parse_i64(arg(3)?)?);
+                let list = self.list_mut(&key)?;
+                *list = match index_range(list.len(), start, stop) {
+                    Some((b, e)) => list.range(b..=e).cloned().collect(),
+                    None => VecDeque::new(),
+                };
+            }
+            "lrem" => {
+                let count = parse_i64(arg(2)?)?;
+                let value = arg(3)?.to_vec();
+                let list = self.list_mut(&key)?;
+                let mut removed = 0;
+                let limit = count.unsigned_abs() as usize;
+                let mut items: Vec<Vec<u8>> = list.drain(..).collect();
+                if count < 0 {
+                    items.reverse();
+                }
+                items.retain(|v| {
+                    if v == &value && (limit == 0 || removed < limit) {
+                        removed += 1;
+                        return false;
+                    }
+                    true
+                });
+                if count < 0 {
+                    items.reverse();
+                }
+                *list = items.into();
+            }
+            "linsert" => {
+                let before = arg(2)?.eq_ignore_ascii_case(b"before");
+                let pivot = arg(3)?.to_vec();
+                let value = arg(4)?.to_vec();
+                let list = self.list_mut(&key)?;
+                if let Some(i) = list.iter().position(|v| v == &pivot) {
+                    list.insert(if before { i } else { i + 1 }, value);
+                }
+            }
+            "rpoplpush" | "lmove" => {
+                let dst = arg(2)?.to_vec();
+                let (from_left, to_left) = match name.as_str() {
+                    "rpoplpush" => (false, true),
+                    _ => (
+                        arg(3)?.eq_ignore_ascii_case(b"left"),
+                        arg(4)?.eq_ignore_ascii_case(b"left"),
+                    ),
+                };
+                if !self.exists(&key) {
+                    return Ok(());
+                }
+                let item = match from_left {
+                    true => self.list_mut(&key)?.pop_front(),
+                    false => self.list_mut(&key)?.pop_back(),
+                };
+                self.remove_if_empty(&key);
+                if let Some(v) = item {
+                    self.touch(&dst, position);
+                    let list = self.list_mut(&dst)?;
+                    match to_left {
+                        true => list.push_front(v),
+                        false => list.push_back(v),
+                    }
+                }
+            }
+
+            // set
+            "sadd" => {
+                let set = self.set_mut(&key)?;
+                for m in &args[2..] {
+                    set.insert(m.clone());
+                }
+            }
+            "srem" => {
+                if self.exists(&key) {
+                    let set = self.set_mut(&key)?;
+                    for m in &args[2..] {
+                        set.remove(m);
+                    }
+                }
+            }
+            "smove" => {
+                let dst = arg(2)?.to_vec();
+                let member = arg(3)?.to_vec();
+                if self.exists(&key) && self.set_mut(&key)?.remove(&member) {
+                    self.remove_if_empty(&key);
+                    self.touch(&dst, position);
+                    self.set_mut(&dst)?.insert(member);
+                }
+            }
+
+            // zset
+            "zadd" => {
+                let mut flags = BTreeSet::new();
+                let mut i = 2;
+                while i < args.len() {
+                    let flag = String::from_utf8_lossy(&args[i]).to_lowercase();
+                    if !matches!(flag.as_str(), "nx" | "xx" | "gt" | "lt" | "ch" | "incr") {
+                        break;
+                    }
+                    flags.insert(flag);
+                    i += 1;
+                }
+                let zset = self.zset_mut(&key)?;
+                for pair in args[i..].chunks(2) {
+                    if pair.len() != 2 {
+                        return Err(anyhow!("syntax error in zadd"));
+                    }
+                    let mut score = parse_f64(&pair[0])?;
+                    let current = zset.get(&pair[1]).copied();
+                    match current {
+                        Some(_) if flags.contains("nx") => continue,
+                        None if flags.contains("xx") => continue,
+                        _ => {}
+                    }
+                    if flags.contains("incr") {
+                        score += current.unwrap_or(0.0);
+                    }
+                    if let Some(c) = current {
+                        if (flags.contains("gt") && score <= c)
+                            || (flags.contains("lt") && score >= c)
+                        {
+                            continue;
+                        }
+                    }
+                    zset.insert(pair[1].clone(), score);
+                }
+            }
+            "zincrby" => {
+                let delta = parse_f64(arg(2)?)?;
+                let member = arg(3)?.to_vec();
+                *self.zset_mut(&key)?.entry(member).or_insert(0.0) += delta;
+            }
+            "zrem" => {
+                if self.exists(&key) {
+                    let zset = self.zset_mut(&key)?;
+                    for m in &args[2..] {
+                        zset.remove(m);
+                    }
+                }
+            }
+            "zremrangebyscore" => {
+                let (min, min_ex) = parse_score_bound(arg(2)?)?;
+                let (max, max_ex) = parse_score_bound(arg(3)?)?;
+                if self.exists(&key) {
+                    self.zset_mut(&key)?.retain(|_, s| {
+                        let above = if min_ex { *s > min } else { *s >= min };
+                        let below = if max_ex { *s < max } else { *s <= max };
+                        !(above && below)
+                    });
+                }
+            }
+            "zremrangebyrank" | "zpopmin" | "zpopmax" => {
+                if !self.exists(&key) {
+                    return Ok(());
+                }
+                let len = self.zset_mut(&key)?.len();
+                let range = match name.as_str() {
+                    "zremrangebyrank" => index_range(len, parse_i64(arg(2)?)?, parse_i64(arg(3)?)?),
+                    _ => {
+                        let count = match args.get(2) {
+                            Some(c) => parse_i64(c)?,
+                            None => 1,
+                        };
+                        match name.as_str() {
+                            "zpopmin" => index_range(len, 0, count - 1),
+                            _ => index_range(len, -count, -1),
+                        }
+                    }
+                };
+                if let Some((b, e)) = range {
+                    let zset = self.zset_mut(&key)?;
+                    let members: Vec<Vec<u8>> = MemValue::zset_sorted(zset)[b..=e]
+                        .iter()
+                        .map(|(m, _)| (*m).clone())
+                        .collect();
+                    for m in members {
+                        zset.remove(&m);
+                    }
+                }
+            }
+
+            // hash
+            "hset" | "hmset" => {
+                let hash = self.hash_mut(&key)?;
+                for pair in args[2..].chunks(2) {
+                    if pair.len() == 2 {
+                        hash.insert(pair[0].clone(), pair[1].clone());
+                    }
+                }
+            }
+            "hsetnx" => {
+                let field = arg(2)?.to_vec();
+                let value = arg(3)?.to_vec();
+                self.hash_mut(&key)?.entry(field).or_insert(value);
+            }
+            "hdel" => {
+                if self.exists(&key) {
+                    let hash = self.hash_mut(&key)?;
+                    for f in &args[2..] {
+                        hash.remove(f);
+                    }
+                }
+            }
+            "hincrby" | "hincrbyfloat" => {
+                let field = arg(2)?.to_vec();
+                let delta = arg(3)?.to_vec();
+                let hash = self.hash_mut(&key)?;
+                let current = hash.get(&field).cloned().unwrap_or_default();
+                let value = match name.as_str() {
+                    "hincrby" => {
+                        let c = if current.is_empty() {
+                            0
+                        } else {
+                            parse_i64(&current)?
+                        };
+                        (c + parse_i64(&delta)?).to_string()
+                    }
+                    _ => {
+                        let c = if current.is_empty() {
+                            0.0
+                        } else {
+                            parse_f64(&current)?
+                        };
+                        (c + parse_f64(&delta)?).to_string()
+                    }
+                };
+                hash.insert(field, value.into_bytes());
+            }
+
+            _ => {
+                *self.unsupported.entry(name.clone()).or_insert(0) += 1;
+                return Err(anyhow!("unsupported aof command {}", name));
+            }
+        }
+
+        self.touch(&key, position);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn cmd(s: &str) -> Vec<Vec<u8>> {
+        s.split_whitespace()
+            .map(|a| a.as_bytes().to_vec())
+            .collect()
+    }
+
+    //cargo test aof::replay::test::test_aof_replay --  --nocapture
+    #[test]
+    fn test_aof_replay() {
+        let mut replayer = AofReplayer::default();
+        let commands = [
+            "SELECT 1",
+            "SET s v",
+            "APPEND s 1",
+            "INCRBY n 5",
+            "RPUSH l a b c d",
+            "LPOP l",
+            "LREM l -1 c",
+            "SADD set a b",
+            "SREM set a b",
+            "ZADD z 1 a 2 b 3 c",
+            "ZADD z XX GT 0 a",
+            "ZPOPMAX z",
+            "HSET h f 1",
+            "HINCRBY h f 2",
+            "PEXPIREAT n 4102444800000",
+            "RENAME s s2",
+        ];
+        for (i, c) in commands.iter().enumerate() {
+            let position = AofPosition {
+                file: "appendonly.aof".to_string(),
+                offset: i as u64,
+            };
+            replayer.apply(&cmd(c), &position).unwrap();
+        }
+
+        let db = replayer.dbs.get_mut(&1).unwrap();
+        assert_eq!(
+            db.get(b"s2").unwrap().value,
+            MemValue::String(b"v1".to_vec())
+        );
+        assert!(db.get(b"s").is_none());
+        assert_eq!(db.get(b"n").unwrap().expire_at_ms, Some(4102444800000));
+        assert_eq!(
+            db.get(b"l").unwrap().value,
+            MemValue::List(vec![b"b".to_vec(), b"d".to_vec()].into())
+        );
+        // 集合为空时 key 被删除
+        assert!(db.get(b"set").is_none());
+        match &db.get(b"z").unwrap().value {
+            MemValue::ZSet(z) => assert_eq!(z.len(), 2),
+            v => panic!("unexpected {:?}", v),
+        }
+        assert_eq!(
+            db.get(b"h").unwrap().value,
+            MemValue::Hash(BTreeMap::from([(b"f".to_vec(), b"3".to_vec())]))
+        );
+        assert_eq!(replayer.touched[&(1, b"set".to_vec())].offset, 8);
+        assert_eq!(replayer.touched[&(1, b"s2".to_vec())].offset, 15);
+
+        let position = AofPosition {
+            file: "appendonly.aof".to_string(),
+            offset: 99,
+        };
+        assert!(replayer.apply(&cmd("XADD st * f v"), &position).is_err());
+        assert_eq!(replayer.unsupported["xadd"], 1);
+    }
+}
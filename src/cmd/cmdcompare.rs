@@ -1,10 +1,176 @@
-use clap::{arg, Arg, Command};
+use clap::{arg, Arg, ArgAction, Command};
+
+use crate::compare::DEFAULT_RESULTS_ROOT;
 
 pub fn new_compare_cmd() -> Command {
     clap::Command::new("compare")
         .about("compare redis data by description file")
         .subcommand(compare_sample_cmd())
         .subcommand(compare_execute_cmd())
+        .subcommand(compare_summary_cmd())
+        .subcommand(compare_slots_cmd())
+        .subcommand(compare_rdb_cmd())
+        .subcommand(compare_aof_cmd())
+        .subcommand(compare_snapshot_cmd())
+        .subcommand(compare_verify_snapshot_cmd())
+        .subcommand(compare_config_cmd())
+        .subcommand(compare_runs_cmd())
+        .subcommand(compare_diff_runs_cmd())
+        .subcommand(compare_export_cmd())
+}
+
+fn compare_export_cmd() -> Command {
+    clap::Command::new("export")
+        .about("export iffy keys of a run as a script that makes target match source, values are read from source at export time")
+        .arg(arg!(<run> "run id"))
+        .arg(arg!(<output> "script file"))
+        .arg(results_root_arg())
+        .arg(sqlite_arg())
+        .arg(
+            Arg::new("format")
+                .long("format")
+                .value_parser(["text", "resp"])
+                .default_value("text")
+                .help("text for redis-cli, resp for redis-cli --pipe"),
+        )
+}
+
+fn compare_diff_runs_cmd() -> Command {
+    clap::Command::new("diff-runs")
+        .about("report newly failing, fixed and still failing keys between two runs")
+        .arg(arg!(<run_a> "earlier run id"))
+        .arg(arg!(<run_b> "later run id"))
+        .arg(results_root_arg())
+        .arg(sqlite_arg())
+}
+
+fn results_root_arg() -> Arg {
+    Arg::new("root")
+        .long("root")
+        .default_value(DEFAULT_RESULTS_ROOT)
+        .help("results root of the dir result store")
+}
+
+fn sqlite_arg() -> Arg {
+    Arg::new("sqlite")
+        .long("sqlite")
+        .help("sqlite result store file, used instead of the results root")
+}
+
+fn compare_runs_cmd() -> Command {
+    clap::Command::new("runs")
+        .about("manage runs in the dir result store, or in the sqlite result store with --sqlite")
+        .subcommand_required(true)
+        .subcommand(
+            clap::Command::new("list")
+                .about("list runs")
+                .arg(results_root_arg())
+                .arg(sqlite_arg()),
+        )
+        .subcommand(
+            clap::Command::new("show")
+                .about("print iffy keys of a run")
+                .arg(arg!(<run> "run id"))
+                .arg(results_root_arg())
+                .arg(sqlite_arg())
+                .arg(
+                    Arg::new("round")
+                        .long("round")
+                        .value_parser(clap::value_parser!(u32))
+                        .help("round of the run, 0 is the first compare, default the last round, the dir result store only keeps the last round"),
+                ),
+        )
+        .subcommand(
+            clap::Command::new("delete")
+                .about("delete a run and its results")
+                .arg(arg!(<run> "run id"))
+                .arg(results_root_arg())
+                .arg(sqlite_arg()),
+        )
+}
+
+fn compare_config_cmd() -> Command {
+    clap::Command::new("config")
+        .about("diff CONFIG GET *, selected INFO fields, function libraries and ACL users between source and target")
+        .arg(arg!(<file> "compare description file"))
+}
+
+fn compare_snapshot_cmd() -> Command {
+    clap::Command::new("snapshot")
+        .about("dump source keyspace of description file to a compressed snapshot file")
+        .arg(arg!(<file> "compare description file"))
+        .arg(arg!(<output> "snapshot file"))
+}
+
+fn compare_verify_snapshot_cmd() -> Command {
+    clap::Command::new("verify-snapshot")
+        .about("verify target of description file against a snapshot file")
+        .arg(arg!(<file> "compare description file"))
+        .arg(arg!(<snapshot> "snapshot file"))
+}
+
+fn compare_aof_cmd() -> Command {
+    clap::Command::new("aof")
+        .about("replay aof file into memory and compare with target of description file")
+        .arg(arg!(<aof> "aof file, manifest file or appenddirname directory"))
+        .arg(arg!(<file> "compare description file"))
+}
+
+fn compare_rdb_cmd() -> Command {
+    clap::Command::new("rdb")
+        .about("diff two rdb or snapshot files key by key without redis server")
+        .arg(arg!(<source> "source rdb or snapshot file"))
+        .arg(arg!(<target> "target rdb or snapshot file"))
+        .arg(
+            Arg::new("file")
+                .long("file")
+                .value_name("filepath")
+                .help("compare description file for batch_size, ttl_diff and result store"),
+        )
+        .arg(
+            Arg::new("ttl_diff")
+                .long("ttl-diff")
+                .value_parser(clap::value_parser!(usize))
+                .help("max ttl difference in seconds, overrides ttl_diff of the description file"),
+        )
+        .arg(
+            Arg::new("full_diff")
+                .long("full-diff")
+                .action(ArgAction::SetTrue)
+                .help("record every differing element of iffy keys"),
+        )
+}
+
+fn compare_slots_cmd() -> Command {
+    clap::Command::new("slots")
+        .about("per slot key count and digest report for cluster target")
+        .arg(arg!(<file> "compare description file"))
+        .arg(
+            Arg::new("restrict")
+                .long("restrict")
+                .value_name("filepath")
+                .help("write a compare description file restricted to mismatched slots"),
+        )
+}
+
+fn compare_summary_cmd() -> Command {
+    clap::Command::new("summary")
+        .about("quick keyspace pre-check by yaml description file before full compare")
+        .arg(arg!(<file> "compare description file"))
+        .arg(
+            Arg::new("sample")
+                .long("sample")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("1000")
+                .help("keys sampled per db for type statistics"),
+        )
+        .arg(
+            Arg::new("threshold")
+                .long("threshold")
+                .value_parser(clap::value_parser!(f64))
+                .default_value("0")
+                .help("max key count diff ratio between source and target"),
+        )
 }
 
 fn compare_execute_cmd() -> Command {
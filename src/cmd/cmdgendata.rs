@@ -41,6 +41,7 @@ pub fn gendata_continuous_template_cmd() -> Command {
         .about("create a generate continuous template file")
         .subcommand(gendata_continuous_template_single_cmd())
         .subcommand(gendata_continuous_template_cluster_cmd())
+        .subcommand(gendata_continuous_template_sentinel_cmd())
         .args(&[Arg::new("filepath").value_name("filepath").index(1)])
 }
 
@@ -56,6 +57,12 @@ pub fn gendata_continuous_template_cluster_cmd() -> Command {
         .args(&[Arg::new("filepath").value_name("filepath").index(1)])
 }
 
+pub fn gendata_continuous_template_sentinel_cmd() -> Command {
+    clap::Command::new("sentinel")
+        .about("create a generate continuous template file for sentinel redis instance")
+        .args(&[Arg::new("filepath").value_name("filepath").index(1)])
+}
+
 pub fn gendata_continuous_from_cmd() -> Command {
     clap::Command::new("from")
         .about("generate big key from a yaml file")
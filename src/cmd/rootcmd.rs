@@ -2,7 +2,11 @@ use crate::cmd::requestsample::new_requestsample_cmd;
 use crate::cmd::{new_compare_cmd, new_config_cmd};
 use crate::commons::CommandCompleter;
 use crate::commons::SubCmd;
-use crate::compare::{Compare, InstanceType, RedisInstance, ScenarioType, SourceInstance};
+use crate::compare::{
+    delete_run, delete_run_dir, diff_runs, export_repair_script, list_run_dirs, list_runs,
+    load_run, load_run_dir, summarize_run_diff, Compare, DBMapTarget, FailKeys, InstanceType,
+    RedisInstance, ScenarioType, ScriptFormat, SourceInstance,
+};
 use crate::configure::{self, get_config_file_path, Config};
 use crate::configure::{generate_default_config, set_config_file_path};
 use crate::util::{flash_struct_to_yaml_file, from_yaml_file_to_struct};
@@ -108,6 +112,88 @@ pub fn process_exists(pid: &u32) -> bool {
     return false;
 }
 
+// 每个校验失败的 key 输出一行 JSON，最后输出总数
+fn print_fail_keys(fail_keys: &[FailKeys]) {
+    let mut total = 0;
+    for fk in fail_keys {
+        for iffy in &fk.iffy_keys {
+            match serde_json::to_string(iffy) {
+                Ok(j) => println!("db{}: {}", fk.target.db, j),
+                Err(e) => eprintln!("{}", e),
+            }
+        }
+        total += fk.iffy_keys.len();
+    }
+    println!("iffy keys: {}", total);
+}
+
+// 读取运行某一轮的校验失败 key，round 为 None 时读取最后一轮，指定 sqlite 时 run 为数据库中的运行 ID
+fn load_run_round(
+    run: &str,
+    round: Option<u32>,
+    root: &str,
+    sqlite: Option<&String>,
+) -> anyhow::Result<Vec<FailKeys>> {
+    match sqlite {
+        Some(db) => load_run(db, run.parse::<i64>()?, round),
+        None => load_run_dir(root, run, round),
+    }
+}
+
+fn load_last_round(
+    run: &str,
+    root: &str,
+    sqlite: Option<&String>,
+) -> anyhow::Result<Vec<FailKeys>> {
+    load_run_round(run, None, root, sqlite)
+}
+
+// 输出结果存储中的全部运行
+fn print_runs(root: &str, sqlite: Option<&String>) -> anyhow::Result<()> {
+    if let Some(db) = sqlite {
+        for r in list_runs(db)? {
+            println!(
+                "run {}: started_at={} rounds={} iffy keys={}",
+                r.id, r.started_at, r.rounds, r.iffy_keys
+            );
+        }
+        return Ok(());
+    }
+    for r in list_run_dirs(root)? {
+        let iffy_keys = load_run_dir(root, &r.id, None)
+            .map(|fks| fks.iter().map(|fk| fk.iffy_keys.len()).sum::<usize>())
+            .unwrap_or(0);
+        println!(
+            "run {}: started_at={} rounds={} finished={} iffy keys={}",
+            r.id,
+            r.started_at,
+            r.rounds,
+            r.finished_at.is_some(),
+            iffy_keys
+        );
+    }
+    Ok(())
+}
+
+fn delete_store_run(run: &str, root: &str, sqlite: Option<&String>) -> anyhow::Result<()> {
+    match sqlite {
+        Some(db) => delete_run(db, run.parse::<i64>()?),
+        None => delete_run_dir(root, run),
+    }
+}
+
+// 离线比较的结果写入结果存储并输出，存在校验失败的 key 时以 1 退出
+fn report_fail_keys(compare: &Compare, fail_keys: &[FailKeys]) {
+    match compare.save_fail_keys(fail_keys) {
+        Ok(run_id) => println!("run id: {}", run_id),
+        Err(e) => eprintln!("{}", e),
+    }
+    print_fail_keys(fail_keys);
+    if fail_keys.iter().any(|fk| !fk.iffy_keys.is_empty()) {
+        std::process::exit(1);
+    }
+}
+
 fn cmd_match(matches: &ArgMatches) {
     if let Some(c) = matches.get_one::<String>("config") {
         set_config_file_path(c.to_string());
@@ -155,6 +241,7 @@ fn cmd_match(matches: &ArgMatches) {
                     ],
                     password: "".to_string(),
                     instance_type: InstanceType::Cluster,
+                    ..RedisInstance::default()
                 };
                 compare.target = target_instance;
                 compare.scenario = ScenarioType::Single2cluster;
@@ -172,8 +259,8 @@ fn cmd_match(matches: &ArgMatches) {
                 }
 
                 let mut compare = Compare::default();
-                let mut dbmapper: HashMap<usize, usize> = HashMap::new();
-                dbmapper.insert(0, 0);
+                let mut dbmapper: HashMap<usize, DBMapTarget> = HashMap::new();
+                dbmapper.insert(0, DBMapTarget::DB(0));
 
                 let source_instance = SourceInstance {
                     instance: RedisInstance {
@@ -184,8 +271,11 @@ fn cmd_match(matches: &ArgMatches) {
                         ],
                         password: "xxx".to_string(),
                         instance_type: InstanceType::Cluster,
+                        ..RedisInstance::default()
                     },
                     dbmapper,
+                    key_mapping: vec![],
+                    rdb_file: "".to_string(),
                 };
                 let target_instance = RedisInstance {
                     urls: vec![
@@ -195,6 +285,7 @@ fn cmd_match(matches: &ArgMatches) {
                     ],
                     password: "xxx".to_string(),
                     instance_type: InstanceType::Cluster,
+                    ..RedisInstance::default()
                 };
                 compare.source[0] = source_instance;
                 compare.target = target_instance;
@@ -212,11 +303,11 @@ fn cmd_match(matches: &ArgMatches) {
                     file = arg.to_string();
                 }
 
-                let mut dbmapper: HashMap<usize, usize> = HashMap::new();
+                let mut dbmapper: HashMap<usize, DBMapTarget> = HashMap::new();
 
-                dbmapper.insert(0, 1);
-                dbmapper.insert(3, 5);
-                dbmapper.insert(4, 4);
+                dbmapper.insert(0, DBMapTarget::DB(1));
+                dbmapper.insert(3, DBMapTarget::DB(5));
+                dbmapper.insert(4, DBMapTarget::DB(4));
 
                 let source_instance1 = SourceInstance {
                     instance: RedisInstance {
@@ -225,14 +316,17 @@ fn cmd_match(matches: &ArgMatches) {
                         ],
                         password: "".to_string(),
                         instance_type: InstanceType::Single,
+                        ..RedisInstance::default()
                     },
                     dbmapper: dbmapper.clone(),
+                    key_mapping: vec![],
+                    rdb_file: "".to_string(),
                 };
 
                 dbmapper.clear();
-                dbmapper.insert(0, 1);
-                dbmapper.insert(2, 5);
-                dbmapper.insert(4, 3);
+                dbmapper.insert(0, DBMapTarget::DB(1));
+                dbmapper.insert(2, DBMapTarget::DB(5));
+                dbmapper.insert(4, DBMapTarget::DB(3));
                 let source_instance2 = SourceInstance {
                     instance: RedisInstance {
                         urls: vec![
@@ -240,14 +334,17 @@ fn cmd_match(matches: &ArgMatches) {
                         ],
                         password: "".to_string(),
                         instance_type: InstanceType::Single,
+                        ..RedisInstance::default()
                     },
                     dbmapper: dbmapper.clone(),
+                    key_mapping: vec![],
+                    rdb_file: "".to_string(),
                 };
 
                 dbmapper.clear();
-                dbmapper.insert(2, 1);
-                dbmapper.insert(1, 5);
-                dbmapper.insert(4, 7);
+                dbmapper.insert(2, DBMapTarget::DB(1));
+                dbmapper.insert(1, DBMapTarget::DB(5));
+                dbmapper.insert(4, DBMapTarget::DB(7));
                 let source_instance3 = SourceInstance {
                     instance: RedisInstance {
                         urls: vec![
@@ -255,14 +352,18 @@ fn cmd_match(matches: &ArgMatches) {
                         ],
                         password: "".to_string(),
                         instance_type: InstanceType::Single,
+                        ..RedisInstance::default()
                     },
                     dbmapper: dbmapper.clone(),
+                    key_mapping: vec![],
+                    rdb_file: "".to_string(),
                 };
 
                 let target_instance = RedisInstance {
                     urls: vec!["redis://:password_target@127.0.0.1:6382/?timeout=1s".to_string()],
                     password: "".to_string(),
                     instance_type: InstanceType::Single,
+                    ..RedisInstance::default()
                 };
 
                 let mut compare = Compare::default();
@@ -285,11 +386,11 @@ fn cmd_match(matches: &ArgMatches) {
                     file = arg.to_string();
                 }
 
-                let mut dbmapper: HashMap<usize, usize> = HashMap::new();
+                let mut dbmapper: HashMap<usize, DBMapTarget> = HashMap::new();
 
-                dbmapper.insert(0, 1);
-                dbmapper.insert(2, 3);
-                dbmapper.insert(6, 6);
+                dbmapper.insert(0, DBMapTarget::DB(1));
+                dbmapper.insert(2, DBMapTarget::DB(3));
+                dbmapper.insert(6, DBMapTarget::DB(6));
 
                 let source_instance1 = SourceInstance {
                     instance: RedisInstance {
@@ -298,14 +399,17 @@ fn cmd_match(matches: &ArgMatches) {
                         ],
                         password: "".to_string(),
                         instance_type: InstanceType::Single,
+                        ..RedisInstance::default()
                     },
                     dbmapper: dbmapper.clone(),
+                    key_mapping: vec![],
+                    rdb_file: "".to_string(),
                 };
 
                 dbmapper.clear();
-                dbmapper.insert(0, 0);
-                dbmapper.insert(2, 5);
-                dbmapper.insert(4, 3);
+                dbmapper.insert(0, DBMapTarget::DB(0));
+                dbmapper.insert(2, DBMapTarget::DB(5));
+                dbmapper.insert(4, DBMapTarget::DB(3));
                 let source_instance2 = SourceInstance {
                     instance: RedisInstance {
                         urls: vec![
@@ -313,14 +417,17 @@ fn cmd_match(matches: &ArgMatches) {
                         ],
                         password: "".to_string(),
                         instance_type: InstanceType::Single,
+                        ..RedisInstance::default()
                     },
                     dbmapper: dbmapper.clone(),
+                    key_mapping: vec![],
+                    rdb_file: "".to_string(),
                 };
 
                 dbmapper.clear();
-                dbmapper.insert(2, 1);
-                dbmapper.insert(1, 5);
-                dbmapper.insert(4, 7);
+                dbmapper.insert(2, DBMapTarget::DB(1));
+                dbmapper.insert(1, DBMapTarget::DB(5));
+                dbmapper.insert(4, DBMapTarget::DB(7));
                 let source_instance3 = SourceInstance {
                     instance: RedisInstance {
                         urls: vec![
@@ -328,8 +435,11 @@ fn cmd_match(matches: &ArgMatches) {
                         ],
                         password: "".to_string(),
                         instance_type: InstanceType::Single,
+                        ..RedisInstance::default()
                     },
                     dbmapper: dbmapper.clone(),
+                    key_mapping: vec![],
+                    rdb_file: "".to_string(),
                 };
 
                 let target_instance = RedisInstance {
@@ -340,6 +450,7 @@ fn cmd_match(matches: &ArgMatches) {
                     ],
                     password: "xxx".to_string(),
                     instance_type: InstanceType::Cluster,
+                    ..RedisInstance::default()
                 };
 
                 let mut compare = Compare::default();
@@ -363,11 +474,11 @@ fn cmd_match(matches: &ArgMatches) {
                     file = arg.to_string();
                 }
 
-                let mut dbmapper: HashMap<usize, usize> = HashMap::new();
+                let mut dbmapper: HashMap<usize, DBMapTarget> = HashMap::new();
 
-                dbmapper.insert(0, 1);
-                dbmapper.insert(3, 5);
-                dbmapper.insert(4, 4);
+                dbmapper.insert(0, DBMapTarget::DB(1));
+                dbmapper.insert(3, DBMapTarget::DB(5));
+                dbmapper.insert(4, DBMapTarget::DB(4));
 
                 let source_instance1 = SourceInstance {
                     instance: RedisInstance {
@@ -376,14 +487,17 @@ fn cmd_match(matches: &ArgMatches) {
                         ],
                         password: "".to_string(),
                         instance_type: InstanceType::Single,
+                        ..RedisInstance::default()
                     },
                     dbmapper: dbmapper.clone(),
+                    key_mapping: vec![],
+                    rdb_file: "".to_string(),
                 };
 
                 dbmapper.clear();
-                dbmapper.insert(0, 1);
-                dbmapper.insert(2, 5);
-                dbmapper.insert(4, 3);
+                dbmapper.insert(0, DBMapTarget::DB(1));
+                dbmapper.insert(2, DBMapTarget::DB(5));
+                dbmapper.insert(4, DBMapTarget::DB(3));
                 let source_instance2 = SourceInstance {
                     instance: RedisInstance {
                         urls: vec![
@@ -391,12 +505,15 @@ fn cmd_match(matches: &ArgMatches) {
                         ],
                         password: "".to_string(),
                         instance_type: InstanceType::Single,
+                        ..RedisInstance::default()
                     },
                     dbmapper: dbmapper.clone(),
+                    key_mapping: vec![],
+                    rdb_file: "".to_string(),
                 };
 
                 dbmapper.clear();
-                dbmapper.insert(0, 1);
+                dbmapper.insert(0, DBMapTarget::DB(1));
                 let source_instance3 = SourceInstance {
                     instance: RedisInstance {
                         urls: vec![
@@ -406,14 +523,18 @@ fn cmd_match(matches: &ArgMatches) {
                         ],
                         password: "xxxx".to_string(),
                         instance_type: InstanceType::Cluster,
+                        ..RedisInstance::default()
                     },
                     dbmapper: dbmapper.clone(),
+                    key_mapping: vec![],
+                    rdb_file: "".to_string(),
                 };
 
                 let target_instance = RedisInstance {
                     urls: vec!["redis://:password_target@127.0.0.1:6382/?timeout=1s".to_string()],
                     password: "".to_string(),
                     instance_type: InstanceType::Single,
+                    ..RedisInstance::default()
                 };
 
                 let mut compare = Compare::default();
@@ -436,11 +557,24 @@ fn cmd_match(matches: &ArgMatches) {
                     file = arg.to_string();
                 }
 
-                let mut dbmapper: HashMap<usize, usize> = HashMap::new();
+                let mut dbmapper: HashMap<usize, DBMapTarget> = HashMap::new();
 
-                dbmapper.insert(1, 0);
-                dbmapper.insert(2, 0);
-                dbmapper.insert(6, 0);
+                dbmapper.insert(1, DBMapTarget::DB(0));
+                // 多个 db 合并到 cluster 时通过 key 前缀区分
+                dbmapper.insert(
+                    2,
+                    DBMapTarget::Prefix {
+                        db: 0,
+                        prefix: "db2:".to_string(),
+                    },
+                );
+                dbmapper.insert(
+                    6,
+                    DBMapTarget::Prefix {
+                        db: 0,
+                        prefix: "db6:".to_string(),
+                    },
+                );
 
                 let source_instance1 = SourceInstance {
                     instance: RedisInstance {
@@ -449,13 +583,16 @@ fn cmd_match(matches: &ArgMatches) {
                         ],
                         password: "".to_string(),
                         instance_type: InstanceType::Single,
+                        ..RedisInstance::default()
                     },
                     dbmapper: dbmapper.clone(),
+                    key_mapping: vec![],
+                    rdb_file: "".to_string(),
                 };
 
                 dbmapper.clear();
-                dbmapper.insert(0, 0);
-                dbmapper.insert(5, 0);
+                dbmapper.insert(0, DBMapTarget::DB(0));
+                dbmapper.insert(5, DBMapTarget::DB(0));
                 let source_instance2 = SourceInstance {
                     instance: RedisInstance {
                         urls: vec![
@@ -463,12 +600,15 @@ fn cmd_match(matches: &ArgMatches) {
                         ],
                         password: "".to_string(),
                         instance_type: InstanceType::Single,
+                        ..RedisInstance::default()
                     },
                     dbmapper: dbmapper.clone(),
+                    key_mapping: vec![],
+                    rdb_file: "".to_string(),
                 };
 
                 dbmapper.clear();
-                dbmapper.insert(0, 0);
+                dbmapper.insert(0, DBMapTarget::DB(0));
 
                 let source_instance3 = SourceInstance {
                     instance: RedisInstance {
@@ -479,8 +619,11 @@ fn cmd_match(matches: &ArgMatches) {
                         ],
                         password: "".to_string(),
                         instance_type: InstanceType::Cluster,
+                        ..RedisInstance::default()
                     },
                     dbmapper: dbmapper.clone(),
+                    key_mapping: vec![],
+                    rdb_file: "".to_string(),
                 };
 
                 let target_instance = RedisInstance {
@@ -491,6 +634,7 @@ fn cmd_match(matches: &ArgMatches) {
                     ],
                     password: "xxx".to_string(),
                     instance_type: InstanceType::Cluster,
+                    ..RedisInstance::default()
                 };
 
                 let mut compare = Compare::default();
@@ -522,6 +666,274 @@ fn cmd_match(matches: &ArgMatches) {
                 }
             }
         }
+
+        if let Some(slots) = compare.subcommand_matches("slots") {
+            let file = slots.get_one::<String>("file");
+            if let Some(path) = file {
+                let r = from_yaml_file_to_struct::<Compare>(path);
+                match r {
+                    Ok(mut compare) => match compare.slot_diff() {
+                        Ok(diffs) => {
+                            for d in &diffs {
+                                println!(
+                                    "slot {}: source count={} digest={:016x} target count={} digest={:016x}",
+                                    d.slot,
+                                    d.source.count,
+                                    d.source.digest,
+                                    d.target.count,
+                                    d.target.digest
+                                );
+                            }
+                            println!("mismatched slots: {}", diffs.len());
+
+                            if let Some(restrict) = slots.get_one::<String>("restrict") {
+                                compare.options.only_slots = diffs.iter().map(|d| d.slot).collect();
+                                match flash_struct_to_yaml_file(&compare, restrict) {
+                                    Ok(_) => println!("Create file {} Ok", restrict),
+                                    Err(e) => eprintln!("{}", e),
+                                };
+                            }
+                        }
+                        Err(e) => {
+                            eprintln!("{}", e);
+                        }
+                    },
+                    Err(e) => {
+                        eprintln!("{}", e);
+                    }
+                }
+            }
+        }
+
+        if let Some(rdb) = compare.subcommand_matches("rdb") {
+            let source = rdb.get_one::<String>("source");
+            let target = rdb.get_one::<String>("target");
+            if let (Some(s), Some(t)) = (source, target) {
+                let mut compare = match rdb.get_one::<String>("file") {
+                    Some(path) => match from_yaml_file_to_struct::<Compare>(path) {
+                        Ok(c) => c,
+                        Err(e) => {
+                            eprintln!("{}", e);
+                            std::process::exit(1);
+                        }
+                    },
+                    None => Compare::default(),
+                };
+                if let Some(ttl_diff) = rdb.get_one::<usize>("ttl_diff") {
+                    compare.ttl_diff = *ttl_diff;
+                }
+                if rdb.get_flag("full_diff") {
+                    compare.options.full_diff = true;
+                }
+                match compare.rdb_diff(s, t) {
+                    Ok(fail_keys) => report_fail_keys(&compare, &fail_keys),
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        std::process::exit(1);
+                    }
+                }
+            }
+        }
+
+        if let Some(aof) = compare.subcommand_matches("aof") {
+            let aof_path = aof.get_one::<String>("aof");
+            let file = aof.get_one::<String>("file");
+            if let (Some(aof_path), Some(path)) = (aof_path, file) {
+                let r = from_yaml_file_to_struct::<Compare>(path);
+                match r {
+                    Ok(compare) => match compare.aof_diff(aof_path) {
+                        Ok(fail_keys) => report_fail_keys(&compare, &fail_keys),
+                        Err(e) => {
+                            eprintln!("{}", e);
+                            std::process::exit(1);
+                        }
+                    },
+                    Err(e) => {
+                        eprintln!("{}", e);
+                    }
+                }
+            }
+        }
+
+        if let Some(snapshot) = compare.subcommand_matches("snapshot") {
+            let file = snapshot.get_one::<String>("file");
+            let output = snapshot.get_one::<String>("output");
+            if let (Some(path), Some(output)) = (file, output) {
+                let r = from_yaml_file_to_struct::<Compare>(path);
+                match r {
+                    Ok(compare) => match compare.snapshot(output) {
+                        Ok(count) => println!("Create snapshot {} with {} keys", output, count),
+                        Err(e) => {
+                            eprintln!("{}", e);
+                            std::process::exit(1);
+                        }
+                    },
+                    Err(e) => {
+                        eprintln!("{}", e);
+                    }
+                }
+            }
+        }
+
+        if let Some(verify) = compare.subcommand_matches("verify-snapshot") {
+            let file = verify.get_one::<String>("file");
+            let snapshot = verify.get_one::<String>("snapshot");
+            if let (Some(path), Some(snapshot)) = (file, snapshot) {
+                let r = from_yaml_file_to_struct::<Compare>(path);
+                match r {
+                    Ok(compare) => match compare.verify_snapshot(snapshot) {
+                        Ok(fail_keys) => report_fail_keys(&compare, &fail_keys),
+                        Err(e) => {
+                            eprintln!("{}", e);
+                            std::process::exit(1);
+                        }
+                    },
+                    Err(e) => {
+                        eprintln!("{}", e);
+                    }
+                }
+            }
+        }
+
+        if let Some(config) = compare.subcommand_matches("config") {
+            let file = config.get_one::<String>("file");
+            if let Some(path) = file {
+                let r = from_yaml_file_to_struct::<Compare>(path);
+                match r {
+                    Ok(compare) => match compare.config_diff() {
+                        Ok(fail_keys) => report_fail_keys(&compare, &fail_keys),
+                        Err(e) => {
+                            eprintln!("{}", e);
+                            std::process::exit(1);
+                        }
+                    },
+                    Err(e) => {
+                        eprintln!("{}", e);
+                    }
+                }
+            }
+        }
+
+        if let Some(runs) = compare.subcommand_matches("runs") {
+            if let Some(list) = runs.subcommand_matches("list") {
+                let root = list.get_one::<String>("root").map_or("", |r| r.as_str());
+                if let Err(e) = print_runs(root, list.get_one::<String>("sqlite")) {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                }
+            }
+            if let Some(show) = runs.subcommand_matches("show") {
+                let root = show.get_one::<String>("root").map_or("", |r| r.as_str());
+                let sqlite = show.get_one::<String>("sqlite");
+                if let Some(run) = show.get_one::<String>("run") {
+                    let round = show.get_one::<u32>("round").copied();
+                    match load_run_round(run, round, root, sqlite) {
+                        Ok(fail_keys) => print_fail_keys(&fail_keys),
+                        Err(e) => {
+                            eprintln!("{}", e);
+                            std::process::exit(1);
+                        }
+                    }
+                }
+            }
+            if let Some(delete) = runs.subcommand_matches("delete") {
+                let root = delete.get_one::<String>("root").map_or("", |r| r.as_str());
+                let sqlite = delete.get_one::<String>("sqlite");
+                if let Some(run) = delete.get_one::<String>("run") {
+                    match delete_store_run(run, root, sqlite) {
+                        Ok(_) => println!("run {} deleted", run),
+                        Err(e) => {
+                            eprintln!("{}", e);
+                            std::process::exit(1);
+                        }
+                    }
+                }
+            }
+        }
+
+        if let Some(diff) = compare.subcommand_matches("diff-runs") {
+            let root = diff.get_one::<String>("root").map_or("", |r| r.as_str());
+            let sqlite = diff.get_one::<String>("sqlite");
+            if let (Some(run_a), Some(run_b)) = (
+                diff.get_one::<String>("run_a"),
+                diff.get_one::<String>("run_b"),
+            ) {
+                let runs = load_last_round(run_a, root, sqlite)
+                    .and_then(|a| Ok((a, load_last_round(run_b, root, sqlite)?)));
+                match runs {
+                    Ok((a, b)) => {
+                        let entries = diff_runs(&a, &b);
+                        for e in &entries {
+                            match serde_json::to_string(e) {
+                                Ok(j) => println!("{}", j),
+                                Err(e) => eprintln!("{}", e),
+                            }
+                        }
+                        let summary = summarize_run_diff(&entries);
+                        println!(
+                            "new: {} fixed: {} still failing: {} error type changed: {}",
+                            summary.new,
+                            summary.fixed,
+                            summary.still_failing,
+                            summary.error_type_changed
+                        );
+                        if summary.new > 0 {
+                            std::process::exit(1);
+                        }
+                    }
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        std::process::exit(1);
+                    }
+                }
+            }
+        }
+
+        if let Some(export) = compare.subcommand_matches("export") {
+            let root = export.get_one::<String>("root").map_or("", |r| r.as_str());
+            let sqlite = export.get_one::<String>("sqlite");
+            let format = match export.get_one::<String>("format").map(|f| f.as_str()) {
+                Some("resp") => ScriptFormat::Resp,
+                _ => ScriptFormat::Text,
+            };
+            if let (Some(run), Some(output)) = (
+                export.get_one::<String>("run"),
+                export.get_one::<String>("output"),
+            ) {
+                let r = load_last_round(run, root, sqlite)
+                    .and_then(|fail_keys| export_repair_script(&fail_keys, output, format));
+                match r {
+                    Ok(summary) => println!(
+                        "deleted: {} rewritten: {} restored: {}",
+                        summary.deleted, summary.rewritten, summary.restored
+                    ),
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        std::process::exit(1);
+                    }
+                }
+            }
+        }
+
+        if let Some(summary) = compare.subcommand_matches("summary") {
+            let file = summary.get_one::<String>("file");
+            let sample = summary.get_one::<usize>("sample").copied().unwrap_or(1000);
+            let threshold = summary.get_one::<f64>("threshold").copied().unwrap_or(0.0);
+            if let Some(path) = file {
+                let r = from_yaml_file_to_struct::<Compare>(path);
+                match r {
+                    Ok(compare) => {
+                        if let Err(e) = compare.summary(sample, threshold) {
+                            eprintln!("{}", e);
+                            std::process::exit(1);
+                        }
+                    }
+                    Err(e) => {
+                        eprintln!("{}", e);
+                    }
+                }
+            }
+        }
     }
 
     if let Some(config) = matches.subcommand_matches("config") {
@@ -646,6 +1058,22 @@ fn cmd_match(matches: &ArgMatches) {
                     return;
                 }
 
+                if let Some(_sentinel) = template.subcommand_matches("sentinel") {
+                    println!("template sentinel redis");
+                    let mut template = GeneratorByDuration::default();
+                    template.redisinstance = RedisInstance::default_sentinel();
+                    let yml = serde_yaml::to_string(&template);
+                    match yml {
+                        Ok(y) => {
+                            println!("{}", y);
+                        }
+                        Err(e) => {
+                            eprintln!("{}", e);
+                        }
+                    }
+                    return;
+                }
+
                 let mut file = String::from("continuous_gen_data_template.yml");
                 if let Some(path) = template.get_one::<String>("filepath") {
                     file = path.to_string();
@@ -0,0 +1,189 @@
+use std::time::Instant;
+
+use redis::{ConnectionLike, RedisResult};
+use serde::{Deserialize, Serialize};
+
+use crate::util::{RedisKey, RedisKeyType};
+
+use super::comparekey::{CompareOptions, Comparer, IffyKey};
+
+// 大 key 处理策略
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "lowercase")]
+pub enum BigKeyPolicy {
+    // 在大 key 线程池中完整比较
+    Compare,
+    // 跳过，仅记录到报告
+    Skip,
+    // 比较元素数量与部分元素
+    Sample,
+}
+
+// 大 key 的大小与比较耗时，输出到运行报告
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BigKey {
+    pub key: RedisKey,
+    // 元素数量，string 为字节数，模块类型等未知类型为 0
+    pub size: usize,
+    // MEMORY USAGE，未开启内存检测时为 None
+    pub memory: Option<usize>,
+    pub policy: BigKeyPolicy,
+    // 比较耗时，单位毫秒
+    pub duration_ms: u64,
+    // 是否校验失败
+    pub iffy: bool,
+}
+
+// 未知类型没有通用的元素数量命令，仅按内存占用判断
+fn size_cmd(key_type: &RedisKeyType) -> Option<&'static str> {
+    match key_type {
+        RedisKeyType::TypeString => Some("strlen"),
+        RedisKeyType::TypeList => Some("llen"),
+        RedisKeyType::TypeSet => Some("scard"),
+        RedisKeyType::TypeZSet => Some("zcard"),
+        RedisKeyType::TypeHash => Some("hlen"),
+        RedisKeyType::TypeOther(_) => None,
+    }
+}
+
+// 通过 pipeline 获取 source key 的元素数量与内存占用，返回普通 key 与大 key
+pub fn split_big_keys(
+    keys: Vec<RedisKey>,
+    options: &CompareOptions,
+    conn: &mut dyn ConnectionLike,
+) -> RedisResult<(Vec<RedisKey>, Vec<BigKey>)> {
+    if !options.big_key_enabled() || keys.is_empty() {
+        return Ok((keys, vec![]));
+    }
+
+    let with_memory = options.big_key_memory > 0;
+    let mut pipe = redis::pipe();
+    for key in &keys {
+        if let Some(cmd) = size_cmd(&key.key_type) {
+            pipe.cmd(cmd).arg(&key.key_name);
+        }
+        if with_memory {
+            pipe.cmd("memory").arg("usage").arg(&key.key_name);
+        }
+    }
+    let values: Vec<Option<usize>> = pipe.query(conn)?;
+    let mut values = values.into_iter();
+
+    let mut normal = vec![];
+    let mut big = vec![];
+    for key in keys {
+        let size = match size_cmd(&key.key_type) {
+            Some(_) => values.next().flatten().unwrap_or(0),
+            None => 0,
+        };
+        let memory = match with_memory {
+            true => values.next().flatten(),
+            false => None,
+        };
+        let over_size = options.big_key_elements > 0 && size >= options.big_key_elements;
+        let over_memory = with_memory && memory.unwrap_or(0) >= options.big_key_memory;
+        if !over_size && !over_memory {
+            normal.push(key);
+            continue;
+        }
+        big.push(BigKey {
+            key,
+            size,
+            memory,
+            policy: options.big_key_policy.clone(),
+            duration_ms: 0,
+            iffy: false,
+        });
+    }
+    Ok((normal, big))
+}
+
+// 按策略比较大 key，记录每个 key 的耗时，返回校验失败的 key
+pub fn compare_big_keys(comparer: &mut Comparer, big_keys: &mut [BigKey]) -> Vec<IffyKey> {
+    let mut iffy_keys = vec![];
+    for big in big_keys.iter_mut() {
+        let start = Instant::now();
+        let iffy = match big.policy {
+            BigKeyPolicy::Skip => None,
+            BigKeyPolicy::Compare => comparer.compare_one(&big.key),
+            BigKeyPolicy::Sample => {
+                let sample = comparer.options.big_key_sample;
+                comparer
+                    .compare_key_sample(big.key.clone(), sample)
+                    .err()
+                    .map(|error| IffyKey {
+                        key: big.key.clone(),
+                        error,
+                        diff: None,
+                    })
+            }
+        };
+        big.duration_ms = start.elapsed().as_millis() as u64;
+        big.iffy = iffy.is_some();
+        log::info!("{:?}", big);
+        iffy_keys.extend(iffy);
+    }
+    iffy_keys
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::compare::KeyMapper;
+    use crate::util::{MemValue, MemoryConnection};
+
+    //cargo test compare::big_key::test::test_big_keys --  --nocapture
+    #[test]
+    fn test_big_keys() {
+        let mut source = MemoryConnection::default();
+        source.insert(b"small".to_vec(), MemValue::String(b"v".to_vec()), None);
+        let list: Vec<Vec<u8>> = (0..100).map(|i| i.to_string().into_bytes()).collect();
+        source.insert(b"big".to_vec(), MemValue::List(list.clone().into()), None);
+
+        let options = CompareOptions {
+            big_key_elements: 50,
+            big_key_memory: 1024,
+            big_key_policy: BigKeyPolicy::Sample,
+            big_key_sample: 10,
+            ..Default::default()
+        };
+        let keys = vec![
+            RedisKey {
+                key_name: "small".to_string(),
+                key_type: RedisKeyType::TypeString,
+            },
+            RedisKey {
+                key_name: "big".to_string(),
+                key_type: RedisKeyType::TypeList,
+            },
+        ];
+        let (normal, mut big) = split_big_keys(keys, &options, &mut source).unwrap();
+        assert_eq!(normal.len(), 1);
+        assert_eq!(big.len(), 1);
+        assert_eq!(big[0].size, 100);
+        assert!(big[0].memory.is_some());
+
+        // 抽样位置 0、10、20... 之外的差异不会被发现
+        let mut target = source.clone();
+        let mut changed = list.clone();
+        changed[5] = b"x".to_vec();
+        target.insert(b"big".to_vec(), MemValue::List(changed.into()), None);
+        let mut comparer = Comparer {
+            sconn: Box::new(source.clone()),
+            tconn: Box::new(target),
+            ttl_diff: 1,
+            batch: 10,
+            key_mapper: KeyMapper::default(),
+            options: options.clone(),
+        };
+        assert!(compare_big_keys(&mut comparer, &mut big).is_empty());
+
+        let mut target = source.clone();
+        let mut changed = list;
+        changed[10] = b"x".to_vec();
+        target.insert(b"big".to_vec(), MemValue::List(changed.into()), None);
+        comparer.tconn = Box::new(target);
+        assert_eq!(compare_big_keys(&mut comparer, &mut big).len(), 1);
+        assert!(big[0].iffy);
+    }
+}
@@ -0,0 +1,92 @@
+use anyhow::Result;
+
+use crate::aof::{AofPosition, AofReplayer};
+use crate::util::{key_exists, key_type, RedisKey};
+
+use super::{
+    compare_db::{compare_memory_with_target, FailKeys},
+    compare_error::CompareErrorType,
+    comparekey::IffyKey,
+    rediscompare::Compare,
+    CompareError,
+};
+
+// 在错误信息中附加最后修改 key 的 AOF 命令位置
+fn with_position(mut iffy: IffyKey, position: Option<&AofPosition>) -> IffyKey {
+    if let Some(p) = position {
+        let message = iffy.error.message.take().unwrap_or_default();
+        iffy.error.message = Some(format!(
+            "{} (last aof command at {}:{})",
+            message, p.file, p.offset
+        ));
+    }
+    iffy
+}
+
+impl Compare {
+    // 回放 AOF 后按 source 的 dbmapper、key_mapping 与 target 比较，source instance 配置被忽略
+    // 返回各 DB 对校验失败的 key，错误信息包含最后修改该 key 的 AOF 命令位置
+    pub fn aof_diff(&self, path: &str) -> Result<Vec<FailKeys>> {
+        let mut replayer = AofReplayer::default();
+        replayer.load(path)?;
+        for (cmd, count) in &replayer.unsupported {
+            log::error!("unsupported aof command {} appears {} times", cmd, count);
+        }
+
+        let mut result = vec![];
+        for (s, t) in self.map_dbinstance_source_to_target()? {
+            let mapper = s.key_mapper()?;
+            // 同一个 source DB 可能对应多个 target，不能从 replayer 中移出
+            let mem = replayer.dbs.get(&s.db).cloned().unwrap_or_default();
+            let mut iffy_keys = vec![];
+            let t_client = t.to_redis_client_with_db()?;
+
+            // AOF 中已删除或已过期的 key 在 target 中也不应存在
+            let mut t_conn = t_client.get_redis_connection()?.get_dyn_connection();
+            for ((_, key), position) in replayer.touched.range((s.db, vec![])..(s.db + 1, vec![])) {
+                if mem.get(key).is_some() {
+                    continue;
+                }
+                let key_name = String::from_utf8_lossy(key).to_string();
+                let t_key = mapper.to_target(&key_name);
+                if !self.options.slot_selected(&t_key) || !key_exists(&t_key, t_conn.as_mut())? {
+                    continue;
+                }
+                let key_type = match key_type(&t_key, t_conn.as_mut()) {
+                    Ok(kt) => kt,
+                    Err(e) => {
+                        log::error!("{}", e);
+                        continue;
+                    }
+                };
+                let iffy = IffyKey {
+                    key: RedisKey { key_name, key_type },
+                    error: CompareError {
+                        message: Some("key deleted by aof but exists in target".to_owned()),
+                        error_type: CompareErrorType::ExistsErr,
+                        reason: None,
+                    },
+                    diff: None,
+                };
+                iffy_keys.push(with_position(iffy, Some(position)));
+            }
+
+            let compared = compare_memory_with_target(
+                mem,
+                &s,
+                &t,
+                self.ttl_diff,
+                self.batch_size,
+                &self.options,
+            )?;
+            for iffy in compared {
+                let position = replayer
+                    .touched
+                    .get(&(s.db, iffy.key.key_name.as_bytes().to_vec()));
+                iffy_keys.push(with_position(iffy, position));
+            }
+            result.push(self.fail_keys(s, t, iffy_keys));
+        }
+        Ok(result)
+    }
+}
@@ -0,0 +1,289 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use anyhow::Result;
+use redis::{ConnectionLike, ErrorKind, RedisResult, Value};
+use regex::Regex;
+
+use crate::util::{fnv1a_64, get_instance_parameters, info, InfoSection, RedisKey, RedisKeyType};
+
+use super::{
+    compare_db::FailKeys,
+    compare_error::{CompareErrorReason, CompareErrorType},
+    comparekey::IffyKey,
+    rediscompare::{Compare, RedisInstance, RedisInstanceWithDB},
+    CompareError,
+};
+
+// 报告中参数的类型名，与 key 校验结果使用相同格式
+const CONFIG_KEY_TYPE: &str = "config";
+const INFO_KEY_TYPE: &str = "info";
+const FUNCTION_KEY_TYPE: &str = "function";
+const ACL_KEY_TYPE: &str = "acl";
+
+// 参数名到各节点取值的映射，相同取值去重
+type ParamValues = BTreeMap<String, BTreeSet<String>>;
+// 按 config、info、function、acl 分类的参数
+type InstanceValues = BTreeMap<&'static str, ParamValues>;
+
+// 低版本 redis 不支持 FUNCTION、ACL 命令，视为没有函数库或用户
+fn unsupported_as_empty<T: Default>(r: RedisResult<T>, cmd: &str) -> RedisResult<T> {
+    match r {
+        Err(e) if e.kind() == ErrorKind::ResponseError => {
+            log::warn!("{} not supported: {}", cmd, e);
+            Ok(T::default())
+        }
+        r => r,
+    }
+}
+
+// FUNCTION LIST WITHCODE，返回库名到代码 hash 的映射
+fn function_libraries(conn: &mut dyn ConnectionLike) -> RedisResult<BTreeMap<String, String>> {
+    let libraries: Vec<Value> = redis::cmd("function")
+        .arg("list")
+        .arg("withcode")
+        .query(conn)?;
+    let mut result = BTreeMap::new();
+    for library in libraries {
+        let fields: Vec<Value> = redis::from_redis_value(&library)?;
+        let mut name = None;
+        let mut code = None;
+        for pair in fields.chunks(2) {
+            if let [k, v] = pair {
+                match redis::from_redis_value::<String>(k)?.as_str() {
+                    "library_name" => name = Some(redis::from_redis_value::<String>(v)?),
+                    "library_code" => code = Some(redis::from_redis_value::<Vec<u8>>(v)?),
+                    _ => {}
+                }
+            }
+        }
+        if let Some(name) = name {
+            result.insert(
+                name,
+                format!("{:016x}", fnv1a_64(&code.unwrap_or_default())),
+            );
+        }
+    }
+    Ok(result)
+}
+
+// ACL LIST 每行为 "user <name> <rules>"，返回用户名到规则的映射，规则中包含密码 hash
+fn acl_users(conn: &mut dyn ConnectionLike) -> RedisResult<BTreeMap<String, String>> {
+    let lines: Vec<String> = redis::cmd("acl").arg("list").query(conn)?;
+    let mut result = BTreeMap::new();
+    for line in lines {
+        let mut parts = line.splitn(3, ' ').skip(1);
+        if let Some(name) = parts.next() {
+            result.insert(name.to_string(), parts.next().unwrap_or("").to_string());
+        }
+    }
+    Ok(result)
+}
+
+impl RedisInstance {
+    // 读取每个节点的 CONFIG GET *、INFO 中的指定字段、函数库与 ACL 用户
+    fn config_values(&self, info_fields: &[String]) -> Result<InstanceValues> {
+        let mut values = InstanceValues::new();
+        for client in self.to_single_redis_clients()? {
+            let mut conn = self.timeouts().single_connection(&client)?;
+            for (k, v) in get_instance_parameters(&mut conn)? {
+                let config = values.entry(CONFIG_KEY_TYPE).or_default();
+                config.entry(k).or_default().insert(v);
+            }
+            let sections = info(InfoSection::All, &mut conn)?;
+            for field in info_fields {
+                if let Some(v) = sections.values().find_map(|s| s.get(field)) {
+                    let infos = values.entry(INFO_KEY_TYPE).or_default();
+                    infos.entry(field.clone()).or_default().insert(v.clone());
+                }
+            }
+            let libraries = unsupported_as_empty(function_libraries(&mut conn), "function")?;
+            for (k, v) in libraries {
+                let functions = values.entry(FUNCTION_KEY_TYPE).or_default();
+                functions.entry(k).or_default().insert(v);
+            }
+            for (k, v) in unsupported_as_empty(acl_users(&mut conn), "acl")? {
+                let users = values.entry(ACL_KEY_TYPE).or_default();
+                users.entry(k).or_default().insert(v);
+            }
+        }
+        Ok(values)
+    }
+}
+
+fn join_values(values: Option<&BTreeSet<String>>) -> Option<String> {
+    values.map(|v| v.iter().cloned().collect::<Vec<String>>().join(","))
+}
+
+// 比较两端参数，名称或 "类型:名称" 匹配 ignore 的参数视为允许的差异
+fn diff_params(
+    kind: &str,
+    source: &ParamValues,
+    target: &ParamValues,
+    ignore: &[Regex],
+) -> Vec<IffyKey> {
+    let names: BTreeSet<&String> = source.keys().chain(target.keys()).collect();
+    let mut iffy_keys = vec![];
+    for name in names {
+        let full_name = format!("{}:{}", kind, name);
+        if ignore
+            .iter()
+            .any(|r| r.is_match(name) || r.is_match(&full_name))
+        {
+            continue;
+        }
+        let (s, t) = (source.get(name), target.get(name));
+        if s == t {
+            continue;
+        }
+        let key = RedisKey {
+            key_name: name.clone(),
+            key_type: RedisKeyType::TypeOther(kind.to_string()),
+        };
+        let reason = CompareErrorReason {
+            redis_key: key.clone(),
+            position: None,
+            source: join_values(s),
+            target: join_values(t),
+        };
+        iffy_keys.push(IffyKey {
+            key,
+            error: CompareError::from_reason(reason, CompareErrorType::ConfigValueDiff),
+            diff: None,
+        });
+    }
+    iffy_keys
+}
+
+impl Compare {
+    // 比较 source 与 target 的 CONFIG GET *、config_info_fields 指定的 INFO 字段、
+    // FUNCTION LIST 中的函数库代码与 ACL LIST 中的用户规则
+    // 多个 source 实例的取值合并后与 target 比较，RDB source 没有运行中的实例，不参与比较
+    // 结果为一组 FailKeys，参与比较的 source 实例与 target 均记为 DB 0
+    pub fn config_diff(&self) -> Result<Vec<FailKeys>> {
+        let mut ignore = vec![];
+        for pattern in &self.config_allow_diff {
+            ignore.push(Regex::new(pattern)?);
+        }
+
+        let mut s_values = InstanceValues::new();
+        let mut instances: Vec<&RedisInstance> = vec![];
+        for s in &self.source {
+            if !s.rdb_file.is_empty() || instances.contains(&&s.instance) {
+                continue;
+            }
+            instances.push(&s.instance);
+            for (kind, params) in s.instance.config_values(&self.config_info_fields)? {
+                let merged = s_values.entry(kind).or_default();
+                for (k, v) in params {
+                    merged.entry(k).or_default().extend(v);
+                }
+            }
+        }
+        let t_values = self.target.config_values(&self.config_info_fields)?;
+
+        let empty = ParamValues::new();
+        let mut iffy_keys = vec![];
+        for kind in [
+            CONFIG_KEY_TYPE,
+            INFO_KEY_TYPE,
+            FUNCTION_KEY_TYPE,
+            ACL_KEY_TYPE,
+        ] {
+            let s = s_values.get(kind).unwrap_or(&empty);
+            let t = t_values.get(kind).unwrap_or(&empty);
+            iffy_keys.append(&mut diff_params(kind, s, t, &ignore));
+        }
+
+        let mut fail_keys = self.fail_keys(
+            RedisInstanceWithDB::default(),
+            RedisInstanceWithDB {
+                instance: self.target.clone(),
+                ..Default::default()
+            },
+            iffy_keys,
+        );
+        fail_keys.source = instances
+            .into_iter()
+            .map(|i| RedisInstanceWithDB {
+                instance: i.clone(),
+                ..Default::default()
+            })
+            .collect();
+        Ok(vec![fail_keys])
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn params(kv: &[(&str, &str)]) -> ParamValues {
+        let mut values = ParamValues::new();
+        for (k, v) in kv {
+            values
+                .entry(k.to_string())
+                .or_default()
+                .insert(v.to_string());
+        }
+        values
+    }
+
+    //cargo test compare::compare_config::test::test_diff_params --  --nocapture
+    #[test]
+    fn test_diff_params() {
+        let source = params(&[
+            ("maxmemory-policy", "allkeys-lru"),
+            ("notify-keyspace-events", "Ex"),
+            ("port", "6379"),
+            ("hash-max-listpack-entries", "128"),
+        ]);
+        let target = params(&[
+            ("maxmemory-policy", "noeviction"),
+            ("maxmemory-policy", "allkeys-lru"),
+            ("notify-keyspace-events", "Ex"),
+            ("port", "6380"),
+        ]);
+        let ignore = vec![Regex::new("^port$").unwrap()];
+
+        let iffy = diff_params(CONFIG_KEY_TYPE, &source, &target, &ignore);
+        let result: Vec<(String, Option<String>, Option<String>)> = iffy
+            .iter()
+            .map(|i| {
+                let reason = i.error.reason.clone().unwrap();
+                (i.key.key_name.clone(), reason.source, reason.target)
+            })
+            .collect();
+        assert_eq!(
+            result,
+            vec![
+                (
+                    "hash-max-listpack-entries".to_string(),
+                    Some("128".to_string()),
+                    None
+                ),
+                (
+                    "maxmemory-policy".to_string(),
+                    Some("allkeys-lru".to_string()),
+                    Some("allkeys-lru,noeviction".to_string())
+                ),
+            ]
+        );
+        assert_eq!(iffy[0].key.key_type.to_string(), CONFIG_KEY_TYPE);
+
+        // 按 "类型:名称" 忽略 default 用户
+        let source = params(&[
+            ("default", "on nopass ~* +@all"),
+            ("app", "on #a1 ~app:* +get"),
+        ]);
+        let target = params(&[
+            ("default", "on #b2 ~* +@all"),
+            ("app", "on #a1 ~app:* +get"),
+        ]);
+        let ignore = vec![Regex::new("^acl:default$").unwrap()];
+        assert!(diff_params(ACL_KEY_TYPE, &source, &target, &ignore).is_empty());
+        assert_eq!(
+            diff_params(CONFIG_KEY_TYPE, &source, &target, &ignore).len(),
+            1
+        );
+    }
+}
@@ -1,4 +1,9 @@
-use std::{fs::OpenOptions, io::Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::{
+    fs::{self, OpenOptions},
+    io::Write,
+};
 
 use anyhow::anyhow;
 use anyhow::Result;
@@ -7,14 +12,21 @@ use redis::{ConnectionLike, RedisResult};
 use rmp_serde::Serializer;
 use serde::{Deserialize, Serialize};
 
+use crate::rdb::{load_db, RdbReader};
 use crate::util::rand_lettter_number_string;
-use crate::util::{key_type, scan, RedisClientWithDB, RedisConnection, RedisKey};
+use crate::util::{
+    key_type, scan, MemValue, MemoryConnection, RedisClient, RedisClientWithDB, RedisConnection,
+    RedisKey,
+};
 
 use super::{
+    big_key::{compare_big_keys, split_big_keys, BigKey, BigKeyPolicy},
     compare_error::CompareErrorType,
-    comparekey::{Comparer, IffyKey},
+    comparekey::{CompareOptions, Comparer, IffyKey},
+    progress::PairProgress,
     rediscompare::RedisInstanceWithDB,
-    CompareError, InstanceType,
+    result_store::{ResultStore, FIRST_ROUND},
+    CompareError, InstanceType, KeyMapper,
 };
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -26,6 +38,9 @@ pub struct FailKeys {
     pub batch: usize,
     // 是否为反向校验
     pub reverse: bool,
+    // 旧版本的结果文件没有该字段，使用默认校验选项
+    #[serde(default)]
+    pub options: CompareOptions,
 }
 
 impl FailKeys {
@@ -55,6 +70,25 @@ impl FailKeys {
         Ok(())
     }
 
+    pub fn from_file(path: &str) -> Result<FailKeys> {
+        let buf = fs::read(path)?;
+        Ok(rmp_serde::from_slice::<FailKeys>(&buf)?)
+    }
+
+    // 复查校验失败的 key，返回仍然失败的 key
+    pub fn recheck(&self) -> Result<FailKeys> {
+        let iffies = self.compare()?;
+        Ok(FailKeys {
+            source: self.source.clone(),
+            target: self.target.clone(),
+            iffy_keys: iffies,
+            ttl_diff: self.ttl_diff,
+            batch: self.batch,
+            reverse: self.reverse,
+            options: self.options.clone(),
+        })
+    }
+
     // 正向校验
     pub fn compare(&self) -> Result<Vec<IffyKey>> {
         let mut keys = vec![];
@@ -70,13 +104,15 @@ impl FailKeys {
                     .get_redis_connection()?;
 
                 let mut s_conns = vec![];
+                let mut s_mappers = vec![];
                 for instace in &self.source {
                     let client = instace.to_redis_client_with_db()?;
                     let conn = client.get_redis_connection()?;
                     s_conns.push(conn);
+                    s_mappers.push(instace.key_mapper()?);
                 }
 
-                Ok(keys_exists_reverse(t_conn, s_conns, &keys))
+                Ok(keys_exists_reverse(t_conn, s_conns, &s_mappers, &keys))
             }
             false => {
                 // 执行正向校验
@@ -84,13 +120,24 @@ impl FailKeys {
                     return Err(anyhow!("source vec len must be 1"));
                 }
 
-                let s_client = self.source[0].to_redis_client_with_db()?;
                 let t_client = self.target.to_redis_client_with_db()?;
-
-                let s_conn = s_client.get_redis_connection()?;
                 let t_conn = t_client.get_redis_connection()?;
 
-                let s_dyn_conn = s_conn.get_dyn_connection();
+                // source 为 RDB 文件时只载入待复查的 key
+                let s_dyn_conn: Box<dyn ConnectionLike> = match self.source[0].rdb_file.is_empty() {
+                    true => {
+                        let s_client = self.source[0].to_redis_client_with_db()?;
+                        s_client.get_redis_connection()?.get_dyn_connection()
+                    }
+                    false => {
+                        let names = keys.iter().map(|k| k.key_name.clone()).collect();
+                        Box::new(load_db(
+                            &self.source[0].rdb_file,
+                            self.source[0].db,
+                            &names,
+                        )?)
+                    }
+                };
                 let t_dyn_conn = t_conn.get_dyn_connection();
 
                 let comparer = Comparer {
@@ -98,6 +145,8 @@ impl FailKeys {
                     tconn: t_dyn_conn,
                     ttl_diff: self.ttl_diff,
                     batch: self.batch,
+                    key_mapper: self.source[0].key_mapper()?,
+                    options: self.source[0].source_options(&self.options),
                 };
 
                 Ok(comparer.compare_rediskeys(&keys))
@@ -113,11 +162,23 @@ pub struct CompareDB {
     pub batch: usize,
     pub ttl_diff: usize,
     pub compare_pool: usize,
-    pub result_store_dir: String,
+    // 大 key 线程池大小
+    pub big_key_pool: usize,
+    pub result_store: Arc<dyn ResultStore>,
+    pub options: CompareOptions,
+    // 检测到的大 key，用于生成运行报告
+    pub big_keys: Arc<Mutex<Vec<BigKey>>>,
+    // 扫描、比较、校验失败的 key 数量
+    pub progress: Arc<PairProgress>,
 }
 
 impl CompareDB {
     pub fn exec(&self) {
+        if !self.source.rdb_file.is_empty() {
+            exec_rdb_group(std::slice::from_ref(self));
+            return;
+        }
+
         // 判断 source 是否为 单实例 redis，cluster 模式不支持 sacn
         if let InstanceType::Cluster = self.source.instance.instance_type {
             log::error!("source redis instance is not single redis instance");
@@ -143,6 +204,17 @@ impl CompareDB {
             }
         };
 
+        let pool_big = match rayon::ThreadPoolBuilder::new()
+            .num_threads(self.big_key_pool)
+            .build()
+        {
+            Ok(p) => p,
+            Err(e) => {
+                log::error!("{}", e);
+                return;
+            }
+        };
+
         let t_client = match self.target.instance.to_redis_client() {
             Ok(rc) => rc,
             Err(e) => {
@@ -151,125 +223,343 @@ impl CompareDB {
             }
         };
 
-        pool_compare.scope(move |pc| {
-            let s_scan_conn = match s_client.get_redis_connection() {
-                Ok(ssc) => ssc,
-                Err(e) => {
-                    log::error!("{}", e);
-                    return;
-                }
-            };
-
-            let mut sscan: Box<dyn ConnectionLike> = s_scan_conn.get_dyn_connection();
-            let redis_cmd_select = redis::cmd("select");
-            if let Err(e) = sscan
-                .as_mut()
-                .req_command(redis_cmd_select.clone().arg(self.source.db))
-            {
+        let key_mapper = match self.source.key_mapper() {
+            Ok(m) => m,
+            Err(e) => {
                 log::error!("{}", e);
                 return;
-            };
-
-            let scan_iter = match scan::<String>(sscan.as_mut()) {
-                Ok(iter) => iter,
-                Err(e) => {
-                    log::error!("{}", e);
-                    return;
-                }
-            };
-
-            let mut vec_keys: Vec<String> = Vec::new();
-            let mut count = 0 as usize;
-            for key in scan_iter {
-                if count < self.batch {
-                    vec_keys.push(key.clone());
-                    count += 1;
-                    continue;
-                }
+            }
+        };
 
-                let s_redis_conn = match s_client.get_redis_connection() {
-                    Ok(c) => c,
+        pool_big.scope(|pb| {
+            pool_compare.scope(move |pc| {
+                let s_scan_conn = match s_client.get_redis_connection() {
+                    Ok(ssc) => ssc,
                     Err(e) => {
                         log::error!("{}", e);
-                        break;
+                        return;
                     }
                 };
 
-                let t_redis_conn = match t_client.get_redis_connection() {
-                    Ok(tc) => tc,
-                    Err(e) => {
-                        log::error!("{}", e);
-                        break;
-                    }
+                let mut sscan: Box<dyn ConnectionLike> = s_scan_conn.get_dyn_connection();
+                let redis_cmd_select = redis::cmd("select");
+                if let Err(e) = sscan
+                    .as_mut()
+                    .req_command(redis_cmd_select.clone().arg(self.source.db))
+                {
+                    log::error!("{}", e);
+                    return;
                 };
+                match redis::cmd("dbsize").query::<u64>(sscan.as_mut()) {
+                    Ok(n) => self.progress.set_total(n),
+                    Err(e) => log::warn!("{}", e),
+                }
 
-                let vk = vec_keys.clone();
-                pc.spawn(move |_| {
-                    self.compare_keys(s_redis_conn, t_redis_conn, vk);
-                });
-
-                count = 0;
-                vec_keys.clear();
-                vec_keys.push(key.clone());
-                count += 1;
-            }
-
-            if !vec_keys.is_empty() {
-                let s_redis_conn = match s_client.get_redis_connection() {
-                    Ok(c) => c,
+                let scan_iter = match scan::<String>(sscan.as_mut()) {
+                    Ok(iter) => iter,
                     Err(e) => {
                         log::error!("{}", e);
                         return;
                     }
                 };
 
-                let t_redis_conn = match t_client.get_redis_connection() {
-                    Ok(tc) => tc,
-                    Err(e) => {
-                        log::error!("{}", e);
-                        return;
+                let mut vec_keys: Vec<String> = Vec::new();
+                let mut count = 0 as usize;
+                for key in scan_iter {
+                    self.progress.add_scanned(1);
+                    // 跳过不属于 only_slots 的 key
+                    if !self.options.slot_selected(&key_mapper.to_target(&key)) {
+                        self.progress.add_skipped(1);
+                        continue;
+                    }
+                    if count < self.batch {
+                        vec_keys.push(key.clone());
+                        count += 1;
+                        continue;
                     }
-                };
 
-                let vk = vec_keys;
+                    let s_redis_conn = match s_client.get_redis_connection() {
+                        Ok(c) => c,
+                        Err(e) => {
+                            log::error!("{}", e);
+                            break;
+                        }
+                    };
 
-                pc.spawn(move |_| {
-                    self.compare_keys(s_redis_conn, t_redis_conn, vk);
-                });
+                    let t_redis_conn = match t_client.get_redis_connection() {
+                        Ok(tc) => tc,
+                        Err(e) => {
+                            log::error!("{}", e);
+                            break;
+                        }
+                    };
+
+                    let vk = vec_keys.clone();
+                    let (s_big, t_big) = (s_client.clone(), t_client.clone());
+                    pc.spawn(move |_| {
+                        let big_keys = self.compare_keys(s_redis_conn, t_redis_conn, vk);
+                        self.spawn_big_keys(pb, t_big, big_keys, move || {
+                            self.select_source_db(s_big.get_redis_connection().ok()?)
+                        });
+                    });
+
+                    count = 0;
+                    vec_keys.clear();
+                    vec_keys.push(key.clone());
+                    count += 1;
+                }
+
+                if !vec_keys.is_empty() {
+                    let s_redis_conn = match s_client.get_redis_connection() {
+                        Ok(c) => c,
+                        Err(e) => {
+                            log::error!("{}", e);
+                            return;
+                        }
+                    };
+
+                    let t_redis_conn = match t_client.get_redis_connection() {
+                        Ok(tc) => tc,
+                        Err(e) => {
+                            log::error!("{}", e);
+                            return;
+                        }
+                    };
+
+                    let vk = vec_keys;
+                    let (s_big, t_big) = (s_client.clone(), t_client.clone());
+                    pc.spawn(move |_| {
+                        let big_keys = self.compare_keys(s_redis_conn, t_redis_conn, vk);
+                        self.spawn_big_keys(pb, t_big, big_keys, move || {
+                            self.select_source_db(s_big.get_redis_connection().ok()?)
+                        });
+                    });
+                }
+            });
+        });
+    }
+
+    // 提交一批内存中的 key 到比较线程池，在途批次达到上限时在当前线程比较
+    fn dispatch_memory_batch<'b, 'c>(
+        &'b self,
+        pc: &rayon::Scope<'c>,
+        pb: &'c rayon::Scope<'b>,
+        t_client: &RedisClient,
+        batch: MemoryConnection,
+        in_flight: &'c AtomicUsize,
+        max_in_flight: usize,
+    ) where
+        'b: 'c,
+    {
+        let t_redis_conn = match t_client.get_redis_connection() {
+            Ok(tc) => tc,
+            Err(e) => {
+                log::error!("{}", e);
+                return;
             }
+        };
+        let t_big = t_client.clone();
+        let compare = move || {
+            let (big_source, big_keys) = self.compare_memory_keys(batch, t_redis_conn);
+            self.spawn_big_keys(pb, t_big, big_keys, move || Some(Box::new(big_source)));
+        };
+
+        if in_flight.load(Ordering::Acquire) >= max_in_flight {
+            compare();
+            return;
+        }
+        in_flight.fetch_add(1, Ordering::AcqRel);
+        pc.spawn(move |_| {
+            compare();
+            in_flight.fetch_sub(1, Ordering::AcqRel);
         });
     }
 
+    // 比较内存中的一批 key，返回移出的大 key 及其数据
+    fn compare_memory_keys(
+        &self,
+        mut source: MemoryConnection,
+        target: RedisConnection,
+    ) -> (MemoryConnection, Vec<BigKey>) {
+        let rediskeys: Vec<RedisKey> = source
+            .keys()
+            .iter()
+            .filter_map(|k| {
+                source.get(k).map(|e| RedisKey {
+                    key_name: String::from_utf8_lossy(k).to_string(),
+                    key_type: e.value.key_type(),
+                })
+            })
+            .collect();
+        let count = rediskeys.len() as u64;
+        let (rediskeys, big_keys) = self.detect_big_keys(rediskeys, &mut source);
+
+        let mut big_source = MemoryConnection::default();
+        for big in &big_keys {
+            let key = big.key.key_name.as_bytes();
+            if let Some(entry) = source.entries.remove(key) {
+                big_source.entries.insert(key.to_vec(), entry);
+            }
+        }
+        self.compare_source_keys(Box::new(source), target, rediskeys);
+        // 大 key 在大 key 线程池中比较，随所在批次计入已比较数量
+        self.progress.add_compared(count);
+        (big_source, big_keys)
+    }
+
     /// .用与进行keys批量正向校验
     /// 正向校验判断 key 在 target 是否存在，校验key的值是否相等以及source 和 target 的 ttl 差值是否在合理范围内
-    fn compare_keys(&self, source: RedisConnection, target: RedisConnection, keys: Vec<String>) {
+    /// 返回未比较的大 key
+    fn compare_keys(
+        &self,
+        source: RedisConnection,
+        target: RedisConnection,
+        keys: Vec<String>,
+    ) -> Vec<BigKey> {
+        let mut sconn = match self.select_source_db(source) {
+            Some(c) => c,
+            None => return vec![],
+        };
+
+        let count = keys.len() as u64;
+        let rediskeys = keys_type(keys, sconn.as_mut());
+        let (rediskeys, big_keys) = self.detect_big_keys(rediskeys, sconn.as_mut());
+        self.compare_source_keys(sconn, target, rediskeys);
+        // 大 key 在大 key 线程池中比较，随所在批次计入已比较数量
+        self.progress.add_compared(count);
+        big_keys
+    }
+
+    fn select_source_db(&self, source: RedisConnection) -> Option<Box<dyn ConnectionLike>> {
         let cmd_select = redis::cmd("select");
         let mut sconn = source.get_dyn_connection();
-        let mut tconn: Box<dyn ConnectionLike> = target.get_dyn_connection();
 
         if let Err(e) = sconn.req_command(cmd_select.clone().arg(self.source.db)) {
             log::error!("{}", e);
-            return;
+            return None;
         };
+        Some(sconn)
+    }
+
+    // 检测大 key，检测失败时全部按普通 key 比较
+    fn detect_big_keys(
+        &self,
+        keys: Vec<RedisKey>,
+        sconn: &mut dyn ConnectionLike,
+    ) -> (Vec<RedisKey>, Vec<BigKey>) {
+        match split_big_keys(keys.clone(), &self.options, sconn) {
+            Ok(r) => r,
+            Err(e) => {
+                log::error!("{}", e);
+                (keys, vec![])
+            }
+        }
+    }
+
+    // 大 key 提交到大 key 线程池，使用新的 source 连接比较，skip 策略仅记录到报告
+    fn spawn_big_keys<'s, F>(
+        &'s self,
+        pb: &rayon::Scope<'s>,
+        t_client: RedisClient,
+        big_keys: Vec<BigKey>,
+        source: F,
+    ) where
+        F: FnOnce() -> Option<Box<dyn ConnectionLike>> + Send + 's,
+    {
+        if big_keys.is_empty() {
+            return;
+        }
+        if let BigKeyPolicy::Skip = self.options.big_key_policy {
+            self.report_big_keys(big_keys);
+            return;
+        }
+
+        pb.spawn(move |_| {
+            let sconn = match source() {
+                Some(c) => c,
+                None => {
+                    log::error!("get source connection for big keys failed");
+                    return;
+                }
+            };
+            let t_redis_conn = match t_client.get_redis_connection() {
+                Ok(tc) => tc,
+                Err(e) => {
+                    log::error!("{}", e);
+                    return;
+                }
+            };
+            let mut big_keys = big_keys;
+            if let Some(mut comparer) = self.comparer(sconn, t_redis_conn) {
+                let iffy_keys = compare_big_keys(&mut comparer, &mut big_keys);
+                self.store_iffy_keys(iffy_keys);
+            }
+            self.report_big_keys(big_keys);
+        });
+    }
+
+    fn report_big_keys(&self, big_keys: Vec<BigKey>) {
+        for big in &big_keys {
+            log::info!("big key {:?}", big);
+        }
+        match self.big_keys.lock() {
+            Ok(mut report) => report.extend(big_keys),
+            Err(e) => log::error!("{}", e),
+        }
+    }
+
+    fn comparer(
+        &self,
+        sconn: Box<dyn ConnectionLike>,
+        target: RedisConnection,
+    ) -> Option<Comparer> {
+        let cmd_select = redis::cmd("select");
+        let mut tconn: Box<dyn ConnectionLike> = target.get_dyn_connection();
 
-        if let InstanceType::Single = self.target.instance.instance_type {
+        if let InstanceType::Single | InstanceType::Sentinel = self.target.instance.instance_type {
             if let Err(e) = tconn.req_command(cmd_select.clone().arg(self.target.db)) {
                 log::error!("{}", e);
-                return;
+                return None;
             };
         }
 
-        let rediskeys = keys_type(keys, sconn.as_mut());
-        let comparer = Comparer {
+        let key_mapper = match self.source.key_mapper() {
+            Ok(m) => m,
+            Err(e) => {
+                log::error!("{}", e);
+                return None;
+            }
+        };
+
+        Some(Comparer {
             sconn,
             tconn,
             ttl_diff: self.ttl_diff,
             batch: self.batch,
+            key_mapper,
+            options: self.source.source_options(&self.options),
+        })
+    }
+
+    fn compare_source_keys(
+        &self,
+        sconn: Box<dyn ConnectionLike>,
+        target: RedisConnection,
+        rediskeys: Vec<RedisKey>,
+    ) {
+        let comparer = match self.comparer(sconn, target) {
+            Some(c) => c,
+            None => return,
         };
 
         // ToDo 错误输出内置到 compare_rediskeys 函数
         let iffy_keys = comparer.compare_rediskeys(&rediskeys);
+        self.store_iffy_keys(iffy_keys);
+    }
+
+    fn store_iffy_keys(&self, iffy_keys: Vec<IffyKey>) {
         if !iffy_keys.is_empty() {
+            self.progress.add_mismatched(iffy_keys.len() as u64);
             let cfk = FailKeys {
                 iffy_keys,
                 source: vec![self.source.clone()],
@@ -277,9 +567,10 @@ impl CompareDB {
                 reverse: false,
                 ttl_diff: self.ttl_diff,
                 batch: self.batch,
+                options: self.options.clone(),
             };
             log::error!("{:?}", cfk);
-            if let Err(e) = cfk.write_to_file(&self.result_store_dir) {
+            if let Err(e) = self.result_store.save(FIRST_ROUND, &cfk) {
                 log::error!("{}", e);
             };
         }
@@ -294,7 +585,10 @@ pub struct CompareDBReverse {
     pub batch: usize,
     pub ttl_diff: usize,
     pub compare_pool: usize,
-    pub result_store_dir: String,
+    pub result_store: Arc<dyn ResultStore>,
+    pub options: CompareOptions,
+    // 扫描、比较、校验失败的 key 数量
+    pub progress: Arc<PairProgress>,
 }
 
 impl CompareDBReverse {
@@ -336,6 +630,10 @@ impl CompareDBReverse {
                 }
             }
             .get_dyn_connection();
+            match redis::cmd("dbsize").query::<u64>(t_scan_conn.as_mut()) {
+                Ok(n) => self.progress.set_total(n),
+                Err(e) => log::warn!("{}", e),
+            }
 
             let t_scan_iter = match scan::<String>(t_scan_conn.as_mut()) {
                 Ok(iter) => iter,
@@ -348,6 +646,11 @@ impl CompareDBReverse {
             let mut vec_keys: Vec<String> = Vec::new();
             let mut count = 0 as usize;
             for key in t_scan_iter {
+                self.progress.add_scanned(1);
+                if !self.options.slot_selected(&key) {
+                    self.progress.add_skipped(1);
+                    continue;
+                }
                 if count < self.batch {
                     vec_keys.push(key.clone());
                     count += 1;
@@ -417,12 +720,26 @@ impl CompareDBReverse {
         source_conns: Vec<RedisConnection>,
         keys: Vec<String>,
     ) {
+        let mut s_mappers = vec![];
+        for s in &self.source {
+            match s.key_mapper() {
+                Ok(m) => s_mappers.push(m),
+                Err(e) => {
+                    log::error!("{}", e);
+                    return;
+                }
+            }
+        }
+
         let mut t_conn = target_conn.get_dyn_connection();
 
+        let count = keys.len() as u64;
         let rediskeys = keys_type(keys, t_conn.as_mut());
-        let iffy_keys = keys_exists_any_connections(source_conns, &rediskeys);
+        let iffy_keys = keys_exists_any_connections(source_conns, &s_mappers, &rediskeys);
+        self.progress.add_compared(count);
 
         if !iffy_keys.is_empty() {
+            self.progress.add_mismatched(iffy_keys.len() as u64);
             let cfk = FailKeys {
                 iffy_keys,
                 source: self.source.clone(),
@@ -430,10 +747,11 @@ impl CompareDBReverse {
                 reverse: true,
                 ttl_diff: self.ttl_diff,
                 batch: self.batch,
+                options: self.options.clone(),
             };
             log::info!("{:?}", cfk);
 
-            if let Err(e) = cfk.write_to_file(&self.result_store_dir) {
+            if let Err(e) = self.result_store.save(FIRST_ROUND, &cfk) {
                 log::error!("{}", e);
             }
         }
@@ -449,6 +767,161 @@ impl CompareDBReverse {
     }
 }
 
+// source 为 RDB 文件时流式读取 key，每 batch 个 key 载入内存后与 target 比较
+// compares 的 source 为同一个 RDB 文件，文件只解析一次，key 按 DB 分发给对应的 DB 对
+// 提交到比较线程池的批次不超过 compare_pool 的两倍，限制读取速度快于比较时的内存占用
+pub(crate) fn exec_rdb_group(compares: &[CompareDB]) {
+    let first = match compares.first() {
+        Some(c) => c,
+        None => return,
+    };
+    let reader = match RdbReader::open(&first.source.rdb_file) {
+        Ok(r) => r,
+        Err(e) => {
+            log::error!("{}", e);
+            return;
+        }
+    };
+    log::info!(
+        "compare rdb file {} version {} dbs {:?}",
+        first.source.rdb_file,
+        reader.version,
+        compares.iter().map(|c| c.source.db).collect::<Vec<usize>>()
+    );
+
+    let pool_compare = match rayon::ThreadPoolBuilder::new()
+        .num_threads(first.compare_pool)
+        .build()
+    {
+        Ok(p) => p,
+        Err(e) => {
+            log::error!("{}", e);
+            return;
+        }
+    };
+
+    let pool_big = match rayon::ThreadPoolBuilder::new()
+        .num_threads(first.big_key_pool)
+        .build()
+    {
+        Ok(p) => p,
+        Err(e) => {
+            log::error!("{}", e);
+            return;
+        }
+    };
+
+    // 每个 DB 对的 target 客户端、改名规则与未满的批次
+    let mut pairs = vec![];
+    for c in compares {
+        let t_client = match c.target.instance.to_redis_client() {
+            Ok(rc) => rc,
+            Err(e) => {
+                log::error!("{}", e);
+                return;
+            }
+        };
+        let key_mapper = match c.source.key_mapper() {
+            Ok(m) => m,
+            Err(e) => {
+                log::error!("{}", e);
+                return;
+            }
+        };
+        pairs.push((c, t_client, key_mapper, MemoryConnection::default()));
+    }
+
+    let in_flight = AtomicUsize::new(0);
+    let in_flight = &in_flight;
+    let max_in_flight = first.compare_pool.max(1) * 2;
+    pool_big.scope(|pb| {
+        pool_compare.scope(move |pc| {
+            for entry in reader {
+                let mut entry = match entry {
+                    Ok(e) => e,
+                    Err(e) => {
+                        log::error!("{}", e);
+                        break;
+                    }
+                };
+                let key = String::from_utf8_lossy(&entry.key).to_string();
+                // 最后一个对应的 DB 对直接取走 value，其余复制
+                let mut remaining = pairs.iter().filter(|p| p.0.source.db == entry.db).count();
+                for (c, t_client, key_mapper, batch) in pairs.iter_mut() {
+                    if entry.db != c.source.db {
+                        continue;
+                    }
+                    remaining -= 1;
+                    c.progress.add_scanned(1);
+                    if !c.options.slot_selected(&key_mapper.to_target(&key)) {
+                        c.progress.add_skipped(1);
+                        continue;
+                    }
+                    let value = match remaining {
+                        0 => std::mem::replace(&mut entry.value, MemValue::String(vec![])),
+                        _ => entry.value.clone(),
+                    };
+                    batch.insert(entry.key.clone(), value, entry.expire_at_ms);
+                    if batch.entries.len() < c.batch {
+                        continue;
+                    }
+                    let mem_conn = std::mem::take(batch);
+                    c.dispatch_memory_batch(pc, pb, t_client, mem_conn, in_flight, max_in_flight);
+                }
+            }
+
+            for (c, t_client, _, batch) in pairs {
+                if !batch.entries.is_empty() {
+                    c.dispatch_memory_batch(pc, pb, &t_client, batch, in_flight, max_in_flight);
+                }
+            }
+        });
+    });
+}
+
+// 比较内存中的 source DB 与 target，用于 AOF、快照等离线数据源
+// 仅比较 source 中存在的 key，返回校验失败的 key
+pub fn compare_memory_with_target(
+    mem: MemoryConnection,
+    source: &RedisInstanceWithDB,
+    target: &RedisInstanceWithDB,
+    ttl_diff: usize,
+    batch: usize,
+    options: &CompareOptions,
+) -> Result<Vec<IffyKey>> {
+    let key_mapper = source.key_mapper()?;
+    let rediskeys: Vec<RedisKey> = mem
+        .keys()
+        .iter()
+        .filter_map(|k| {
+            let key_name = String::from_utf8_lossy(k).to_string();
+            if !options.slot_selected(&key_mapper.to_target(&key_name)) {
+                return None;
+            }
+            mem.get(k).map(|e| RedisKey {
+                key_name,
+                key_type: e.value.key_type(),
+            })
+        })
+        .collect();
+
+    let mut options = options.clone();
+    // 内存数据无法执行 Lua
+    options.string_chunk_sha1 = false;
+    let comparer = Comparer {
+        sconn: Box::new(mem),
+        tconn: target
+            .to_redis_client_with_db()?
+            .get_redis_connection()?
+            .get_dyn_connection(),
+        ttl_diff,
+        batch,
+        key_mapper,
+        options,
+    };
+    Ok(comparer.compare_rediskeys(&rediskeys))
+}
+
 /// .批量获取key type
 fn keys_type(keys: Vec<String>, con: &mut dyn redis::ConnectionLike) -> Vec<RedisKey> {
     let mut redis_key_vec = vec![];
@@ -470,9 +943,11 @@ fn keys_type(keys: Vec<String>, con: &mut dyn redis::ConnectionLike) -> Vec<Redi
 }
 
 // 比较key在多个db中是否存在，在任意一个库中存在则返回true，key在所有key中都不存在返回false
+// mappers 与 conns 一一对应，用于将 target key 还原为 source key
 // 返回 检查结果为false 的 RedisKey
 fn keys_exists_any_connections(
     mut conns: Vec<RedisConnection>,
+    mappers: &[KeyMapper],
     keys: &Vec<RedisKey>,
 ) -> Vec<IffyKey> {
     let mut vec_iffykeys: Vec<IffyKey> = vec![];
@@ -480,29 +955,29 @@ fn keys_exists_any_connections(
     for key in keys {
         let mut key_existes = false;
 
-        for conn in &mut conns {
-            if let RedisConnection::Single(sc) = conn {
-                match redis::cmd("exists")
-                    .arg(key.key_name.clone())
-                    .query::<bool>(sc)
-                {
-                    Ok(exists) => {
-                        if exists {
-                            key_existes = exists;
+        for (conn, mapper) in conns.iter_mut().zip(mappers) {
+            // key 不可能由该 source 改名而来时没有候选
+            for s_key in mapper.to_source(&key.key_name) {
+                if let RedisConnection::Single(sc) = conn {
+                    match redis::cmd("exists").arg(s_key.clone()).query::<bool>(sc) {
+                        Ok(exists) => {
+                            if exists {
+                                key_existes = exists;
+                            }
                         }
+                        Err(_) => {}
                     }
-                    Err(_) => {}
                 }
-            }
 
-            if let RedisConnection::Cluster(cc) = conn {
-                match redis::cmd("exists").arg(key.key_name.clone()).query(cc) {
-                    Ok(exists) => {
-                        if exists {
-                            key_existes = exists;
+                if let RedisConnection::Cluster(cc) = conn {
+                    match redis::cmd("exists").arg(s_key.clone()).query(cc) {
+                        Ok(exists) => {
+                            if exists {
+                                key_existes = exists;
+                            }
                         }
+                        Err(_) => {}
                     }
-                    Err(_) => {}
                 }
             }
         }
@@ -515,6 +990,7 @@ fn keys_exists_any_connections(
                     error_type: CompareErrorType::ExistsErr,
                     reason: None,
                 },
+                diff: None,
             };
             vec_iffykeys.push(iffy);
         }
@@ -528,6 +1004,7 @@ fn keys_exists_any_connections(
 fn keys_exists_reverse(
     target_conn: RedisConnection,
     mut source_conns: Vec<RedisConnection>,
+    source_mappers: &[KeyMapper],
     keys: &Vec<RedisKey>,
 ) -> Vec<IffyKey> {
     let mut vec_iffykeys: Vec<IffyKey> = vec![];
@@ -545,26 +1022,28 @@ fn keys_exists_reverse(
             continue;
         }
         let mut s_exists = false;
-        for conn in &mut source_conns {
-            if let RedisConnection::Single(sc) = conn {
-                match cmd_select.arg(key.key_name.clone()).query::<bool>(sc) {
-                    Ok(exists) => {
-                        if exists {
-                            s_exists = exists;
+        for (conn, mapper) in source_conns.iter_mut().zip(source_mappers) {
+            for s_key in mapper.to_source(&key.key_name) {
+                if let RedisConnection::Single(sc) = conn {
+                    match cmd_select.arg(s_key.clone()).query::<bool>(sc) {
+                        Ok(exists) => {
+                            if exists {
+                                s_exists = exists;
+                            }
                         }
+                        Err(_) => {}
                     }
-                    Err(_) => {}
                 }
-            }
 
-            if let RedisConnection::Cluster(cc) = conn {
-                match cmd_select.arg(key.key_name.clone()).query(cc) {
-                    Ok(exists) => {
-                        if exists {
-                            s_exists = exists;
+                if let RedisConnection::Cluster(cc) = conn {
+                    match cmd_select.arg(s_key.clone()).query(cc) {
+                        Ok(exists) => {
+                            if exists {
+                                s_exists = exists;
+                            }
                         }
+                        Err(_) => {}
                     }
-                    Err(_) => {}
                 }
             }
         }
@@ -577,9 +1056,52 @@ fn keys_exists_reverse(
                     error_type: CompareErrorType::ExistsErr,
                     reason: None,
                 },
+                diff: None,
             };
             vec_iffykeys.push(iffy);
         }
     }
     vec_iffykeys
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    //cargo test compare::compare_db::test::test_fail_keys_without_options --  --nocapture
+    #[test]
+    fn test_fail_keys_without_options() {
+        // 旧版本的 .cr 文件按旧的字段布局编码，RedisInstance 为 (urls, password, instance_type)，
+        // RedisInstanceWithDB 为 (instance, db)，FailKeys 没有 options 字段
+        let instance = (
+            vec!["redis://127.0.0.1:6379".to_string()],
+            "old_password".to_string(),
+            InstanceType::Cluster,
+        );
+        let source = (instance, 3usize);
+        let iffy_keys: Vec<IffyKey> = vec![];
+        let mut buf = Vec::new();
+        (
+            vec![source.clone()],
+            source,
+            iffy_keys,
+            2usize,
+            10usize,
+            false,
+        )
+            .serialize(&mut Serializer::new(&mut buf))
+            .unwrap();
+        let fk: FailKeys = rmp_serde::from_slice(&buf).unwrap();
+        assert_eq!((fk.ttl_diff, fk.batch, fk.reverse), (2, 10, false));
+        for i in fk.source.iter().chain([&fk.target]) {
+            assert_eq!(i.db, 3);
+            assert_eq!(i.instance.password, "old_password");
+            assert_eq!(i.instance.instance_type, InstanceType::Cluster);
+            assert_eq!(i.instance.username, "");
+        }
+        assert_eq!(
+            format!("{:?}", fk.options),
+            format!("{:?}", CompareOptions::default())
+        );
+    }
+}
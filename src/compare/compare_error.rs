@@ -13,11 +13,29 @@ pub enum CompareErrorType {
     ListIndexValueDiff,
     SetCardDiff,
     SetMemberNotIn,
+    // member 仅存在于 target
+    SetMemberNotInSource,
     ZSetCardDiff,
     ZSetMemberScoreDiff,
+    // member 仅存在于 target
+    ZSetMemberNotInSource,
+    // 相同排名的 member 不一致
+    ZSetRankDiff,
     HashLenDiff,
     HashFieldValueDiff,
+    // field 仅存在于 target
+    HashFieldNotInSource,
     StringValueNotEqual,
+    // HyperLogLog 基数超出允许误差
+    HyperLogLogCountDiff,
+    // geo member 坐标距离超出允许误差
+    GeoPositionDiff,
+    // 两端 key 类型不一致
+    KeyTypeDiff,
+    // 模块类型等未知类型的 DUMP 内容不一致
+    DumpPayloadDiff,
+    // 实例 CONFIG 或 INFO 字段不一致
+    ConfigValueDiff,
     RedisConnectionErr,
     KeyTypeNotString,
     KeyTypeNotList,
@@ -49,21 +67,48 @@ impl fmt::Display for CompareErrorType {
             CompareErrorType::SetMemberNotIn => {
                 write!(f, "mumber not in set")
             }
+            CompareErrorType::SetMemberNotInSource => {
+                write!(f, "mumber not in source set")
+            }
             CompareErrorType::ZSetCardDiff => {
                 write!(f, "Sorted set cardinality different")
             }
             CompareErrorType::ZSetMemberScoreDiff => {
                 write!(f, "mumber not in sorted set")
             }
+            CompareErrorType::ZSetMemberNotInSource => {
+                write!(f, "mumber not in source sorted set")
+            }
+            CompareErrorType::ZSetRankDiff => {
+                write!(f, "Sorted set rank different")
+            }
             CompareErrorType::HashLenDiff => {
                 write!(f, "Hash length different")
             }
             CompareErrorType::HashFieldValueDiff => {
                 write!(f, "Hash field value different")
             }
+            CompareErrorType::HashFieldNotInSource => {
+                write!(f, "field not in source hash")
+            }
             CompareErrorType::StringValueNotEqual => {
                 write!(f, "String value not equal")
             }
+            CompareErrorType::HyperLogLogCountDiff => {
+                write!(f, "HyperLogLog cardinality different")
+            }
+            CompareErrorType::GeoPositionDiff => {
+                write!(f, "Geo position different")
+            }
+            CompareErrorType::KeyTypeDiff => {
+                write!(f, "Key type different")
+            }
+            CompareErrorType::DumpPayloadDiff => {
+                write!(f, "Dump payload different")
+            }
+            CompareErrorType::ConfigValueDiff => {
+                write!(f, "Config value different")
+            }
             CompareErrorType::RedisConnectionErr => {
                 write!(f, "Redis connection error")
             }
@@ -90,11 +135,18 @@ impl fmt::Display for CompareErrorType {
 }
 
 // 用于描述集合类型元素位置，list index；zset member；hash field
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub enum Position {
     ListIndex(usize),
+    // list 下标闭区间
+    ListRange(usize, usize),
+    SetMember(String),
     ZsetMember(String),
+    // zset 按 score 排序后的排名
+    ZsetRank(usize),
     HashField(String),
+    // string 第一个不同字节的偏移量
+    StringOffset(usize),
 }
 
 // #[derive(Debug, Clone)]
@@ -113,7 +165,8 @@ pub struct CompareError {
     pub message: Option<String>,
     /// 错误类型
     pub error_type: CompareErrorType,
-    pub reason: Option<CompareErrorReason>,
+    // 装箱以减小 CompareResult 的体积，序列化格式不变
+    pub reason: Option<Box<CompareErrorReason>>,
 }
 
 impl CompareError {
@@ -139,6 +192,15 @@ impl CompareError {
             CompareErrorType::KeyTypeNotSet => 1013,
             CompareErrorType::KeyTypeNotZSet => 1014,
             CompareErrorType::KeyTypeNotHash => 1015,
+            CompareErrorType::ZSetMemberNotInSource => 1016,
+            CompareErrorType::ZSetRankDiff => 1017,
+            CompareErrorType::SetMemberNotInSource => 1018,
+            CompareErrorType::HashFieldNotInSource => 1019,
+            CompareErrorType::HyperLogLogCountDiff => 1020,
+            CompareErrorType::GeoPositionDiff => 1021,
+            CompareErrorType::KeyTypeDiff => 1022,
+            CompareErrorType::DumpPayloadDiff => 1023,
+            CompareErrorType::ConfigValueDiff => 1024,
         }
     }
 
@@ -156,7 +218,7 @@ impl CompareError {
         Self {
             message: Some(error_type.to_string()),
             error_type,
-            reason: Some(reason),
+            reason: Some(Box::new(reason)),
         }
     }
 }
@@ -1,26 +1,11 @@
 use anyhow::Result;
-use std::{fs::File, io::Read};
 
 use super::FailKeys;
 
 pub fn compare_from_file(path: &str) -> Result<FailKeys> {
-    // open file
-    let mut file = File::open(path)?;
     // 反序列化文件
-    let mut buf = vec![];
-    let _ = file.read_to_end(&mut buf);
-    let fk = rmp_serde::from_slice::<FailKeys>(&buf)?;
-    let iffies = fk.compare()?;
-    let new_result = FailKeys {
-        source: fk.source.clone(),
-        target: fk.target.clone(),
-        iffy_keys: iffies,
-        ttl_diff: fk.ttl_diff,
-        batch: fk.batch,
-        reverse: fk.reverse,
-    };
-
-    Ok(new_result)
+    let fk = FailKeys::from_file(path)?;
+    fk.recheck()
 }
 
 #[cfg(test)]
@@ -0,0 +1,359 @@
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+
+use crate::rdb::load_all;
+use crate::util::{MemoryConnection, RedisKey};
+
+use super::{
+    compare_db::FailKeys,
+    compare_error::CompareErrorType,
+    compare_snapshot::{is_snapshot_file, load_snapshot},
+    comparekey::{CompareOptions, Comparer, IffyKey},
+    rediscompare::{Compare, RedisInstanceWithDB},
+    CompareError, KeyMapper,
+};
+
+// 离线比较两组内存 keyspace，a 作为 source，b 作为 target
+// 返回各 DB 校验失败的 key，仅存在于 b 的 key 以 ExistsErr 报告
+// 两侧以固定时间 0 判断过期，dump 之后到期的 key 不会被跳过，TTL 即 expire_at_ms 的比较
+pub fn compare_memory_dbs(
+    mut a: BTreeMap<usize, MemoryConnection>,
+    mut b: BTreeMap<usize, MemoryConnection>,
+    ttl_diff: usize,
+    batch: usize,
+    options: &CompareOptions,
+) -> BTreeMap<usize, Vec<IffyKey>> {
+    let mut result = BTreeMap::new();
+    let mut dbs: Vec<usize> = a.keys().chain(b.keys()).copied().collect();
+    dbs.sort();
+    dbs.dedup();
+
+    for db in dbs {
+        let mut s_conn = a.remove(&db).unwrap_or_default();
+        let mut t_conn = b.remove(&db).unwrap_or_default();
+        s_conn.clock_ms = Some(0);
+        t_conn.clock_ms = Some(0);
+
+        let mut iffy_keys = vec![];
+        for key in t_conn.keys() {
+            if s_conn.get(&key).is_some() {
+                continue;
+            }
+            let value_type = t_conn.get(&key).map(|e| e.value.key_type());
+            if let Some(key_type) = value_type {
+                iffy_keys.push(IffyKey {
+                    key: RedisKey {
+                        key_name: String::from_utf8_lossy(&key).to_string(),
+                        key_type,
+                    },
+                    error: CompareError {
+                        message: Some("key not in source".to_owned()),
+                        error_type: CompareErrorType::ExistsErr,
+                        reason: None,
+                    },
+                    diff: None,
+                });
+            }
+        }
+
+        let rediskeys: Vec<RedisKey> = s_conn
+            .keys()
+            .iter()
+            .filter_map(|k| {
+                s_conn.get(k).map(|e| RedisKey {
+                    key_name: String::from_utf8_lossy(k).to_string(),
+                    key_type: e.value.key_type(),
+                })
+            })
+            .collect();
+        let comparer = Comparer {
+            sconn: Box::new(s_conn),
+            tconn: Box::new(t_conn),
+            ttl_diff,
+            batch,
+            key_mapper: KeyMapper::default(),
+            options: options.clone(),
+        };
+        let mut forward = comparer.compare_rediskeys(&rediskeys);
+        forward.append(&mut iffy_keys);
+
+        if !forward.is_empty() {
+            result.insert(db, forward);
+        }
+    }
+    result
+}
+
+// 读取 RDB 或快照文件，快照中多个 source 的同一 DB 合并，与 RDB 的 keyspace 对应
+fn load_dump(path: &str) -> Result<BTreeMap<usize, MemoryConnection>> {
+    if is_snapshot_file(path)? {
+        let mut dbs: BTreeMap<usize, MemoryConnection> = BTreeMap::new();
+        for ((_, db), mem) in load_snapshot(path)?.1 {
+            dbs.entry(db).or_default().entries.extend(mem.entries);
+        }
+        return Ok(dbs);
+    }
+    load_all(path)
+}
+
+// 比较两个 RDB 或快照文件，无需运行 redis
+pub fn compare_rdb_files(
+    a: &str,
+    b: &str,
+    ttl_diff: usize,
+    batch: usize,
+    options: &CompareOptions,
+) -> Result<BTreeMap<usize, Vec<IffyKey>>> {
+    let a_dbs = load_dump(a)?;
+    let b_dbs = load_dump(b)?;
+    let mut options = options.clone();
+    // 内存数据无法执行 Lua
+    options.string_chunk_sha1 = false;
+    Ok(compare_memory_dbs(a_dbs, b_dbs, ttl_diff, batch, &options))
+}
+
+impl Compare {
+    // 比较两个 RDB 或快照文件，使用描述文件的 batch_size、ttl_diff 与校验选项
+    // 结果与 key 校验使用相同的 FailKeys 格式，source、target 记录对应的文件
+    pub fn rdb_diff(&self, a: &str, b: &str) -> Result<Vec<FailKeys>> {
+        let result = compare_rdb_files(a, b, self.ttl_diff, self.batch_size, &self.options)?;
+        let endpoint = |path: &str, db: usize| RedisInstanceWithDB {
+            db,
+            rdb_file: path.to_string(),
+            ..Default::default()
+        };
+        Ok(result
+            .into_iter()
+            .map(|(db, iffy_keys)| self.fail_keys(endpoint(a, db), endpoint(b, db), iffy_keys))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::util::MemValue;
+
+    //cargo test compare::compare_rdb::test::test_compare_memory_dbs --  --nocapture
+    #[test]
+    fn test_compare_memory_dbs() {
+        let mut a = MemoryConnection::default();
+        a.insert(b"same".to_vec(), MemValue::String(b"v".to_vec()), None);
+        a.insert(b"changed".to_vec(), MemValue::String(b"v1".to_vec()), None);
+        a.insert(b"only_a".to_vec(), MemValue::String(b"v".to_vec()), None);
+        a.insert(
+            b"list".to_vec(),
+            MemValue::List(vec![b"x".to_vec()].into()),
+            None,
+        );
+
+        let mut b = a.clone();
+        b.insert(b"changed".to_vec(), MemValue::String(b"v2".to_vec()), None);
+        b.entries.remove(b"only_a".as_slice());
+        b.insert(b"only_b".to_vec(), MemValue::String(b"v".to_vec()), None);
+        b.insert(
+            b"list".to_vec(),
+            MemValue::List(vec![b"x".to_vec(), b"y".to_vec()].into()),
+            None,
+        );
+
+        let result = compare_memory_dbs(
+            BTreeMap::from([(0, a)]),
+            BTreeMap::from([(0, b), (1, MemoryConnection::default())]),
+            2,
+            10,
+            &CompareOptions::default(),
+        );
+        assert_eq!(result.len(), 1);
+        let mut errors: Vec<(String, String)> = result[&0]
+            .iter()
+            .map(|i| (i.key.key_name.clone(), i.error.error_type.to_string()))
+            .collect();
+        errors.sort();
+        assert_eq!(
+            errors,
+            vec![
+                (
+                    "changed".to_string(),
+                    CompareErrorType::StringValueNotEqual.to_string()
+                ),
+                (
+                    "list".to_string(),
+                    CompareErrorType::ListLenDiff.to_string()
+                ),
+                (
+                    "only_a".to_string(),
+                    CompareErrorType::ExistsErr.to_string()
+                ),
+                (
+                    "only_b".to_string(),
+                    CompareErrorType::ExistsErr.to_string()
+                ),
+            ]
+        );
+    }
+
+    //cargo test compare::compare_rdb::test::test_compare_hll --  --nocapture
+    #[test]
+    fn test_compare_hll() {
+        // register 100 为 1 的稀疏与稠密编码
+        let mut sparse = b"HYLL".to_vec();
+        sparse.push(1);
+        sparse.extend_from_slice(&[0; 11]);
+        sparse.extend_from_slice(&[0x40, 99, 0x80, 0x7f, 0x9a]);
+        let mut dense = b"HYLL".to_vec();
+        dense.extend_from_slice(&[0; 12]);
+        let mut registers = vec![0u8; 12288];
+        registers[75] = 1;
+        dense.extend_from_slice(&registers);
+        let mut empty = b"HYLL".to_vec();
+        empty.push(1);
+        empty.extend_from_slice(&[0; 11]);
+        empty.extend_from_slice(&[0x7f, 0xff]);
+
+        let mut a = MemoryConnection::default();
+        a.insert(b"same".to_vec(), MemValue::String(sparse.clone()), None);
+        a.insert(b"diff".to_vec(), MemValue::String(sparse), None);
+        let mut b = MemoryConnection::default();
+        b.insert(b"same".to_vec(), MemValue::String(dense), None);
+        b.insert(b"diff".to_vec(), MemValue::String(empty), None);
+
+        let result = compare_memory_dbs(
+            BTreeMap::from([(0, a)]),
+            BTreeMap::from([(0, b)]),
+            2,
+            10,
+            &CompareOptions::default(),
+        );
+        let iffy = &result[&0];
+        assert_eq!(iffy.len(), 1);
+        assert_eq!(iffy[0].key.key_name, "diff");
+        assert_eq!(
+            iffy[0].error.error_type.to_string(),
+            CompareErrorType::HyperLogLogCountDiff.to_string()
+        );
+    }
+
+    //cargo test compare::compare_rdb::test::test_compare_geo --  --nocapture
+    #[test]
+    fn test_compare_geo() {
+        // Palermo、Catania 的 geohash，score 加 1 后坐标相差约 0.3 米
+        let mut a = MemoryConnection::default();
+        a.insert(
+            b"geo:sicily".to_vec(),
+            MemValue::ZSet(BTreeMap::from([
+                (b"Palermo".to_vec(), 3479099956230698.0),
+                (b"Catania".to_vec(), 3479447370796909.0),
+            ])),
+            None,
+        );
+        let mut b = MemoryConnection::default();
+        b.insert(
+            b"geo:sicily".to_vec(),
+            MemValue::ZSet(BTreeMap::from([
+                (b"Palermo".to_vec(), 3479099956230699.0),
+                (b"Catania".to_vec(), 3479447370796909.0),
+            ])),
+            None,
+        );
+
+        let compare = |options: &CompareOptions, b: &MemoryConnection| {
+            compare_memory_dbs(
+                BTreeMap::from([(0, a.clone())]),
+                BTreeMap::from([(0, b.clone())]),
+                2,
+                10,
+                options,
+            )
+        };
+        let result = compare(&CompareOptions::default(), &b);
+        assert_eq!(
+            result[&0][0].error.error_type.to_string(),
+            CompareErrorType::ZSetMemberScoreDiff.to_string()
+        );
+
+        let patterns = CompareOptions {
+            geo_key_patterns: vec!["^geo:".to_string()],
+            ..Default::default()
+        };
+        assert!(compare(&patterns, &b).is_empty());
+        let auto = CompareOptions {
+            geo_auto_detect: true,
+            ..Default::default()
+        };
+        assert!(compare(&auto, &b).is_empty());
+
+        // Catania 移动到 Palermo
+        b.insert(
+            b"geo:sicily".to_vec(),
+            MemValue::ZSet(BTreeMap::from([
+                (b"Palermo".to_vec(), 3479099956230698.0),
+                (b"Catania".to_vec(), 3479099956230698.0),
+            ])),
+            None,
+        );
+        let result = compare(&patterns, &b);
+        assert_eq!(
+            result[&0][0].error.error_type.to_string(),
+            CompareErrorType::GeoPositionDiff.to_string()
+        );
+    }
+
+    //cargo test compare::compare_rdb::test::test_compare_expired --  --nocapture
+    #[test]
+    fn test_compare_expired() {
+        // dump 之后已过期的 key 仍按 expire_at_ms 比较
+        let expire_at = crate::util::now_ms() - 60_000;
+        let mut a = MemoryConnection::default();
+        a.insert(
+            b"same".to_vec(),
+            MemValue::String(b"v".to_vec()),
+            Some(expire_at),
+        );
+        a.insert(
+            b"ttl".to_vec(),
+            MemValue::String(b"v".to_vec()),
+            Some(expire_at),
+        );
+        a.insert(
+            b"value".to_vec(),
+            MemValue::String(b"v1".to_vec()),
+            Some(expire_at),
+        );
+        let mut b = a.clone();
+        b.insert(
+            b"ttl".to_vec(),
+            MemValue::String(b"v".to_vec()),
+            Some(expire_at + 10_000),
+        );
+        b.insert(
+            b"value".to_vec(),
+            MemValue::String(b"v2".to_vec()),
+            Some(expire_at),
+        );
+
+        let result = compare_memory_dbs(
+            BTreeMap::from([(0, a)]),
+            BTreeMap::from([(0, b)]),
+            2,
+            10,
+            &CompareOptions::default(),
+        );
+        let mut errors: Vec<(String, String)> = result[&0]
+            .iter()
+            .map(|i| (i.key.key_name.clone(), i.error.error_type.to_string()))
+            .collect();
+        errors.sort();
+        assert_eq!(
+            errors,
+            vec![
+                ("ttl".to_string(), CompareErrorType::TTLDiff.to_string()),
+                (
+                    "value".to_string(),
+                    CompareErrorType::StringValueNotEqual.to_string()
+                ),
+            ]
+        );
+    }
+}
@@ -0,0 +1,155 @@
+use std::collections::BTreeMap;
+
+use anyhow::{anyhow, Result};
+use redis::cluster_routing::get_slot;
+use serde::{Deserialize, Serialize};
+
+use crate::util::{fnv1a_64, scan_count};
+
+use super::rediscompare::{Compare, InstanceType, RedisInstanceWithDB};
+use super::KeyMapper;
+
+pub const CLUSTER_SLOTS: u16 = 16384;
+
+// 单个 slot 的 key 数量与 key 名摘要，摘要为各 key 名 hash 的异或，与遍历顺序无关
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SlotDigest {
+    pub count: usize,
+    pub digest: u64,
+}
+
+impl SlotDigest {
+    fn add(&mut self, key: &[u8]) {
+        self.count += 1;
+        self.digest ^= fnv1a_64(key);
+    }
+
+    fn merge(&mut self, other: &SlotDigest) {
+        self.count += other.count;
+        self.digest ^= other.digest;
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SlotDiff {
+    pub slot: u16,
+    pub source: SlotDigest,
+    pub target: SlotDigest,
+}
+
+pub type SlotMap = BTreeMap<u16, SlotDigest>;
+
+// key 按改名后在 target 中的名称计算 slot，改名规则按字符串匹配，非 UTF-8 的 key 保持原名
+fn add_key(map: &mut SlotMap, mapper: &KeyMapper, key: &[u8]) {
+    let t_key = match std::str::from_utf8(key) {
+        Ok(k) => mapper.to_target(k).into_bytes(),
+        Err(_) => key.to_vec(),
+    };
+    map.entry(get_slot(&t_key)).or_default().add(&t_key);
+}
+
+impl RedisInstanceWithDB {
+    // 统计 DB 中全部 key 在 target cluster 中所属 slot 的数量与摘要，每次 SCAN 约 batch 个 key
+    // cluster 逐个 master SCAN，只统计该 master 负责的 slot，迁移中同时存在于两个节点的 key 不会重复计算
+    pub fn slot_map(&self, batch: usize) -> Result<SlotMap> {
+        let mapper = self.key_mapper()?;
+        let mut map = SlotMap::new();
+        if let InstanceType::Cluster = self.instance.instance_type {
+            for (client, slots) in self.instance.cluster_master_clients()? {
+                let mut owned = vec![false; CLUSTER_SLOTS as usize];
+                for slot in slots {
+                    owned[slot as usize] = true;
+                }
+                let mut conn = self.instance.timeouts().single_connection(&client)?;
+                for key in scan_count::<Vec<u8>>(batch, &mut conn)? {
+                    if owned[get_slot(&key) as usize] {
+                        add_key(&mut map, &mapper, &key);
+                    }
+                }
+            }
+            return Ok(map);
+        }
+
+        let client = self.to_redis_client_with_db()?;
+        let mut conn = client.get_redis_connection()?.get_dyn_connection();
+        for key in scan_count::<Vec<u8>>(batch, conn.as_mut())? {
+            add_key(&mut map, &mapper, &key);
+        }
+        Ok(map)
+    }
+}
+
+// 逐 slot 比较数量与摘要，返回不一致的 slot
+pub fn diff_slot_maps(source: &SlotMap, target: &SlotMap) -> Vec<SlotDiff> {
+    let mut diffs = vec![];
+    for slot in 0..CLUSTER_SLOTS {
+        let s = source.get(&slot).copied().unwrap_or_default();
+        let t = target.get(&slot).copied().unwrap_or_default();
+        if s != t {
+            diffs.push(SlotDiff {
+                slot,
+                source: s,
+                target: t,
+            });
+        }
+    }
+    diffs
+}
+
+impl Compare {
+    // target 为 cluster 时生成 slot 级别一致性报告，映射到 target 的所有 source DB 合并统计
+    pub fn slot_diff(&self) -> Result<Vec<SlotDiff>> {
+        if !self.target.instance_type.eq(&InstanceType::Cluster) {
+            return Err(anyhow!("target instance is not cluster"));
+        }
+        let map = self.map_dbinstance_source_to_target()?;
+
+        let mut s_map = SlotMap::new();
+        let mut t_map = None;
+        for (s, t) in map {
+            for (slot, digest) in s.slot_map(self.batch_size)? {
+                s_map.entry(slot).or_default().merge(&digest);
+            }
+            if t_map.is_none() {
+                t_map = Some(t.slot_map(self.batch_size)?);
+            }
+        }
+
+        Ok(diff_slot_maps(&s_map, &t_map.unwrap_or_default()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::compare::KeyMapRule;
+
+    //cargo test compare::compare_slots::test::test_diff_slot_maps --  --nocapture
+    #[test]
+    fn test_diff_slot_maps() {
+        let plain = KeyMapper::default();
+        let prefixed = KeyMapper::new(&[KeyMapRule::AddPrefix("{db3}".to_string())]).unwrap();
+
+        let mut source = SlotMap::new();
+        add_key(&mut source, &plain, b"k1");
+        add_key(&mut source, &plain, b"k2");
+        add_key(&mut source, &prefixed, b"k3");
+
+        let mut target = SlotMap::new();
+        add_key(&mut target, &plain, b"k2");
+        add_key(&mut target, &plain, b"k1");
+        add_key(&mut target, &plain, b"{db3}k3");
+        assert!(diff_slot_maps(&source, &target).is_empty());
+
+        add_key(&mut target, &plain, b"k4");
+        let diffs = diff_slot_maps(&source, &target);
+        assert_eq!(diffs.len(), 1);
+        assert_eq!(diffs[0].slot, get_slot(b"k4"));
+        assert_eq!(diffs[0].target.count, diffs[0].source.count + 1);
+
+        // 非 UTF-8 的 key 按原始字节统计
+        add_key(&mut source, &prefixed, b"\xff\xfe");
+        add_key(&mut target, &plain, b"\xff\xfe");
+        assert_eq!(diff_slot_maps(&source, &target).len(), 1);
+    }
+}
@@ -0,0 +1,434 @@
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::fs::{File, OpenOptions};
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Write};
+
+use anyhow::{anyhow, Result};
+use redis::{ConnectionLike, RedisResult};
+use rmp_serde::Serializer;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::rdb::{lzf_compress, lzf_decompress};
+use crate::util::{now_ms, scan, MemEntry, MemValue, MemoryConnection, RedisKey};
+
+use super::{
+    compare_db::{compare_memory_with_target, FailKeys},
+    compare_error::{CompareErrorReason, CompareErrorType},
+    comparekey::{CompareOptions, IffyKey},
+    rediscompare::{Compare, RedisInstanceWithDB},
+    CompareError,
+};
+
+pub const SNAPSHOT_MAGIC: &[u8] = b"RCSNAP01";
+// 每个数据帧包含的 key 数量
+const SNAPSHOT_FRAME_KEYS: usize = 1000;
+// 读取 list 时每次 LRANGE 的元素数量
+const LIST_RANGE_BATCH: usize = 1000;
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct SnapshotHeader {
+    // 生成时间，unix 毫秒时间戳
+    pub created_at: i64,
+    pub sources: Vec<RedisInstanceWithDB>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct SnapshotEntry {
+    pub db: usize,
+    pub key: Vec<u8>,
+    pub value: MemValue,
+    // 过期时间，unix 毫秒时间戳
+    pub expire_at_ms: Option<i64>,
+    // 所属 source 在 SnapshotHeader.sources 中的下标，旧版本快照无此字段，视为第一个 source
+    #[serde(default)]
+    pub source: usize,
+}
+
+// 快照文件：magic 后为若干帧，每帧为 原始长度(4) 压缩长度(4) LZF 压缩的 MessagePack
+// 第一帧为 SnapshotHeader，其后每帧为 Vec<SnapshotEntry>
+fn write_frame<W: Write, T: Serialize>(w: &mut W, val: &T) -> Result<()> {
+    let mut buf = Vec::new();
+    val.serialize(&mut Serializer::new(&mut buf))?;
+    let compressed = lzf_compress(&buf);
+    w.write_all(&(buf.len() as u32).to_le_bytes())?;
+    w.write_all(&(compressed.len() as u32).to_le_bytes())?;
+    w.write_all(&compressed)?;
+    Ok(())
+}
+
+fn read_frame<R: Read, T: DeserializeOwned>(r: &mut R) -> Result<Option<T>> {
+    let mut lens = [0u8; 8];
+    match r.read_exact(&mut lens) {
+        Ok(_) => {}
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e.into()),
+    }
+    let raw_len = u32::from_le_bytes([lens[0], lens[1], lens[2], lens[3]]) as usize;
+    let compressed_len = u32::from_le_bytes([lens[4], lens[5], lens[6], lens[7]]) as usize;
+    let mut compressed = vec![0u8; compressed_len];
+    r.read_exact(&mut compressed)?;
+    let buf = lzf_decompress(&compressed, raw_len)?;
+    Ok(Some(rmp_serde::from_slice::<T>(&buf)?))
+}
+
+// 读取 key 的值与过期时间，key 不存在或为不支持的类型时返回 None
+pub(crate) fn read_key(
+    key: &[u8],
+    conn: &mut dyn ConnectionLike,
+) -> RedisResult<Option<(MemValue, Option<i64>)>> {
+    let key_type: String = redis::cmd("type").arg(key).query(conn)?;
+    let value = match key_type.as_str() {
+        "string" => MemValue::String(redis::cmd("get").arg(key).query(conn)?),
+        "list" => {
+            let mut list = VecDeque::new();
+            loop {
+                let start = list.len();
+                let items: Vec<Vec<u8>> = redis::cmd("lrange")
+                    .arg(key)
+                    .arg(start)
+                    .arg(start + LIST_RANGE_BATCH - 1)
+                    .query(conn)?;
+                let done = items.len() < LIST_RANGE_BATCH;
+                list.extend(items);
+                if done {
+                    break;
+                }
+            }
+            MemValue::List(list)
+        }
+        "set" => {
+            let mut cmd = redis::cmd("sscan");
+            cmd.arg(key).cursor_arg(0);
+            let members: BTreeSet<Vec<u8>> = cmd.iter::<Vec<u8>>(conn)?.collect();
+            MemValue::Set(members)
+        }
+        "zset" => {
+            let mut cmd = redis::cmd("zscan");
+            cmd.arg(key).cursor_arg(0);
+            let items: Vec<Vec<u8>> = cmd.iter::<Vec<u8>>(conn)?.collect();
+            let mut zset = BTreeMap::new();
+            for pair in items.chunks(2) {
+                if let [member, score] = pair {
+                    let score = String::from_utf8_lossy(score);
+                    let score = match score.as_ref() {
+                        "inf" => f64::INFINITY,
+                        "-inf" => f64::NEG_INFINITY,
+                        s => s.parse().unwrap_or(0.0),
+                    };
+                    zset.insert(member.clone(), score);
+                }
+            }
+            MemValue::ZSet(zset)
+        }
+        "hash" => {
+            let mut cmd = redis::cmd("hscan");
+            cmd.arg(key).cursor_arg(0);
+            let items: Vec<Vec<u8>> = cmd.iter::<Vec<u8>>(conn)?.collect();
+            let mut hash = BTreeMap::new();
+            for pair in items.chunks(2) {
+                if let [field, value] = pair {
+                    hash.insert(field.clone(), value.clone());
+                }
+            }
+            MemValue::Hash(hash)
+        }
+        "none" => return Ok(None),
+        other => {
+            // 快照以值保存，模块类型等未知类型无法导出
+            log::warn!(
+                "skip key {} of type {}",
+                String::from_utf8_lossy(key),
+                other
+            );
+            return Ok(None);
+        }
+    };
+    let pttl: i64 = redis::cmd("pttl").arg(key).query(conn)?;
+    let expire_at_ms = match pttl {
+        -2 => return Ok(None),
+        -1 => None,
+        ms => Some(now_ms() + ms),
+    };
+    Ok(Some((value, expire_at_ms)))
+}
+
+// 快照中的 keyspace，按 (source 下标, db) 分组
+pub type SnapshotDbs = BTreeMap<(usize, usize), MemoryConnection>;
+
+// 读取快照文件中的全部 key
+pub fn load_snapshot(path: &str) -> Result<(SnapshotHeader, SnapshotDbs)> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut magic = [0u8; 8];
+    reader.read_exact(&mut magic)?;
+    if magic != SNAPSHOT_MAGIC {
+        return Err(anyhow!("{} is not a snapshot file", path));
+    }
+    let header: SnapshotHeader =
+        read_frame(&mut reader)?.ok_or_else(|| anyhow!("snapshot header missing"))?;
+    let mut dbs: SnapshotDbs = BTreeMap::new();
+    while let Some(entries) = read_frame::<_, Vec<SnapshotEntry>>(&mut reader)? {
+        for e in entries {
+            dbs.entry((e.source, e.db))
+                .or_default()
+                .insert(e.key, e.value, e.expire_at_ms);
+        }
+    }
+    Ok((header, dbs))
+}
+
+// 文件是否为快照文件
+pub fn is_snapshot_file(path: &str) -> Result<bool> {
+    let mut magic = [0u8; 8];
+    let mut file = File::open(path)?;
+    match file.read_exact(&mut magic) {
+        Ok(_) => Ok(magic == SNAPSHOT_MAGIC),
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+// source 在快照中的下标，按地址与 DB 匹配
+// 地址变化时（如更换主机名）退回到快照中唯一的同 DB source
+fn snapshot_source_index(header: &SnapshotHeader, source: &RedisInstanceWithDB) -> Result<usize> {
+    if let Some(i) = header
+        .sources
+        .iter()
+        .position(|s| s.db == source.db && s.instance.urls == source.instance.urls)
+    {
+        return Ok(i);
+    }
+    let same_db: Vec<usize> = header
+        .sources
+        .iter()
+        .enumerate()
+        .filter(|(_, s)| s.db == source.db)
+        .map(|(i, _)| i)
+        .collect();
+    match same_db.as_slice() {
+        [i] => Ok(*i),
+        _ => Err(anyhow!("source {} not found in snapshot", source.label())),
+    }
+}
+
+// 快照中已过期的 key 按绝对过期时间与 target 比较
+// target 中不存在视为一致，仍存在时过期时间相差超过 ttl_diff 秒报告 TTLDiff
+fn compare_expired_with_target(
+    expired: BTreeMap<Vec<u8>, MemEntry>,
+    source: &RedisInstanceWithDB,
+    target: &RedisInstanceWithDB,
+    ttl_diff: usize,
+    options: &CompareOptions,
+) -> Result<Vec<IffyKey>> {
+    let key_mapper = source.key_mapper()?;
+    let mut conn = target
+        .to_redis_client_with_db()?
+        .get_redis_connection()?
+        .get_dyn_connection();
+    let mut iffy_keys = vec![];
+    for (key, entry) in expired {
+        let expire_at_ms = match entry.expire_at_ms {
+            Some(at) => at,
+            None => continue,
+        };
+        let key_name = String::from_utf8_lossy(&key).to_string();
+        let target_key = key_mapper.to_target(&key_name);
+        if !options.slot_selected(&target_key) {
+            continue;
+        }
+        let pttl: i64 = redis::cmd("pttl").arg(&target_key).query(conn.as_mut())?;
+        let target_at = match pttl {
+            -2 => continue,
+            -1 => None,
+            ms => Some(now_ms() + ms),
+        };
+        if let Some(at) = target_at {
+            if (at - expire_at_ms).abs() <= ttl_diff as i64 * 1000 {
+                continue;
+            }
+        }
+        let redis_key = RedisKey {
+            key_name,
+            key_type: entry.value.key_type(),
+        };
+        let reason = CompareErrorReason {
+            redis_key: redis_key.clone(),
+            position: None,
+            source: Some(format!("expire at {}", expire_at_ms)),
+            target: Some(match target_at {
+                Some(at) => format!("expire at {}", at),
+                None => "no expire".to_string(),
+            }),
+        };
+        iffy_keys.push(IffyKey {
+            key: redis_key,
+            error: CompareError::from_reason(reason, CompareErrorType::TTLDiff),
+            diff: None,
+        });
+    }
+    Ok(iffy_keys)
+}
+
+impl Compare {
+    // 导出 source 全部 DB 的类型、值与过期时间到快照文件，返回导出的 key 数量
+    pub fn snapshot(&self, path: &str) -> Result<usize> {
+        let map = self.map_dbinstance_source_to_target()?;
+        let sources: Vec<RedisInstanceWithDB> = map.into_keys().collect();
+
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(true)
+            .write(true)
+            .open(path)?;
+        let mut writer = BufWriter::new(file);
+        writer.write_all(SNAPSHOT_MAGIC)?;
+        let header = SnapshotHeader {
+            created_at: now_ms(),
+            sources: sources.clone(),
+        };
+        write_frame(&mut writer, &header)?;
+
+        let mut total = 0;
+        let mut frame: Vec<SnapshotEntry> = vec![];
+        for (i, s) in sources.iter().enumerate() {
+            for node in s.to_single_redis_instance_with_db_vec() {
+                let client = node.to_redis_client_with_db()?;
+                let mut scan_conn = client.get_redis_connection()?.get_dyn_connection();
+                let mut read_conn = client.get_redis_connection()?.get_dyn_connection();
+                for key in scan::<Vec<u8>>(scan_conn.as_mut())? {
+                    let (value, expire_at_ms) = match read_key(&key, read_conn.as_mut())? {
+                        Some(v) => v,
+                        None => continue,
+                    };
+                    frame.push(SnapshotEntry {
+                        db: s.db,
+                        key,
+                        value,
+                        expire_at_ms,
+                        source: i,
+                    });
+                    total += 1;
+                    if frame.len() >= SNAPSHOT_FRAME_KEYS {
+                        write_frame(&mut writer, &frame)?;
+                        frame.clear();
+                    }
+                }
+            }
+        }
+        if !frame.is_empty() {
+            write_frame(&mut writer, &frame)?;
+        }
+        writer.flush()?;
+        Ok(total)
+    }
+
+    // 以快照作为 source 校验 target，按 source 的 dbmapper、key_mapping 映射
+    // 快照中已过期的 key 按绝对过期时间校验，不因校验时已过期而跳过
+    pub fn verify_snapshot(&self, path: &str) -> Result<Vec<FailKeys>> {
+        let (header, dbs) = load_snapshot(path)?;
+        log::info!(
+            "verify snapshot {} created at {} with {} source dbs",
+            path,
+            header.created_at,
+            header.sources.len()
+        );
+
+        let mut result = vec![];
+        for (s, t) in self.map_dbinstance_source_to_target()? {
+            let index = snapshot_source_index(&header, &s)?;
+            // 多个 target 可能对应同一个 source，快照数据只读取不移除
+            let mut mem = dbs.get(&(index, s.db)).cloned().unwrap_or_default();
+            let now = now_ms();
+            let (expired, alive): (BTreeMap<Vec<u8>, MemEntry>, BTreeMap<Vec<u8>, MemEntry>) =
+                std::mem::take(&mut mem.entries)
+                    .into_iter()
+                    .partition(|(_, e)| matches!(e.expire_at_ms, Some(at) if at <= now));
+            mem.entries = alive;
+            // 固定判断过期的时间，校验过程中到期的 key 不会被跳过
+            mem.clock_ms = Some(now);
+
+            let mut iffy_keys =
+                compare_expired_with_target(expired, &s, &t, self.ttl_diff, &self.options)?;
+            iffy_keys.extend(compare_memory_with_target(
+                mem,
+                &s,
+                &t,
+                self.ttl_diff,
+                self.batch_size,
+                &self.options,
+            )?);
+            result.push(self.fail_keys(s, t, iffy_keys));
+        }
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    //cargo test compare::compare_snapshot::test::test_snapshot_frame --  --nocapture
+    #[test]
+    fn test_snapshot_frame() {
+        let entries: Vec<SnapshotEntry> = (0..100)
+            .map(|i| SnapshotEntry {
+                db: 0,
+                key: format!("key{}", i).into_bytes(),
+                value: MemValue::List(vec![b"item".to_vec(); 10].into()),
+                expire_at_ms: Some(i),
+                source: 1,
+            })
+            .collect();
+        let mut buf = SNAPSHOT_MAGIC.to_vec();
+        write_frame(&mut buf, &entries).unwrap();
+
+        let mut reader = &buf[SNAPSHOT_MAGIC.len()..];
+        let decoded: Vec<SnapshotEntry> = read_frame(&mut reader).unwrap().unwrap();
+        assert_eq!(decoded, entries);
+        assert!(read_frame::<_, Vec<SnapshotEntry>>(&mut reader)
+            .unwrap()
+            .is_none());
+    }
+
+    //cargo test compare::compare_snapshot::test::test_snapshot_source --  --nocapture
+    #[test]
+    fn test_snapshot_source() {
+        // 旧版本快照的 entry 没有 source 字段
+        let mut buf = Vec::new();
+        (
+            0usize,
+            b"k".to_vec(),
+            MemValue::String(b"v".to_vec()),
+            None::<i64>,
+        )
+            .serialize(&mut Serializer::new(&mut buf))
+            .unwrap();
+        let entry: SnapshotEntry = rmp_serde::from_slice(&buf).unwrap();
+        assert_eq!((entry.db, entry.source), (0, 0));
+
+        let instance = |url: &str, db: usize| {
+            let mut s = RedisInstanceWithDB {
+                db,
+                ..Default::default()
+            };
+            s.instance.urls = vec![url.to_string()];
+            s
+        };
+        let header = SnapshotHeader {
+            created_at: 0,
+            sources: vec![
+                instance("redis://a", 0),
+                instance("redis://b", 0),
+                instance("redis://a", 1),
+            ],
+        };
+        assert_eq!(
+            snapshot_source_index(&header, &instance("redis://b", 0)).unwrap(),
+            1
+        );
+        assert_eq!(
+            snapshot_source_index(&header, &instance("redis://c", 1)).unwrap(),
+            2
+        );
+        assert!(snapshot_source_index(&header, &instance("redis://c", 0)).is_err());
+    }
+}
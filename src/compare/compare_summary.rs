@@ -0,0 +1,208 @@
+use std::collections::{BTreeMap, HashMap};
+
+use anyhow::{anyhow, Result};
+use redis::ConnectionLike;
+
+use crate::util::{count_keys_in_slot, info, scan, InfoSection};
+
+use super::rediscompare::{Compare, InstanceType, RedisInstance, RedisInstanceWithDB};
+
+// 单个 DB 的 keyspace 概况，cluster 为各节点之和
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct DBSummary {
+    pub dbsize: usize,
+    // INFO keyspace 中的 keys、expires
+    pub keys: usize,
+    pub expires: usize,
+    // 抽样 SCAN 得到的各类型 key 数量
+    pub types: BTreeMap<String, usize>,
+}
+
+impl DBSummary {
+    fn merge(&mut self, other: &DBSummary) {
+        self.dbsize += other.dbsize;
+        self.keys += other.keys;
+        self.expires += other.expires;
+        for (t, c) in &other.types {
+            *self.types.entry(t.clone()).or_insert(0) += c;
+        }
+    }
+}
+
+impl RedisInstanceWithDB {
+    // 逐节点统计 DBSIZE、INFO keyspace，并抽样 sample 个 key 统计类型分布
+    // cluster 只统计各 master，replica 的数据与 master 重复，urls 只配置部分节点时也能覆盖全部 slot
+    pub fn summary(&self, sample: usize) -> Result<DBSummary> {
+        let mut summary = DBSummary::default();
+        if self.instance.instance_type.eq(&InstanceType::Cluster) {
+            for (client, _) in self.instance.cluster_master_clients()? {
+                let mut conn = self.instance.timeouts().single_connection(&client)?;
+                summary.merge(&self.node_summary(&mut conn, sample)?);
+            }
+            return Ok(summary);
+        }
+
+        for node in self.to_single_redis_instance_with_db_vec() {
+            let client = node.to_redis_client_with_db()?;
+            let mut conn = client.get_redis_connection()?.get_dyn_connection();
+            summary.merge(&self.node_summary(conn.as_mut(), sample)?);
+        }
+        Ok(summary)
+    }
+
+    fn node_summary(&self, conn: &mut dyn ConnectionLike, sample: usize) -> Result<DBSummary> {
+        let dbsize: usize = redis::cmd("dbsize").query(conn)?;
+        let mut node_summary = DBSummary {
+            dbsize,
+            ..Default::default()
+        };
+
+        let keyspace = info(InfoSection::Keyspace, conn)?;
+        if let Some(section) = keyspace.get("# Keyspace") {
+            if let Some(v) = section.get(&format!("db{}", self.db)) {
+                let (keys, expires) = parse_keyspace(v);
+                node_summary.keys = keys;
+                node_summary.expires = expires;
+            }
+        }
+
+        let keys: Vec<String> = scan::<String>(conn)?.take(sample).collect();
+        for key in keys {
+            let key_type: String = redis::cmd("type").arg(key).query(conn)?;
+            *node_summary.types.entry(key_type).or_insert(0) += 1;
+        }
+        Ok(node_summary)
+    }
+}
+
+impl RedisInstance {
+    // 统计 cluster 每个 slot 的 key 数量，由负责该 slot 的 master 执行 COUNTKEYSINSLOT
+    pub fn slot_key_counts(&self) -> Result<BTreeMap<u16, usize>> {
+        if !self.instance_type.eq(&InstanceType::Cluster) {
+            return Err(anyhow!("instance is not cluster"));
+        }
+        let mut counts = BTreeMap::new();
+        for (client, slots) in self.cluster_master_clients()? {
+            let mut conn = self.timeouts().single_connection(&client)?;
+            for slot in slots {
+                counts.insert(slot, count_keys_in_slot(slot, &mut conn)?);
+            }
+        }
+        Ok(counts)
+    }
+}
+
+// 解析 keys=1,expires=0,avg_ttl=0
+fn parse_keyspace(value: &str) -> (usize, usize) {
+    let mut keys = 0;
+    let mut expires = 0;
+    for kv in value.split(',') {
+        match kv.split_once('=') {
+            Some(("keys", v)) => keys = v.parse().unwrap_or(0),
+            Some(("expires", v)) => expires = v.parse().unwrap_or(0),
+            _ => {}
+        }
+    }
+    (keys, expires)
+}
+
+// 差异比例，以 source 数量为基准
+fn diff_ratio(source: usize, target: usize) -> f64 {
+    let diff = (source as f64 - target as f64).abs();
+    diff / source.max(1) as f64
+}
+
+impl Compare {
+    // 全量校验前的快速预检，按 target DB 汇总映射到该 DB 的所有 source DB
+    // key 数量差异比例超过 threshold 时立即返回错误
+    pub fn summary(&self, sample: usize, threshold: f64) -> Result<()> {
+        let map = self.map_dbinstance_source_to_target()?;
+        let mut t_to_s: HashMap<RedisInstanceWithDB, Vec<RedisInstanceWithDB>> = HashMap::new();
+        for (s, t) in map {
+            t_to_s.entry(t).or_default().push(s);
+        }
+
+        for (t, sources) in &t_to_s {
+            let mut s_summary = DBSummary::default();
+            for s in sources {
+                let summary = s.summary(sample)?;
+                println!(
+                    "source {:?} db{}: dbsize={} keys={} expires={} types={:?}",
+                    s.instance.urls,
+                    s.db,
+                    summary.dbsize,
+                    summary.keys,
+                    summary.expires,
+                    summary.types
+                );
+                s_summary.merge(&summary);
+            }
+            let t_summary = t.summary(sample)?;
+            println!(
+                "target {:?} db{}: dbsize={} keys={} expires={} types={:?}",
+                t.instance.urls,
+                t.db,
+                t_summary.dbsize,
+                t_summary.keys,
+                t_summary.expires,
+                t_summary.types
+            );
+            println!(
+                "delta db{}: dbsize={} expires={}",
+                t.db,
+                t_summary.dbsize as i64 - s_summary.dbsize as i64,
+                t_summary.expires as i64 - s_summary.expires as i64
+            );
+
+            let ratio = diff_ratio(s_summary.dbsize, t_summary.dbsize);
+            if ratio > threshold {
+                return Err(anyhow!(
+                    "target db{} key count {} diverges from source {} by {:.4}, threshold {}",
+                    t.db,
+                    t_summary.dbsize,
+                    s_summary.dbsize,
+                    ratio,
+                    threshold
+                ));
+            }
+        }
+
+        // 两端均为 cluster 时逐 slot 比较 key 数量
+        if self.target.instance_type.eq(&InstanceType::Cluster)
+            && self.source.len() == 1
+            && self.source[0]
+                .instance
+                .instance_type
+                .eq(&InstanceType::Cluster)
+        {
+            let s_counts = self.source[0].instance.slot_key_counts()?;
+            let t_counts = self.target.slot_key_counts()?;
+            let mut diff_slots = 0;
+            for slot in 0..16384u16 {
+                let s = s_counts.get(&slot).copied().unwrap_or(0);
+                let t = t_counts.get(&slot).copied().unwrap_or(0);
+                if s != t {
+                    diff_slots += 1;
+                    println!("slot {}: source={} target={}", slot, s, t);
+                }
+            }
+            println!("slots with different key count: {}", diff_slots);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    //cargo test compare::compare_summary::test::test_parse_keyspace --  --nocapture
+    #[test]
+    fn test_parse_keyspace() {
+        assert_eq!(parse_keyspace("keys=12,expires=3,avg_ttl=100"), (12, 3));
+        assert_eq!(parse_keyspace(""), (0, 0));
+        assert_eq!(diff_ratio(100, 99), 0.01);
+        assert_eq!(diff_ratio(0, 0), 0.0);
+    }
+}
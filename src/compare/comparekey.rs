@@ -1,18 +1,272 @@
-use super::{compare_error::CompareErrorReason, Position};
+use super::{
+    big_key::BigKeyPolicy, compare_error::CompareErrorReason, compare_slots::CLUSTER_SLOTS,
+    DiffKind, ElementDiff, KeyDiff, KeyMapper, Position,
+};
 use crate::compare::compare_error::{CompareError, CompareErrorType};
 use crate::util::{
-    hget, hlen, key_exists, list_len, lrange, scard, sismumber, ttl, zcard, zscore, RedisKey,
-    RedisKeyType,
+    geo_distance, hget, hlen, is_geohash_score, is_hll, key_exists, list_len, lrange, scard,
+    sismumber, ttl, zcard, zrange_withscores, zscore, RedisKey, RedisKeyType,
 };
 use redis::{ConnectionLike, Iter};
-use serde::{Deserialize, Serialize};
+use regex::Regex;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::sync::{Arc, OnceLock};
 
 pub type CompareResult<T, E = CompareError> = core::result::Result<T, E>;
 
+// DUMP 尾部 RDB 版本与 CRC64 的长度
+const DUMP_TRAILER_LEN: usize = 10;
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct IffyKey {
     pub key: RedisKey,
     pub error: CompareError,
+    // full_diff 模式下 key 的完整差异
+    #[serde(default)]
+    pub diff: Option<KeyDiff>,
+}
+
+// Comparer 校验选项，随 FailKeys 一起保存，循环校验时沿用
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct CompareOptions {
+    // zset score 允许的绝对误差，为 0 时精确比较
+    #[serde(default = "CompareOptions::zset_score_abs_epsilon_default")]
+    pub zset_score_abs_epsilon: f64,
+    // zset score 允许的相对误差，以两端 score 绝对值较大者为基准
+    #[serde(default = "CompareOptions::zset_score_rel_epsilon_default")]
+    pub zset_score_rel_epsilon: f64,
+    // 通过 ZRANGE WITHSCORES 分页校验 zset 元素顺序，用于确认相同 score 元素的字典序
+    #[serde(default = "CompareOptions::zset_rank_check_default")]
+    pub zset_rank_check: bool,
+    // 校验失败的 key 输出全部差异元素，而不仅是第一个
+    #[serde(default = "CompareOptions::full_diff_default")]
+    pub full_diff: bool,
+    // 单个 key 记录的差异元素上限，0 为不限制
+    #[serde(default = "CompareOptions::full_diff_max_default")]
+    pub full_diff_max: usize,
+    // string 长度达到该值时按 GETRANGE 分片比较，0 为不分片
+    #[serde(default = "CompareOptions::big_string_threshold_default")]
+    pub big_string_threshold: usize,
+    // 分片大小，单位 byte
+    #[serde(default = "CompareOptions::string_chunk_size_default")]
+    pub string_chunk_size: usize,
+    // 在服务端通过 lua 计算分片 SHA1，仅在摘要不同时拉取分片内容
+    #[serde(default = "CompareOptions::string_chunk_sha1_default")]
+    pub string_chunk_sha1: bool,
+    // 仅校验 target 中属于这些 slot 的 key，为空时校验全部，通常由 compare slots 生成
+    #[serde(default = "CompareOptions::only_slots_default")]
+    pub only_slots: Vec<u16>,
+    // 元素数量达到该值视为大 key，string 按字节数计算，0 为不按元素数量检测
+    #[serde(default = "CompareOptions::big_key_elements_default")]
+    pub big_key_elements: usize,
+    // MEMORY USAGE 达到该值视为大 key，单位 byte，0 为不按内存检测
+    #[serde(default = "CompareOptions::big_key_memory_default")]
+    pub big_key_memory: usize,
+    // 大 key 处理策略：compare 完整比较，skip 跳过，sample 抽样比较
+    #[serde(default = "CompareOptions::big_key_policy_default")]
+    pub big_key_policy: BigKeyPolicy,
+    // sample 策略下每个大 key 抽样比较的元素数量
+    #[serde(default = "CompareOptions::big_key_sample_default")]
+    pub big_key_sample: usize,
+    // HyperLogLog 编码不同时按 PFCOUNT 比较，允许的相对误差，为 0 时基数必须相等
+    #[serde(default = "CompareOptions::hll_count_tolerance_default")]
+    pub hll_count_tolerance: f64,
+    // 匹配这些正则的 zset 作为 geo 索引，按 GEOPOS 坐标比较
+    #[serde(
+        default = "CompareOptions::geo_key_patterns_default",
+        deserialize_with = "CompareOptions::deserialize_geo_key_patterns"
+    )]
+    pub geo_key_patterns: Vec<String>,
+    // 自动识别 geo 索引：source 前 10 个元素的 score 均像 geohash，可能误判整数 score 的普通 zset
+    #[serde(default = "CompareOptions::geo_auto_detect_default")]
+    pub geo_auto_detect: bool,
+    // geo member 两端坐标允许的距离，单位米
+    #[serde(default = "CompareOptions::geo_tolerance_meters_default")]
+    pub geo_tolerance_meters: f64,
+    // 首次使用时由上面的选项生成，不参与序列化与比较
+    #[serde(skip)]
+    pub(crate) compiled: CompiledCache,
+}
+
+// 由 CompareOptions 预先生成、逐 key 使用的数据
+#[derive(Debug)]
+struct CompiledOptions {
+    geo_key_patterns: Vec<Regex>,
+    // only_slots 的位图，每个 slot 一位，only_slots 为空时为 None
+    only_slots: Option<Vec<u64>>,
+}
+
+// clone 时共享已生成的数据，options 在加载配置后不再修改
+#[derive(Debug, Default, Clone)]
+pub(crate) struct CompiledCache(OnceLock<Arc<CompiledOptions>>);
+
+impl PartialEq for CompiledCache {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+impl Default for CompareOptions {
+    fn default() -> Self {
+        Self {
+            zset_score_abs_epsilon: 0.0,
+            zset_score_rel_epsilon: 0.0,
+            zset_rank_check: false,
+            full_diff: false,
+            full_diff_max: 1000,
+            big_string_threshold: 16 * 1024 * 1024,
+            string_chunk_size: 1024 * 1024,
+            string_chunk_sha1: false,
+            only_slots: vec![],
+            big_key_elements: 0,
+            big_key_memory: 0,
+            big_key_policy: BigKeyPolicy::Compare,
+            big_key_sample: 100,
+            hll_count_tolerance: 0.0,
+            geo_key_patterns: vec![],
+            geo_auto_detect: false,
+            geo_tolerance_meters: 1.0,
+            compiled: CompiledCache::default(),
+        }
+    }
+}
+
+impl CompareOptions {
+    fn zset_score_abs_epsilon_default() -> f64 {
+        0.0
+    }
+    fn zset_score_rel_epsilon_default() -> f64 {
+        0.0
+    }
+    fn zset_rank_check_default() -> bool {
+        false
+    }
+    fn full_diff_default() -> bool {
+        false
+    }
+    fn full_diff_max_default() -> usize {
+        1000
+    }
+    fn big_string_threshold_default() -> usize {
+        16 * 1024 * 1024
+    }
+    fn string_chunk_size_default() -> usize {
+        1024 * 1024
+    }
+    fn string_chunk_sha1_default() -> bool {
+        false
+    }
+    fn only_slots_default() -> Vec<u16> {
+        vec![]
+    }
+    fn big_key_elements_default() -> usize {
+        0
+    }
+    fn big_key_memory_default() -> usize {
+        0
+    }
+    fn big_key_policy_default() -> BigKeyPolicy {
+        BigKeyPolicy::Compare
+    }
+    fn big_key_sample_default() -> usize {
+        100
+    }
+    fn hll_count_tolerance_default() -> f64 {
+        0.0
+    }
+    fn geo_key_patterns_default() -> Vec<String> {
+        vec![]
+    }
+    fn geo_auto_detect_default() -> bool {
+        false
+    }
+    fn geo_tolerance_meters_default() -> f64 {
+        1.0
+    }
+
+    // 加载配置时校验正则，无效的 pattern 直接报错
+    fn deserialize_geo_key_patterns<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let patterns = Vec::<String>::deserialize(deserializer)?;
+        for pattern in &patterns {
+            if let Err(e) = Regex::new(pattern) {
+                return Err(serde::de::Error::custom(format!(
+                    "invalid geo_key_patterns {}: {}",
+                    pattern, e
+                )));
+            }
+        }
+        Ok(patterns)
+    }
+
+    fn compiled(&self) -> &CompiledOptions {
+        self.compiled.0.get_or_init(|| {
+            // 通过配置加载的 pattern 已校验，代码中构造的无效 pattern 记录日志后忽略
+            let geo_key_patterns = self
+                .geo_key_patterns
+                .iter()
+                .filter_map(|p| match Regex::new(p) {
+                    Ok(r) => Some(r),
+                    Err(e) => {
+                        log::error!("{}", e);
+                        None
+                    }
+                })
+                .collect();
+            let only_slots = match self.only_slots.is_empty() {
+                true => None,
+                false => {
+                    let mut bits = vec![0u64; CLUSTER_SLOTS as usize / 64];
+                    for slot in self.only_slots.iter().filter(|s| **s < CLUSTER_SLOTS) {
+                        bits[*slot as usize / 64] |= 1 << (slot % 64);
+                    }
+                    Some(bits)
+                }
+            };
+            Arc::new(CompiledOptions {
+                geo_key_patterns,
+                only_slots,
+            })
+        })
+    }
+
+    // key 名是否匹配 geo_key_patterns
+    pub fn geo_key_matched(&self, key: &str) -> bool {
+        self.compiled()
+            .geo_key_patterns
+            .iter()
+            .any(|r| r.is_match(key))
+    }
+
+    // 是否检测大 key
+    pub fn big_key_enabled(&self) -> bool {
+        self.big_key_elements > 0 || self.big_key_memory > 0
+    }
+
+    // key 在 target 中的名称是否属于 only_slots
+    pub fn slot_selected(&self, target_key: &str) -> bool {
+        match &self.compiled().only_slots {
+            Some(bits) => {
+                let slot = redis::cluster_routing::get_slot(target_key.as_bytes()) as usize;
+                bits[slot / 64] & (1 << (slot % 64)) != 0
+            }
+            None => true,
+        }
+    }
+
+    // score 差值在绝对误差或相对误差范围内即视为相等
+    pub fn score_equal(&self, source: f64, target: f64) -> bool {
+        if source == target {
+            return true;
+        }
+        let diff = (source - target).abs();
+        if diff <= self.zset_score_abs_epsilon {
+            return true;
+        }
+        diff <= self.zset_score_rel_epsilon * source.abs().max(target.abs())
+    }
 }
 
 pub struct Comparer {
@@ -22,6 +276,9 @@ pub struct Comparer {
     pub tconn: Box<dyn ConnectionLike>,
     pub ttl_diff: usize,
     pub batch: usize,
+    // source key 到 target key 的改名规则
+    pub key_mapper: KeyMapper,
+    pub options: CompareOptions,
 }
 
 impl Comparer {
@@ -29,27 +286,52 @@ impl Comparer {
     pub fn compare_rediskeys(mut self, keys_vec: &Vec<RedisKey>) -> Vec<IffyKey> {
         let mut iffy_keys = vec![];
         for key in keys_vec {
-            let r = self.compare_key(key.clone());
-            if let Err(e) = r {
-                let iffy = IffyKey {
-                    key: key.clone(),
-                    error: e,
-                };
+            if let Some(iffy) = self.compare_one(key) {
                 iffy_keys.push(iffy);
             }
         }
         iffy_keys
     }
 
+    // 校验单个 key，失败时返回 IffyKey
+    pub fn compare_one(&mut self, key: &RedisKey) -> Option<IffyKey> {
+        let e = self.compare_key(key.clone()).err()?;
+        let diff = match self.options.full_diff {
+            true => self.full_diff(key, &e),
+            false => None,
+        };
+        Some(IffyKey {
+            key: key.clone(),
+            error: e,
+            diff,
+        })
+    }
+
     pub fn compare_key(&mut self, key: RedisKey) -> CompareResult<()> {
         return match key.key_type {
             RedisKeyType::TypeString => self.compare_string(key),
             RedisKeyType::TypeList => self.compare_list(key),
             RedisKeyType::TypeSet => self.compare_set(key),
+            RedisKeyType::TypeZSet if self.is_geo_key(&key) => self.compare_geo(key),
             RedisKeyType::TypeZSet => self.compare_zset(key),
             RedisKeyType::TypeHash => self.compare_hash(key),
+            RedisKeyType::TypeOther(_) => self.compare_dump(key),
         };
     }
+
+    // zset 是否按 geo 索引比较
+    fn is_geo_key(&mut self, key: &RedisKey) -> bool {
+        if self.options.geo_key_matched(&key.key_name) {
+            return true;
+        }
+        if !self.options.geo_auto_detect {
+            return false;
+        }
+        match zrange_withscores(key.key_name.clone(), 0, 9, self.sconn.as_mut()) {
+            Ok(members) => !members.is_empty() && members.iter().all(|(_, s)| is_geohash_score(*s)),
+            Err(_) => false,
+        }
+    }
 }
 
 //ToDo 错误输出统一到CompareError，使用into 获得CompareError，便于存储错误信息
@@ -75,21 +357,22 @@ impl Comparer {
         let (s_len, _t_len) = self.list_len_equal(&key)?;
 
         // 遍历source，核对target中相应的值是否一致
-        let quotient = s_len / self.batch; // integer division, decimals are truncated
-        let remainder = s_len % self.batch;
+        let batch = self.batch.max(1);
+        let quotient = s_len / batch; // integer division, decimals are truncated
+        let remainder = s_len % batch;
 
         let mut lrange_end: isize = 0;
         if quotient != 0 {
             for i in 0..quotient {
                 if i == quotient - 1 {
-                    lrange_end = (quotient * self.batch) as isize;
+                    lrange_end = (quotient * batch) as isize;
                 } else {
-                    lrange_end = ((self.batch - 1) + i * self.batch) as isize;
+                    lrange_end = ((batch - 1) + i * batch) as isize;
                 }
 
                 let s_elements = lrange(
                     key.key_name.clone(),
-                    (0 + i * self.batch) as isize,
+                    (0 + i * batch) as isize,
                     lrange_end,
                     self.sconn.as_mut(),
                 )
@@ -100,8 +383,8 @@ impl Comparer {
                     )
                 })?;
                 let t_elements = lrange(
-                    key.key_name.clone(),
-                    (0 + i * self.batch) as isize,
+                    self.target_key(&key.key_name),
+                    (0 + i * batch) as isize,
                     lrange_end,
                     self.tconn.as_mut(),
                 )
@@ -144,22 +427,22 @@ impl Comparer {
             if quotient == 0 {
                 start = 0;
             } else {
-                start = (quotient * self.batch) as isize + 1;
+                start = (quotient * batch) as isize + 1;
             }
 
             let s_elements = lrange(
                 key.key_name.clone(),
                 start,
-                (remainder + quotient * self.batch) as isize,
+                (remainder + quotient * batch) as isize,
                 self.sconn.as_mut(),
             )
             .map_err(|e| -> CompareError {
                 CompareError::from_str(e.to_string().as_str(), CompareErrorType::RedisConnectionErr)
             })?;
             let t_elements = lrange(
-                key.key_name.clone(),
+                self.target_key(&key.key_name),
                 start,
-                (remainder + quotient * self.batch) as isize,
+                (remainder + quotient * batch) as isize,
                 self.tconn.as_mut(),
             )
             .map_err(|e| -> CompareError {
@@ -200,12 +483,15 @@ impl Comparer {
         // target端key是否存在
         self.target_key_exists(&key)?;
 
-        // 比较 set 元素数量 是否一致
-        self.set_members_number_equal(&key)?;
-
         // 遍历source，核对在target是否存在
         self.set_source_member_in_target(&key)?;
 
+        // 遍历target，核对在source是否存在
+        self.set_target_member_in_source(&key)?;
+
+        // 比较 set 元素数量 是否一致，元素双向校验后用于发现校验期间的数据变化
+        self.set_members_number_equal(&key)?;
+
         // ttl差值是否在规定范围内
         self.ttl_diff(&key)?;
         Ok(())
@@ -215,35 +501,133 @@ impl Comparer {
         // target端key是否存在
         self.target_key_exists(&key)?;
 
+        // 遍历source，核对在target score 和 值是否一致
+        self.zset_source_members_in_target(&key)?;
+
+        // 遍历target，核对 member 在 source 中是否存在
+        self.zset_target_members_in_source(&key)?;
+
         // 比较 zset 元素数量 是否一致
         self.zset_members_number_equal(&key)?;
 
-        // 遍历source，核对在target score 和 值是否一致
-        self.zset_source_members_in_target(&key)?;
+        // 校验元素顺序
+        if self.options.zset_rank_check {
+            self.zset_rank_equal(&key)?;
+        }
 
         // ttl差值是否在规定范围内
         self.ttl_diff(&key)?;
         Ok(())
     }
 
-    pub fn compare_hash(&mut self, key: RedisKey) -> CompareResult<()> {
+    pub fn compare_geo(&mut self, key: RedisKey) -> CompareResult<()> {
         // target端key是否存在
         self.target_key_exists(&key)?;
 
-        // 比较 hash 元素数量 是否一致
-        self.hash_len_equal(&key)?;
+        // 遍历source，核对 member 在 target 中的坐标
+        self.geo_source_members_in_target(&key)?;
+
+        // 遍历target，核对 member 在 source 中是否存在
+        self.zset_target_members_in_source(&key)?;
+
+        // 比较 zset 元素数量 是否一致
+        self.zset_members_number_equal(&key)?;
+
+        // ttl差值是否在规定范围内
+        self.ttl_diff(&key)?;
+        Ok(())
+    }
+
+    pub fn compare_hash(&mut self, key: RedisKey) -> CompareResult<()> {
+        // target端key是否存在
+        self.target_key_exists(&key)?;
 
         // 遍历source，核对在target field 和 value 是否一致
         self.hash_field_vale_equal(&key)?;
 
+        // 遍历target，核对 field 在 source 中是否存在
+        self.hash_target_field_in_source(&key)?;
+
+        // 比较 hash 元素数量 是否一致
+        self.hash_len_equal(&key)?;
+
         // ttl差值是否在规定范围内
         self.ttl_diff(&key)?;
 
         Ok(())
     }
+
+    // 模块类型等未知类型，比较两端 DUMP 内容
+    pub fn compare_dump(&mut self, key: RedisKey) -> CompareResult<()> {
+        // target端key是否存在
+        self.target_key_exists(&key)?;
+
+        // 比较两端类型名是否一致
+        self.key_type_equal(&key)?;
+
+        // 去掉 RDB 版本与 CRC 后比较 DUMP 内容
+        self.dump_payload_equal(&key)?;
+
+        // ttl差值是否在规定范围内
+        self.ttl_diff(&key)?;
+        Ok(())
+    }
 }
 
 impl Comparer {
+    // key 在 target 中的名称
+    fn target_key(&self, key_name: &str) -> String {
+        self.key_mapper.to_target(key_name)
+    }
+
+    fn key_type_equal(&mut self, key: &RedisKey) -> CompareResult<()> {
+        let t_type: String = redis::cmd("type")
+            .arg(self.target_key(&key.key_name))
+            .query(self.tconn.as_mut())
+            .map_err(redis_err)?;
+        if !key.key_type.to_string().eq(&t_type) {
+            let reason = CompareErrorReason {
+                redis_key: key.clone(),
+                position: None,
+                source: Some(key.key_type.to_string()),
+                target: Some(t_type),
+            };
+            return Err(CompareError::from_reason(
+                reason,
+                CompareErrorType::KeyTypeDiff,
+            ));
+        }
+        Ok(())
+    }
+
+    // DUMP 末尾的 RDB 版本与 CRC64 随 redis 版本变化，仅比较数据部分
+    fn dump_payload_equal(&mut self, key: &RedisKey) -> CompareResult<()> {
+        let s_dump: Option<Vec<u8>> = redis::cmd("dump")
+            .arg(key.key_name.clone())
+            .query(self.sconn.as_mut())
+            .map_err(redis_err)?;
+        let t_dump: Option<Vec<u8>> = redis::cmd("dump")
+            .arg(self.target_key(&key.key_name))
+            .query(self.tconn.as_mut())
+            .map_err(redis_err)?;
+        let s_dump = s_dump.unwrap_or_default();
+        let t_dump = t_dump.unwrap_or_default();
+
+        if !dump_body(&s_dump).eq(dump_body(&t_dump)) {
+            let reason = CompareErrorReason {
+                redis_key: key.clone(),
+                position: None,
+                source: Some(format!("{} ({} bytes)", key.key_type, s_dump.len())),
+                target: Some(format!("{} ({} bytes)", key.key_type, t_dump.len())),
+            };
+            return Err(CompareError::from_reason(
+                reason,
+                CompareErrorType::DumpPayloadDiff,
+            ));
+        }
+        Ok(())
+    }
+
     // key exist 校验
     // 校验规则，当exists 值相等时，返回true
     fn target_key_exists(&mut self, redis_key: &RedisKey) -> CompareResult<()> {
@@ -253,11 +637,10 @@ impl Comparer {
             },
         )?;
 
-        let t_exist = key_exists(redis_key.key_name.clone(), self.tconn.as_mut()).map_err(
-            |e| -> CompareError {
+        let t_exist = key_exists(self.target_key(&redis_key.key_name), self.tconn.as_mut())
+            .map_err(|e| -> CompareError {
                 CompareError::from_str(e.to_string().as_str(), CompareErrorType::RedisConnectionErr)
-            },
-        )?;
+            })?;
 
         if !s_exist.eq(&t_exist) {
             let reason = CompareErrorReason {
@@ -279,10 +662,11 @@ impl Comparer {
             ttl(redis_key.key_name.clone(), self.sconn.as_mut()).map_err(|e| -> CompareError {
                 CompareError::from_str(e.to_string().as_str(), CompareErrorType::RedisConnectionErr)
             })?;
-        let t_ttl =
-            ttl(redis_key.key_name.clone(), self.tconn.as_mut()).map_err(|e| -> CompareError {
+        let t_ttl = ttl(self.target_key(&redis_key.key_name), self.tconn.as_mut()).map_err(
+            |e| -> CompareError {
                 CompareError::from_str(e.to_string().as_str(), CompareErrorType::RedisConnectionErr)
-            })?;
+            },
+        )?;
 
         if self.ttl_diff < (s_ttl - t_ttl).abs() as usize {
             let reason: CompareErrorReason = CompareErrorReason {
@@ -310,25 +694,44 @@ impl Comparer {
             ));
         }
 
-        let sval: String = redis::cmd("get")
+        if self.options.big_string_threshold > 0 {
+            let s_len: usize = redis::cmd("strlen")
+                .arg(key.key_name.clone())
+                .query(self.sconn.as_mut())
+                .map_err(|e| -> CompareError {
+                    CompareError::from_str(
+                        e.to_string().as_str(),
+                        CompareErrorType::RedisConnectionErr,
+                    )
+                })?;
+            if s_len >= self.options.big_string_threshold {
+                return self.string_chunks_equal(key, s_len);
+            }
+        }
+
+        let sval: Vec<u8> = redis::cmd("get")
             .arg(key.key_name.clone())
             .query(self.sconn.as_mut())
             .map_err(|e| -> CompareError {
                 CompareError::from_str(e.to_string().as_str(), CompareErrorType::RedisConnectionErr)
             })?;
-        let tval: String = redis::cmd("get")
-            .arg(key.key_name.clone())
+        let tval: Vec<u8> = redis::cmd("get")
+            .arg(self.target_key(&key.key_name))
             .query(self.tconn.as_mut())
             .map_err(|e| -> CompareError {
                 CompareError::from_str(e.to_string().as_str(), CompareErrorType::RedisConnectionErr)
             })?;
 
         if !sval.eq(&tval) {
+            // 等价的 HyperLogLog 可能分别为稀疏、稠密编码，按基数比较
+            if is_hll(&sval) && is_hll(&tval) {
+                return self.hll_count_equal(key);
+            }
             let reason = CompareErrorReason {
                 redis_key: key.clone(),
                 position: None,
-                source: Some(sval.clone()),
-                target: Some(tval.clone()),
+                source: Some(String::from_utf8_lossy(&sval).to_string()),
+                target: Some(String::from_utf8_lossy(&tval).to_string()),
             };
             return Err(CompareError::from_reason(
                 reason,
@@ -338,6 +741,75 @@ impl Comparer {
         Ok(())
     }
 
+    // 比较两端 PFCOUNT，相对误差不超过 hll_count_tolerance 视为一致
+    fn hll_count_equal(&mut self, key: &RedisKey) -> CompareResult<()> {
+        let s_count: u64 = redis::cmd("pfcount")
+            .arg(key.key_name.clone())
+            .query(self.sconn.as_mut())
+            .map_err(redis_err)?;
+        let t_count: u64 = redis::cmd("pfcount")
+            .arg(self.target_key(&key.key_name))
+            .query(self.tconn.as_mut())
+            .map_err(redis_err)?;
+
+        let diff = s_count.abs_diff(t_count) as f64;
+        if diff > self.options.hll_count_tolerance * s_count.max(t_count) as f64 {
+            let reason = CompareErrorReason {
+                redis_key: key.clone(),
+                position: None,
+                source: Some(s_count.to_string()),
+                target: Some(t_count.to_string()),
+            };
+            return Err(CompareError::from_reason(
+                reason,
+                CompareErrorType::HyperLogLogCountDiff,
+            ));
+        }
+        Ok(())
+    }
+
+    // 大 string 按分片比较，避免一次性读取整个 value，返回第一个不同字节的偏移量
+    fn string_chunks_equal(&mut self, key: &RedisKey, s_len: usize) -> CompareResult<()> {
+        let t_key = self.target_key(&key.key_name);
+        let t_len: usize = redis::cmd("strlen")
+            .arg(t_key.clone())
+            .query(self.tconn.as_mut())
+            .map_err(|e| -> CompareError {
+                CompareError::from_str(e.to_string().as_str(), CompareErrorType::RedisConnectionErr)
+            })?;
+
+        let chunk = self.options.string_chunk_size.max(1);
+        let mut start = 0;
+        while start < s_len.max(t_len) {
+            let end = start + chunk - 1;
+            if self.options.string_chunk_sha1 {
+                let s_sha1 = getrange_sha1(&key.key_name, start, end, self.sconn.as_mut())?;
+                let t_sha1 = getrange_sha1(&t_key, start, end, self.tconn.as_mut())?;
+                if s_sha1.eq(&t_sha1) {
+                    start += chunk;
+                    continue;
+                }
+            }
+
+            let s_bytes = getrange(&key.key_name, start, end, self.sconn.as_mut())?;
+            let t_bytes = getrange(&t_key, start, end, self.tconn.as_mut())?;
+            if let Some(offset) = first_diff_offset(&s_bytes, &t_bytes) {
+                let reason = CompareErrorReason {
+                    redis_key: key.clone(),
+                    position: Some(Position::StringOffset(start + offset)),
+                    source: Some(s_len.to_string()),
+                    target: Some(t_len.to_string()),
+                };
+                return Err(CompareError::from_reason(
+                    reason,
+                    CompareErrorType::StringValueNotEqual,
+                ));
+            }
+            start += chunk;
+        }
+        Ok(())
+    }
+
     fn list_len_equal(&mut self, key: &RedisKey) -> CompareResult<(usize, usize)> {
         if !key.key_type.eq(&RedisKeyType::TypeList) {
             let reason = CompareErrorReason {
@@ -356,10 +828,11 @@ impl Comparer {
             list_len(key.key_name.clone(), self.sconn.as_mut()).map_err(|e| -> CompareError {
                 CompareError::from_str(e.to_string().as_str(), CompareErrorType::RedisConnectionErr)
             })?;
-        let t_len =
-            list_len(key.key_name.clone(), self.tconn.as_mut()).map_err(|e| -> CompareError {
+        let t_len = list_len(self.target_key(&key.key_name), self.tconn.as_mut()).map_err(
+            |e| -> CompareError {
                 CompareError::from_str(e.to_string().as_str(), CompareErrorType::RedisConnectionErr)
-            })?;
+            },
+        )?;
 
         if !s_len.eq(&t_len) {
             let reason = CompareErrorReason {
@@ -395,10 +868,11 @@ impl Comparer {
             scard(key.key_name.clone(), self.sconn.as_mut()).map_err(|e| -> CompareError {
                 CompareError::from_str(e.to_string().as_str(), CompareErrorType::RedisConnectionErr)
             })?;
-        let t_size =
-            scard(key.key_name.clone(), self.tconn.as_mut()).map_err(|e| -> CompareError {
+        let t_size = scard(self.target_key(&key.key_name), self.tconn.as_mut()).map_err(
+            |e| -> CompareError {
                 CompareError::from_str(e.to_string().as_str(), CompareErrorType::RedisConnectionErr)
-            })?;
+            },
+        )?;
 
         if !s_size.eq(&t_size) {
             let reason: CompareErrorReason = CompareErrorReason {
@@ -429,6 +903,7 @@ impl Comparer {
                 CompareErrorType::KeyTypeNotSet,
             ));
         }
+        let t_key = self.target_key(&key.key_name);
         let mut cmd_sscan = redis::cmd("sscan");
         cmd_sscan.arg(key.key_name.clone()).cursor_arg(0);
         let iter: Iter<String> =
@@ -441,7 +916,7 @@ impl Comparer {
                     )
                 })?;
         for item in iter {
-            let is = sismumber(key.key_name.clone(), item.clone(), self.tconn.as_mut()).map_err(
+            let is = sismumber(t_key.clone(), item.clone(), self.tconn.as_mut()).map_err(
                 |e| -> CompareError {
                     CompareError::from_str(
                         e.to_string().as_str(),
@@ -452,7 +927,7 @@ impl Comparer {
             if !is {
                 let reason: CompareErrorReason = CompareErrorReason {
                     redis_key: key.clone(),
-                    position: None,
+                    position: Some(Position::SetMember(item.clone())),
                     source: Some(item.clone()),
                     target: None,
                 };
@@ -465,6 +940,35 @@ impl Comparer {
         Ok(())
     }
 
+    // 遍历target，核对在source是否存在
+    fn set_target_member_in_source(&mut self, key: &RedisKey) -> CompareResult<()> {
+        let t_key = self.target_key(&key.key_name);
+        let iter = scan_iter("sscan", &t_key, self.tconn.as_mut())?;
+        for item in iter {
+            let is = sismumber(key.key_name.clone(), item.clone(), self.sconn.as_mut()).map_err(
+                |e| -> CompareError {
+                    CompareError::from_str(
+                        e.to_string().as_str(),
+                        CompareErrorType::RedisConnectionErr,
+                    )
+                },
+            )?;
+            if !is {
+                let reason = CompareErrorReason {
+                    redis_key: key.clone(),
+                    position: Some(Position::SetMember(item.clone())),
+                    source: None,
+                    target: Some(item),
+                };
+                return Err(CompareError::from_reason(
+                    reason,
+                    CompareErrorType::SetMemberNotInSource,
+                ));
+            }
+        }
+        Ok(())
+    }
+
     fn zset_members_number_equal(&mut self, key: &RedisKey) -> CompareResult<()> {
         if !key.key_type.eq(&RedisKeyType::TypeZSet) {
             let reason = CompareErrorReason {
@@ -482,10 +986,11 @@ impl Comparer {
             zcard(key.key_name.clone(), self.sconn.as_mut()).map_err(|e| -> CompareError {
                 CompareError::from_str(e.to_string().as_str(), CompareErrorType::RedisConnectionErr)
             })?;
-        let t_size =
-            zcard(key.key_name.clone(), self.tconn.as_mut()).map_err(|e| -> CompareError {
+        let t_size = zcard(self.target_key(&key.key_name), self.tconn.as_mut()).map_err(
+            |e| -> CompareError {
                 CompareError::from_str(e.to_string().as_str(), CompareErrorType::RedisConnectionErr)
-            })?;
+            },
+        )?;
 
         if !s_size.eq(&t_size) {
             let reason: CompareErrorReason = CompareErrorReason {
@@ -515,6 +1020,7 @@ impl Comparer {
                 CompareErrorType::KeyTypeNotZSet,
             ));
         }
+        let t_key = self.target_key(&key.key_name);
         let mut cmd_zscan = redis::cmd("zscan");
         cmd_zscan.arg(key.key_name.clone()).cursor_arg(0);
         let iter: Iter<String> =
@@ -533,20 +1039,28 @@ impl Comparer {
             if count % 2 == 0 {
                 member = item.clone();
             } else {
-                let t_scroe = zscore(key.key_name.clone(), member.clone(), self.tconn.as_mut())
-                    .map_err(|e| -> CompareError {
+                let t_scroe = zscore(t_key.clone(), member.clone(), self.tconn.as_mut()).map_err(
+                    |e| -> CompareError {
                         CompareError::from_str(
                             e.to_string().as_str(),
                             CompareErrorType::RedisConnectionErr,
                         )
-                    })?;
+                    },
+                )?;
+                let s_score = item.parse::<f64>().map_err(|e| -> CompareError {
+                    CompareError::from_str(e.to_string().as_str(), CompareErrorType::Unknown)
+                })?;
 
-                if !t_scroe.to_string().eq(&item) {
+                let equal = match t_scroe {
+                    Some(t) => self.options.score_equal(s_score, t),
+                    None => false,
+                };
+                if !equal {
                     let reason: CompareErrorReason = CompareErrorReason {
                         redis_key: key.clone(),
                         position: Some(Position::ZsetMember(member.clone())),
                         source: Some(item.clone()),
-                        target: Some(t_scroe.to_string()),
+                        target: t_scroe.map(|t| t.to_string()),
                     };
                     return Err(CompareError::from_reason(
                         reason,
@@ -559,27 +1073,176 @@ impl Comparer {
         Ok(())
     }
 
-    fn hash_len_equal(&mut self, key: &RedisKey) -> CompareResult<()> {
-        if !key.key_type.eq(&RedisKeyType::TypeHash) {
-            let reason = CompareErrorReason {
-                redis_key: key.clone(),
-                position: None,
-                source: None,
-                target: None,
-            };
-            return Err(CompareError::from_reason(
-                reason,
-                CompareErrorType::KeyTypeNotHash,
-            ));
+    // 按 batch 分页遍历 source geo 索引，比较两端 GEOPOS 坐标的距离
+    fn geo_source_members_in_target(&mut self, key: &RedisKey) -> CompareResult<()> {
+        let t_key = self.target_key(&key.key_name);
+        let batch = self.batch.max(1);
+        let mut start = 0;
+        loop {
+            let members: Vec<String> = redis::cmd("zrange")
+                .arg(key.key_name.clone())
+                .arg(start)
+                .arg(start + batch - 1)
+                .query(self.sconn.as_mut())
+                .map_err(redis_err)?;
+            if members.is_empty() {
+                return Ok(());
+            }
+            let s_pos: Vec<Option<(f64, f64)>> = redis::cmd("geopos")
+                .arg(key.key_name.clone())
+                .arg(&members)
+                .query(self.sconn.as_mut())
+                .map_err(redis_err)?;
+            let t_pos: Vec<Option<(f64, f64)>> = redis::cmd("geopos")
+                .arg(t_key.clone())
+                .arg(&members)
+                .query(self.tconn.as_mut())
+                .map_err(redis_err)?;
+
+            for ((member, s), t) in members.iter().zip(s_pos).zip(t_pos) {
+                // 比较期间 source 中已删除的 member
+                let (s_lon, s_lat) = match s {
+                    Some(p) => p,
+                    None => continue,
+                };
+                let equal = match t {
+                    Some((t_lon, t_lat)) => {
+                        geo_distance(s_lon, s_lat, t_lon, t_lat)
+                            <= self.options.geo_tolerance_meters
+                    }
+                    None => false,
+                };
+                if !equal {
+                    let reason = CompareErrorReason {
+                        redis_key: key.clone(),
+                        position: Some(Position::ZsetMember(member.clone())),
+                        source: Some(format!("{},{}", s_lon, s_lat)),
+                        target: t.map(|(lon, lat)| format!("{},{}", lon, lat)),
+                    };
+                    return Err(CompareError::from_reason(
+                        reason,
+                        CompareErrorType::GeoPositionDiff,
+                    ));
+                }
+            }
+            if members.len() < batch {
+                return Ok(());
+            }
+            start += batch;
         }
-        let s_len =
-            hlen(key.key_name.clone(), self.sconn.as_mut()).map_err(|e| -> CompareError {
-                CompareError::from_str(e.to_string().as_str(), CompareErrorType::RedisConnectionErr)
-            })?;
-        let t_len =
-            hlen(key.key_name.clone(), self.tconn.as_mut()).map_err(|e| -> CompareError {
+    }
+
+    // 遍历 target zset，校验 member 在 source 中是否存在，score 已在正向遍历中校验
+    fn zset_target_members_in_source(&mut self, key: &RedisKey) -> CompareResult<()> {
+        let t_key = self.target_key(&key.key_name);
+        let iter = scan_iter("zscan", &t_key, self.tconn.as_mut())?;
+
+        for (count, item) in iter.enumerate() {
+            if count % 2 == 1 {
+                continue;
+            }
+            let s_score = zscore(key.key_name.clone(), item.clone(), self.sconn.as_mut()).map_err(
+                |e| -> CompareError {
+                    CompareError::from_str(
+                        e.to_string().as_str(),
+                        CompareErrorType::RedisConnectionErr,
+                    )
+                },
+            )?;
+            if s_score.is_none() {
+                let reason = CompareErrorReason {
+                    redis_key: key.clone(),
+                    position: Some(Position::ZsetMember(item.clone())),
+                    source: None,
+                    target: Some(item),
+                };
+                return Err(CompareError::from_reason(
+                    reason,
+                    CompareErrorType::ZSetMemberNotInSource,
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    // 按 batch 分页执行 ZRANGE WITHSCORES，校验两端相同排名的 member 是否一致
+    fn zset_rank_equal(&mut self, key: &RedisKey) -> CompareResult<()> {
+        let t_key = self.target_key(&key.key_name);
+        let s_size =
+            zcard(key.key_name.clone(), self.sconn.as_mut()).map_err(|e| -> CompareError {
+                CompareError::from_str(e.to_string().as_str(), CompareErrorType::RedisConnectionErr)
+            })?;
+
+        // batch 为 0 时按 1 分页，避免 start + batch - 1 下溢
+        let batch = self.batch.max(1);
+        let mut start = 0;
+        while start < s_size {
+            let stop = (start + batch - 1) as isize;
+            let s_members = zrange_withscores(
+                key.key_name.clone(),
+                start as isize,
+                stop,
+                self.sconn.as_mut(),
+            )
+            .map_err(|e| -> CompareError {
+                CompareError::from_str(e.to_string().as_str(), CompareErrorType::RedisConnectionErr)
+            })?;
+            let t_members =
+                zrange_withscores(t_key.clone(), start as isize, stop, self.tconn.as_mut())
+                    .map_err(|e| -> CompareError {
+                        CompareError::from_str(
+                            e.to_string().as_str(),
+                            CompareErrorType::RedisConnectionErr,
+                        )
+                    })?;
+
+            for (i, (s_member, s_score)) in s_members.iter().enumerate() {
+                let equal = match t_members.get(i) {
+                    Some((t_member, t_score)) => {
+                        s_member.eq(t_member) && self.options.score_equal(*s_score, *t_score)
+                    }
+                    None => false,
+                };
+                if !equal {
+                    let reason = CompareErrorReason {
+                        redis_key: key.clone(),
+                        position: Some(Position::ZsetRank(start + i)),
+                        source: Some(s_member.clone()),
+                        target: t_members.get(i).map(|(m, _)| m.clone()),
+                    };
+                    return Err(CompareError::from_reason(
+                        reason,
+                        CompareErrorType::ZSetRankDiff,
+                    ));
+                }
+            }
+            start += batch;
+        }
+        Ok(())
+    }
+
+    fn hash_len_equal(&mut self, key: &RedisKey) -> CompareResult<()> {
+        if !key.key_type.eq(&RedisKeyType::TypeHash) {
+            let reason = CompareErrorReason {
+                redis_key: key.clone(),
+                position: None,
+                source: None,
+                target: None,
+            };
+            return Err(CompareError::from_reason(
+                reason,
+                CompareErrorType::KeyTypeNotHash,
+            ));
+        }
+        let s_len =
+            hlen(key.key_name.clone(), self.sconn.as_mut()).map_err(|e| -> CompareError {
                 CompareError::from_str(e.to_string().as_str(), CompareErrorType::RedisConnectionErr)
             })?;
+        let t_len = hlen(self.target_key(&key.key_name), self.tconn.as_mut()).map_err(
+            |e| -> CompareError {
+                CompareError::from_str(e.to_string().as_str(), CompareErrorType::RedisConnectionErr)
+            },
+        )?;
         if s_len != t_len {
             let reason = CompareErrorReason {
                 redis_key: key.clone(),
@@ -608,6 +1271,7 @@ impl Comparer {
                 CompareErrorType::KeyTypeNotHash,
             ));
         }
+        let t_key = self.target_key(&key.key_name);
         let mut cmd_hscan = redis::cmd("hscan");
         cmd_hscan.arg(key.key_name.clone()).cursor_arg(0);
         let iter: Iter<String> =
@@ -626,13 +1290,14 @@ impl Comparer {
                 field = item;
                 tag = false;
             } else {
-                let t_val = hget(key.key_name.clone(), field.clone(), self.tconn.as_mut())
-                    .map_err(|e| -> CompareError {
+                let t_val = hget(t_key.clone(), field.clone(), self.tconn.as_mut()).map_err(
+                    |e| -> CompareError {
                         CompareError::from_str(
                             e.to_string().as_str(),
                             CompareErrorType::RedisConnectionErr,
                         )
-                    })?;
+                    },
+                )?;
                 if !item.eq(&t_val) {
                     let reason = CompareErrorReason {
                         redis_key: key.clone(),
@@ -650,6 +1315,608 @@ impl Comparer {
         }
         Ok(())
     }
+
+    // 遍历target，核对 field 在 source 中是否存在，value 已在正向遍历中校验
+    fn hash_target_field_in_source(&mut self, key: &RedisKey) -> CompareResult<()> {
+        let t_key = self.target_key(&key.key_name);
+        let mut iter = scan_iter("hscan", &t_key, self.tconn.as_mut())?;
+        while let Some(field) = iter.next() {
+            let t_val = iter.next();
+            let exists: bool = redis::cmd("hexists")
+                .arg(key.key_name.clone())
+                .arg(field.clone())
+                .query(self.sconn.as_mut())
+                .map_err(|e| -> CompareError {
+                    CompareError::from_str(
+                        e.to_string().as_str(),
+                        CompareErrorType::RedisConnectionErr,
+                    )
+                })?;
+            if !exists {
+                let reason = CompareErrorReason {
+                    redis_key: key.clone(),
+                    position: Some(Position::HashField(field)),
+                    source: None,
+                    target: t_val,
+                };
+                return Err(CompareError::from_reason(
+                    reason,
+                    CompareErrorType::HashFieldNotInSource,
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+// full_diff 模式，遍历两端全部元素收集差异
+impl Comparer {
+    // 仅对元素差异生成 KeyDiff，key 不存在、ttl 差异、连接错误时返回 None
+    fn full_diff(&mut self, key: &RedisKey, error: &CompareError) -> Option<KeyDiff> {
+        match error.error_type {
+            CompareErrorType::ExistsErr
+            | CompareErrorType::TTLDiff
+            | CompareErrorType::HyperLogLogCountDiff
+            | CompareErrorType::GeoPositionDiff
+            | CompareErrorType::KeyTypeDiff
+            | CompareErrorType::DumpPayloadDiff
+            | CompareErrorType::ConfigValueDiff
+            | CompareErrorType::RedisConnectionErr => return None,
+            _ => {}
+        }
+        // 大 string 已按分片定位到差异偏移量，不再整体读取
+        if let Some(Position::StringOffset(_)) =
+            error.reason.as_ref().and_then(|r| r.position.as_ref())
+        {
+            return None;
+        }
+        let r = match key.key_type {
+            RedisKeyType::TypeString => self.string_diff(key),
+            RedisKeyType::TypeList => self.list_diff(key),
+            RedisKeyType::TypeSet => self.set_diff(key),
+            RedisKeyType::TypeZSet => self.zset_diff(key),
+            RedisKeyType::TypeHash => self.hash_diff(key),
+            // DUMP 内容无法按元素比较
+            RedisKeyType::TypeOther(_) => return None,
+        };
+        match r {
+            Ok(diff) if !diff.is_empty() => Some(diff),
+            Ok(_) => None,
+            Err(e) => {
+                log::error!("{}", e);
+                None
+            }
+        }
+    }
+
+    fn string_diff(&mut self, key: &RedisKey) -> CompareResult<KeyDiff> {
+        let mut diff = KeyDiff::new(self.options.full_diff_max);
+        let sval: Option<String> = redis::cmd("get")
+            .arg(key.key_name.clone())
+            .query(self.sconn.as_mut())
+            .map_err(|e| -> CompareError {
+                CompareError::from_str(e.to_string().as_str(), CompareErrorType::RedisConnectionErr)
+            })?;
+        let tval: Option<String> = redis::cmd("get")
+            .arg(self.target_key(&key.key_name))
+            .query(self.tconn.as_mut())
+            .map_err(|e| -> CompareError {
+                CompareError::from_str(e.to_string().as_str(), CompareErrorType::RedisConnectionErr)
+            })?;
+        if !sval.eq(&tval) {
+            diff.push(
+                DiffKind::Changed,
+                ElementDiff {
+                    position: None,
+                    source: sval,
+                    target: tval,
+                },
+            );
+        }
+        Ok(diff)
+    }
+
+    // 按 batch 分页比较两端相同下标的元素，连续的差异下标合并为区间
+    fn list_diff(&mut self, key: &RedisKey) -> CompareResult<KeyDiff> {
+        let mut diff = KeyDiff::new(self.options.full_diff_max);
+        let t_key = self.target_key(&key.key_name);
+        let s_len =
+            list_len(key.key_name.clone(), self.sconn.as_mut()).map_err(|e| -> CompareError {
+                CompareError::from_str(e.to_string().as_str(), CompareErrorType::RedisConnectionErr)
+            })?;
+        let t_len = list_len(t_key.clone(), self.tconn.as_mut()).map_err(|e| -> CompareError {
+            CompareError::from_str(e.to_string().as_str(), CompareErrorType::RedisConnectionErr)
+        })?;
+
+        let batch = self.batch.max(1);
+        let mut start = 0;
+        while start < s_len.max(t_len) && !diff.is_full() {
+            let stop = (start + batch - 1) as isize;
+            let s_elements = lrange(
+                key.key_name.clone(),
+                start as isize,
+                stop,
+                self.sconn.as_mut(),
+            )
+            .map_err(|e| -> CompareError {
+                CompareError::from_str(e.to_string().as_str(), CompareErrorType::RedisConnectionErr)
+            })?;
+            let t_elements = lrange(t_key.clone(), start as isize, stop, self.tconn.as_mut())
+                .map_err(|e| -> CompareError {
+                    CompareError::from_str(
+                        e.to_string().as_str(),
+                        CompareErrorType::RedisConnectionErr,
+                    )
+                })?;
+
+            for i in 0..s_elements.len().max(t_elements.len()) {
+                let s_val = s_elements.get(i).cloned();
+                let t_val = t_elements.get(i).cloned();
+                let kind = match (&s_val, &t_val) {
+                    (Some(s), Some(t)) if s.eq(t) => continue,
+                    (Some(_), Some(_)) => DiffKind::Changed,
+                    (Some(_), None) => DiffKind::Removed,
+                    _ => DiffKind::Added,
+                };
+                if !diff.push_list_index(kind, start + i, s_val, t_val) {
+                    break;
+                }
+            }
+            start += batch;
+        }
+        Ok(diff)
+    }
+
+    fn set_diff(&mut self, key: &RedisKey) -> CompareResult<KeyDiff> {
+        let mut diff = KeyDiff::new(self.options.full_diff_max);
+        let t_key = self.target_key(&key.key_name);
+
+        // source 中存在，target 中不存在
+        let s_members = scan_iter("sscan", &key.key_name, self.sconn.as_mut())?;
+        for member in s_members {
+            let is = sismumber(t_key.clone(), member.clone(), self.tconn.as_mut()).map_err(
+                |e| -> CompareError {
+                    CompareError::from_str(
+                        e.to_string().as_str(),
+                        CompareErrorType::RedisConnectionErr,
+                    )
+                },
+            )?;
+            if !is {
+                let element = ElementDiff {
+                    position: Some(Position::SetMember(member.clone())),
+                    source: Some(member),
+                    target: None,
+                };
+                if !diff.push(DiffKind::Removed, element) {
+                    return Ok(diff);
+                }
+            }
+        }
+
+        // target 中存在，source 中不存在
+        let t_members = scan_iter("sscan", &t_key, self.tconn.as_mut())?;
+        for member in t_members {
+            let is = sismumber(key.key_name.clone(), member.clone(), self.sconn.as_mut()).map_err(
+                |e| -> CompareError {
+                    CompareError::from_str(
+                        e.to_string().as_str(),
+                        CompareErrorType::RedisConnectionErr,
+                    )
+                },
+            )?;
+            if !is {
+                let element = ElementDiff {
+                    position: Some(Position::SetMember(member.clone())),
+                    source: None,
+                    target: Some(member),
+                };
+                if !diff.push(DiffKind::Added, element) {
+                    return Ok(diff);
+                }
+            }
+        }
+        Ok(diff)
+    }
+
+    fn zset_diff(&mut self, key: &RedisKey) -> CompareResult<KeyDiff> {
+        let mut diff = KeyDiff::new(self.options.full_diff_max);
+        let t_key = self.target_key(&key.key_name);
+
+        let mut s_items = scan_iter("zscan", &key.key_name, self.sconn.as_mut())?;
+        while let Some(member) = s_items.next() {
+            let s_score = s_items.next();
+            let t_score = zscore(t_key.clone(), member.clone(), self.tconn.as_mut()).map_err(
+                |e| -> CompareError {
+                    CompareError::from_str(
+                        e.to_string().as_str(),
+                        CompareErrorType::RedisConnectionErr,
+                    )
+                },
+            )?;
+            let kind = match t_score {
+                None => DiffKind::Removed,
+                Some(t) => {
+                    let s = s_score
+                        .as_ref()
+                        .and_then(|s| s.parse::<f64>().ok())
+                        .unwrap_or(f64::NAN);
+                    if self.options.score_equal(s, t) {
+                        continue;
+                    }
+                    DiffKind::Changed
+                }
+            };
+            let element = ElementDiff {
+                position: Some(Position::ZsetMember(member)),
+                source: s_score,
+                target: t_score.map(|t| t.to_string()),
+            };
+            if !diff.push(kind, element) {
+                return Ok(diff);
+            }
+        }
+
+        let mut t_items = scan_iter("zscan", &t_key, self.tconn.as_mut())?;
+        while let Some(member) = t_items.next() {
+            let t_score = t_items.next();
+            let s_score = zscore(key.key_name.clone(), member.clone(), self.sconn.as_mut())
+                .map_err(|e| -> CompareError {
+                    CompareError::from_str(
+                        e.to_string().as_str(),
+                        CompareErrorType::RedisConnectionErr,
+                    )
+                })?;
+            if s_score.is_none() {
+                let element = ElementDiff {
+                    position: Some(Position::ZsetMember(member)),
+                    source: None,
+                    target: t_score,
+                };
+                if !diff.push(DiffKind::Added, element) {
+                    return Ok(diff);
+                }
+            }
+        }
+        Ok(diff)
+    }
+
+    fn hash_diff(&mut self, key: &RedisKey) -> CompareResult<KeyDiff> {
+        let mut diff = KeyDiff::new(self.options.full_diff_max);
+        let t_key = self.target_key(&key.key_name);
+
+        let mut s_items = scan_iter("hscan", &key.key_name, self.sconn.as_mut())?;
+        while let Some(field) = s_items.next() {
+            let s_val = s_items.next();
+            let t_val: Option<String> = redis::cmd("hget")
+                .arg(t_key.clone())
+                .arg(field.clone())
+                .query(self.tconn.as_mut())
+                .map_err(|e| -> CompareError {
+                    CompareError::from_str(
+                        e.to_string().as_str(),
+                        CompareErrorType::RedisConnectionErr,
+                    )
+                })?;
+            let kind = match &t_val {
+                None => DiffKind::Removed,
+                Some(t) if Some(t).eq(&s_val.as_ref()) => continue,
+                Some(_) => DiffKind::Changed,
+            };
+            let element = ElementDiff {
+                position: Some(Position::HashField(field)),
+                source: s_val,
+                target: t_val,
+            };
+            if !diff.push(kind, element) {
+                return Ok(diff);
+            }
+        }
+
+        let mut t_items = scan_iter("hscan", &t_key, self.tconn.as_mut())?;
+        while let Some(field) = t_items.next() {
+            let t_val = t_items.next();
+            let exists: bool = redis::cmd("hexists")
+                .arg(key.key_name.clone())
+                .arg(field.clone())
+                .query(self.sconn.as_mut())
+                .map_err(|e| -> CompareError {
+                    CompareError::from_str(
+                        e.to_string().as_str(),
+                        CompareErrorType::RedisConnectionErr,
+                    )
+                })?;
+            if !exists {
+                let element = ElementDiff {
+                    position: Some(Position::HashField(field)),
+                    source: None,
+                    target: t_val,
+                };
+                if !diff.push(DiffKind::Added, element) {
+                    return Ok(diff);
+                }
+            }
+        }
+        Ok(diff)
+    }
+}
+
+// 大 key 抽样校验，比较元素数量与部分元素，不遍历整个 key
+impl Comparer {
+    pub fn compare_key_sample(&mut self, key: RedisKey, sample: usize) -> CompareResult<()> {
+        // target端key是否存在
+        self.target_key_exists(&key)?;
+
+        let sample = sample.max(1);
+        match key.key_type {
+            RedisKeyType::TypeString => self.string_sample_equal(&key)?,
+            RedisKeyType::TypeList => {
+                let (s_len, _) = self.list_len_equal(&key)?;
+                self.list_sample_equal(&key, s_len, sample)?;
+            }
+            RedisKeyType::TypeSet => {
+                self.set_members_number_equal(&key)?;
+                self.set_sample_in_target(&key, sample)?;
+            }
+            RedisKeyType::TypeZSet => {
+                self.zset_members_number_equal(&key)?;
+                self.zset_sample_in_target(&key, sample)?;
+            }
+            RedisKeyType::TypeHash => {
+                self.hash_len_equal(&key)?;
+                self.hash_sample_equal(&key, sample)?;
+            }
+            // 无法抽样，完整比较 DUMP 内容
+            RedisKeyType::TypeOther(_) => return self.compare_dump(key),
+        }
+
+        // ttl差值是否在规定范围内
+        self.ttl_diff(&key)?;
+        Ok(())
+    }
+
+    // 比较 string 长度及首个分片
+    fn string_sample_equal(&mut self, key: &RedisKey) -> CompareResult<()> {
+        let t_key = self.target_key(&key.key_name);
+        let s_len: usize = redis::cmd("strlen")
+            .arg(key.key_name.clone())
+            .query(self.sconn.as_mut())
+            .map_err(redis_err)?;
+        let t_len: usize = redis::cmd("strlen")
+            .arg(t_key.clone())
+            .query(self.tconn.as_mut())
+            .map_err(redis_err)?;
+        if s_len != t_len {
+            let reason = CompareErrorReason {
+                redis_key: key.clone(),
+                position: None,
+                source: Some(s_len.to_string()),
+                target: Some(t_len.to_string()),
+            };
+            return Err(CompareError::from_reason(
+                reason,
+                CompareErrorType::StringValueNotEqual,
+            ));
+        }
+
+        let end = self.options.string_chunk_size.max(1) - 1;
+        let s_bytes = getrange(&key.key_name, 0, end, self.sconn.as_mut())?;
+        let t_bytes = getrange(&t_key, 0, end, self.tconn.as_mut())?;
+        if let Some(offset) = first_diff_offset(&s_bytes, &t_bytes) {
+            let reason = CompareErrorReason {
+                redis_key: key.clone(),
+                position: Some(Position::StringOffset(offset)),
+                source: Some(s_len.to_string()),
+                target: Some(t_len.to_string()),
+            };
+            return Err(CompareError::from_reason(
+                reason,
+                CompareErrorType::StringValueNotEqual,
+            ));
+        }
+        Ok(())
+    }
+
+    // 按固定间隔抽取 sample 个下标，比较两端元素
+    fn list_sample_equal(
+        &mut self,
+        key: &RedisKey,
+        len: usize,
+        sample: usize,
+    ) -> CompareResult<()> {
+        let t_key = self.target_key(&key.key_name);
+        let step = (len / sample).max(1);
+        for index in (0..len).step_by(step).take(sample) {
+            let s_val: Option<String> = redis::cmd("lindex")
+                .arg(key.key_name.clone())
+                .arg(index)
+                .query(self.sconn.as_mut())
+                .map_err(redis_err)?;
+            let t_val: Option<String> = redis::cmd("lindex")
+                .arg(t_key.clone())
+                .arg(index)
+                .query(self.tconn.as_mut())
+                .map_err(redis_err)?;
+            if s_val != t_val {
+                let reason = CompareErrorReason {
+                    redis_key: key.clone(),
+                    position: Some(Position::ListIndex(index)),
+                    source: s_val,
+                    target: t_val,
+                };
+                return Err(CompareError::from_reason(
+                    reason,
+                    CompareErrorType::ListIndexValueDiff,
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    // 取 source SSCAN 首页的 sample 个 member，核对在 target 中是否存在
+    fn set_sample_in_target(&mut self, key: &RedisKey, sample: usize) -> CompareResult<()> {
+        let t_key = self.target_key(&key.key_name);
+        let members = scan_page("sscan", &key.key_name, sample, self.sconn.as_mut())?;
+        for member in members.into_iter().take(sample) {
+            let is =
+                sismumber(t_key.clone(), member.clone(), self.tconn.as_mut()).map_err(redis_err)?;
+            if !is {
+                let reason = CompareErrorReason {
+                    redis_key: key.clone(),
+                    position: Some(Position::SetMember(member.clone())),
+                    source: Some(member),
+                    target: None,
+                };
+                return Err(CompareError::from_reason(
+                    reason,
+                    CompareErrorType::SetMemberNotIn,
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    // 取 source ZSCAN 首页的 sample 个 member，核对 target 中的 score
+    fn zset_sample_in_target(&mut self, key: &RedisKey, sample: usize) -> CompareResult<()> {
+        let t_key = self.target_key(&key.key_name);
+        let items = scan_page("zscan", &key.key_name, sample, self.sconn.as_mut())?;
+        for pair in items.chunks(2).take(sample) {
+            let (member, score) = match pair {
+                [m, s] => (m, s),
+                _ => continue,
+            };
+            let s_score = score.parse::<f64>().map_err(|e| -> CompareError {
+                CompareError::from_str(e.to_string().as_str(), CompareErrorType::Unknown)
+            })?;
+            let t_score =
+                zscore(t_key.clone(), member.clone(), self.tconn.as_mut()).map_err(redis_err)?;
+            let equal = match t_score {
+                Some(t) => self.options.score_equal(s_score, t),
+                None => false,
+            };
+            if !equal {
+                let reason = CompareErrorReason {
+                    redis_key: key.clone(),
+                    position: Some(Position::ZsetMember(member.clone())),
+                    source: Some(score.clone()),
+                    target: t_score.map(|t| t.to_string()),
+                };
+                return Err(CompareError::from_reason(
+                    reason,
+                    CompareErrorType::ZSetMemberScoreDiff,
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    // 取 source HSCAN 首页的 sample 个 field，核对 target 中的 value
+    fn hash_sample_equal(&mut self, key: &RedisKey, sample: usize) -> CompareResult<()> {
+        let t_key = self.target_key(&key.key_name);
+        let items = scan_page("hscan", &key.key_name, sample, self.sconn.as_mut())?;
+        for pair in items.chunks(2).take(sample) {
+            let (field, value) = match pair {
+                [f, v] => (f, v),
+                _ => continue,
+            };
+            let t_val =
+                hget(t_key.clone(), field.clone(), self.tconn.as_mut()).map_err(redis_err)?;
+            if !value.eq(&t_val) {
+                let reason = CompareErrorReason {
+                    redis_key: key.clone(),
+                    position: Some(Position::HashField(field.clone())),
+                    source: Some(value.clone()),
+                    target: Some(t_val),
+                };
+                return Err(CompareError::from_reason(
+                    reason,
+                    CompareErrorType::HashFieldValueDiff,
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn redis_err(e: redis::RedisError) -> CompareError {
+    CompareError::from_str(e.to_string().as_str(), CompareErrorType::RedisConnectionErr)
+}
+
+// DUMP 格式：数据、RDB 版本(2)、CRC64(8)，返回去掉尾部的数据
+fn dump_body(dump: &[u8]) -> &[u8] {
+    &dump[..dump.len().saturating_sub(DUMP_TRAILER_LEN)]
+}
+
+// 执行一次 sscan、zscan、hscan，返回首页元素
+fn scan_page(
+    cmd: &str,
+    key: &str,
+    count: usize,
+    conn: &mut dyn ConnectionLike,
+) -> CompareResult<Vec<String>> {
+    let (_, items): (String, Vec<String>) = redis::cmd(cmd)
+        .arg(key)
+        .arg(0)
+        .arg("count")
+        .arg(count)
+        .query(conn)
+        .map_err(redis_err)?;
+    Ok(items)
+}
+
+fn getrange(
+    key: &str,
+    start: usize,
+    end: usize,
+    conn: &mut dyn ConnectionLike,
+) -> CompareResult<Vec<u8>> {
+    redis::cmd("getrange")
+        .arg(key)
+        .arg(start)
+        .arg(end)
+        .query(conn)
+        .map_err(|e| -> CompareError {
+            CompareError::from_str(e.to_string().as_str(), CompareErrorType::RedisConnectionErr)
+        })
+}
+
+// 服务端计算 GETRANGE 结果的 SHA1
+fn getrange_sha1(
+    key: &str,
+    start: usize,
+    end: usize,
+    conn: &mut dyn ConnectionLike,
+) -> CompareResult<String> {
+    redis::Script::new("return redis.sha1hex(redis.call('getrange', KEYS[1], ARGV[1], ARGV[2]))")
+        .key(key)
+        .arg(start)
+        .arg(end)
+        .invoke(conn)
+        .map_err(|e| -> CompareError {
+            CompareError::from_str(e.to_string().as_str(), CompareErrorType::RedisConnectionErr)
+        })
+}
+
+// 第一个不同字节的偏移量，长度不同时较短一端结束处即为差异
+fn first_diff_offset(source: &[u8], target: &[u8]) -> Option<usize> {
+    match source.iter().zip(target).position(|(s, t)| s != t) {
+        Some(i) => Some(i),
+        None if source.len() != target.len() => Some(source.len().min(target.len())),
+        None => None,
+    }
+}
+
+// 执行 sscan、zscan、hscan 遍历集合元素，zscan、hscan 依次返回 member、score 或 field、value
+fn scan_iter<'a>(
+    cmd: &str,
+    key: &str,
+    conn: &'a mut dyn ConnectionLike,
+) -> CompareResult<Iter<'a, String>> {
+    let mut cmd_scan = redis::cmd(cmd);
+    cmd_scan.arg(key).cursor_arg(0);
+    cmd_scan.iter(conn).map_err(|e| -> CompareError {
+        CompareError::from_str(e.to_string().as_str(), CompareErrorType::RedisConnectionErr)
+    })
 }
 
 #[cfg(test)]
@@ -677,6 +1944,8 @@ mod test {
             tconn: t,
             ttl_diff: 1,
             batch: 10,
+            key_mapper: KeyMapper::default(),
+            options: CompareOptions::default(),
         };
 
         let _cmd_set = redis::cmd("set");
@@ -713,6 +1982,8 @@ mod test {
             tconn: t,
             ttl_diff: 1,
             batch: 10,
+            key_mapper: KeyMapper::default(),
+            options: CompareOptions::default(),
         };
         let cmd_rpush = redis::cmd("rpush");
 
@@ -749,6 +2020,8 @@ mod test {
             tconn: t,
             ttl_diff: 1,
             batch: 10,
+            key_mapper: KeyMapper::default(),
+            options: CompareOptions::default(),
         };
 
         let cmd_sadd = redis::cmd("sadd");
@@ -786,6 +2059,8 @@ mod test {
             tconn: t,
             ttl_diff: 1,
             batch: 10,
+            key_mapper: KeyMapper::default(),
+            options: CompareOptions::default(),
         };
 
         let cmd_zadd = redis::cmd("zadd");
@@ -831,6 +2106,8 @@ mod test {
             tconn: t,
             ttl_diff: 1,
             batch: 10,
+            key_mapper: KeyMapper::default(),
+            options: CompareOptions::default(),
         };
 
         let cmd_hset = redis::cmd("hset");
@@ -875,6 +2152,8 @@ mod test {
             tconn: t,
             ttl_diff: 1,
             batch: 10,
+            key_mapper: KeyMapper::default(),
+            options: CompareOptions::default(),
         };
         let cmd = redis::cmd("ping");
         let r = comparer.sconn.as_mut().req_command(&cmd);
@@ -922,4 +2201,95 @@ mod test {
         let mut cmd_set = redis::cmd("set");
         cmd_set.arg("a").arg("aa").execute(&mut conn);
     }
+
+    //cargo test compare::comparekey::test::test_first_diff_offset --  --nocapture
+    #[test]
+    fn test_first_diff_offset() {
+        assert_eq!(first_diff_offset(b"abcdef", b"abcdef"), None);
+        assert_eq!(first_diff_offset(b"abcdef", b"abXdef"), Some(2));
+        assert_eq!(first_diff_offset(b"abc", b"abcdef"), Some(3));
+        assert_eq!(first_diff_offset(b"", b""), None);
+    }
+
+    //cargo test compare::comparekey::test::test_dump_body --  --nocapture
+    #[test]
+    fn test_dump_body() {
+        // 相同数据，RDB 版本与 CRC 不同
+        let v9 = b"\x00\x03abc\x09\x00\x01\x02\x03\x04\x05\x06\x07\x08";
+        let v11 = b"\x00\x03abc\x0b\x00\x11\x12\x13\x14\x15\x16\x17\x18";
+        assert_eq!(dump_body(v9), b"\x00\x03abc");
+        assert_eq!(dump_body(v9), dump_body(v11));
+        assert!(dump_body(b"short").is_empty());
+
+        let key_type: RedisKeyType =
+            redis::from_redis_value(&redis::Value::Status("ReJSON-RL".to_string())).unwrap();
+        assert_eq!(key_type, RedisKeyType::TypeOther("ReJSON-RL".to_string()));
+        assert_eq!(key_type.to_string(), "ReJSON-RL");
+        assert!(
+            redis::from_redis_value::<RedisKeyType>(&redis::Value::Status("none".to_string()))
+                .is_err()
+        );
+    }
+
+    //cargo test compare::comparekey::test::test_zset_score_equal --  --nocapture
+    #[test]
+    fn test_zset_score_equal() {
+        let yml = r#"
+batch_size: 10
+zset_score_abs_epsilon: 0.001
+zset_rank_check: true
+"#;
+        let compare: crate::compare::Compare = serde_yaml::from_str(yml).unwrap();
+        let options = compare.options;
+        assert!(options.zset_rank_check);
+        assert!(options.score_equal(1.0, 1.0005));
+        assert!(!options.score_equal(1.0, 1.01));
+        assert!(options.score_equal(f64::INFINITY, f64::INFINITY));
+
+        let options = CompareOptions {
+            zset_score_rel_epsilon: 1e-9,
+            ..CompareOptions::default()
+        };
+        assert!(options.score_equal(1e12, 1e12 + 1.0));
+        assert!(!options.score_equal(1.0, 1.0 + 1e-6));
+        assert!(!CompareOptions::default().score_equal(0.1 + 0.2, 0.3));
+    }
+
+    //cargo test compare::comparekey::test::test_geo_key_patterns --  --nocapture
+    #[test]
+    fn test_geo_key_patterns() {
+        let yml = r#"
+batch_size: 10
+geo_key_patterns: ["^geo:", "places$"]
+"#;
+        let compare: crate::compare::Compare = serde_yaml::from_str(yml).unwrap();
+        let options = compare.options;
+        assert!(options.geo_key_matched("geo:sicily"));
+        assert!(options.geo_key_matched("city_places"));
+        assert!(!options.geo_key_matched("zset:geo:sicily"));
+        assert!(!CompareOptions::default().geo_key_matched("geo:sicily"));
+
+        let yml = r#"
+batch_size: 10
+geo_key_patterns: ["geo:("]
+"#;
+        let r = serde_yaml::from_str::<crate::compare::Compare>(yml);
+        assert!(r.unwrap_err().to_string().contains("geo_key_patterns"));
+    }
+
+    //cargo test compare::comparekey::test::test_slot_selected --  --nocapture
+    #[test]
+    fn test_slot_selected() {
+        assert!(CompareOptions::default().slot_selected("foo"));
+
+        // foo 属于 slot 12182，bar 属于 slot 5061
+        let options = CompareOptions {
+            only_slots: vec![0, 12182, 16383, 20000],
+            ..CompareOptions::default()
+        };
+        assert!(options.slot_selected("foo"));
+        assert!(options.slot_selected("{foo}bar"));
+        assert!(!options.slot_selected("bar"));
+        assert!(options.clone().slot_selected("foo"));
+    }
 }
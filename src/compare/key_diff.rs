@@ -0,0 +1,131 @@
+use serde::{Deserialize, Serialize};
+
+use super::Position;
+
+// 单个元素的差异，list 连续差异合并为 ListRange，此时仅单个元素的区间记录值
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ElementDiff {
+    pub position: Option<Position>,
+    pub source: Option<String>,
+    pub target: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum DiffKind {
+    // 仅存在于 target
+    Added,
+    // 仅存在于 source
+    Removed,
+    // 两端均存在但值不同
+    Changed,
+}
+
+// full_diff 模式下单个 key 的完整差异
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct KeyDiff {
+    pub added: Vec<ElementDiff>,
+    pub removed: Vec<ElementDiff>,
+    pub changed: Vec<ElementDiff>,
+    // 差异数量达到上限后不再记录
+    pub truncated: bool,
+    #[serde(skip)]
+    max: usize,
+}
+
+impl KeyDiff {
+    pub fn new(max: usize) -> Self {
+        Self {
+            max,
+            ..Default::default()
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.added.len() + self.removed.len() + self.changed.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // 记录一个元素差异，达到上限时返回 false
+    pub fn push(&mut self, kind: DiffKind, element: ElementDiff) -> bool {
+        if self.is_full() {
+            return false;
+        }
+        self.elements_mut(kind).push(element);
+        true
+    }
+
+    // 记录 list 下标差异，与同类型的上一个区间相邻时合并
+    pub fn push_list_index(
+        &mut self,
+        kind: DiffKind,
+        index: usize,
+        source: Option<String>,
+        target: Option<String>,
+    ) -> bool {
+        if let Some(last) = self.elements_mut(kind).last_mut() {
+            if let Some(Position::ListRange(start, end)) = last.position {
+                if end + 1 == index {
+                    last.position = Some(Position::ListRange(start, index));
+                    last.source = None;
+                    last.target = None;
+                    return true;
+                }
+            }
+        }
+        self.push(
+            kind,
+            ElementDiff {
+                position: Some(Position::ListRange(index, index)),
+                source,
+                target,
+            },
+        )
+    }
+
+    pub fn is_full(&mut self) -> bool {
+        if self.max > 0 && self.len() >= self.max {
+            self.truncated = true;
+        }
+        self.truncated
+    }
+
+    fn elements_mut(&mut self, kind: DiffKind) -> &mut Vec<ElementDiff> {
+        match kind {
+            DiffKind::Added => &mut self.added,
+            DiffKind::Removed => &mut self.removed,
+            DiffKind::Changed => &mut self.changed,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    //cargo test compare::key_diff::test::test_key_diff --  --nocapture
+    #[test]
+    fn test_key_diff() {
+        let mut diff = KeyDiff::new(3);
+        for i in 2..6 {
+            diff.push_list_index(DiffKind::Changed, i, Some("a".to_string()), None);
+        }
+        diff.push_list_index(DiffKind::Removed, 9, Some("b".to_string()), None);
+        assert_eq!(diff.changed.len(), 1);
+        assert_eq!(diff.changed[0].position, Some(Position::ListRange(2, 5)));
+        assert_eq!(diff.changed[0].source, None);
+        assert_eq!(diff.removed[0].source, Some("b".to_string()));
+
+        let member = |m: &str| ElementDiff {
+            position: Some(Position::ZsetMember(m.to_string())),
+            source: None,
+            target: Some("1".to_string()),
+        };
+        assert!(diff.push(DiffKind::Added, member("m1")));
+        assert!(!diff.push(DiffKind::Added, member("m2")));
+        assert!(diff.truncated);
+        assert_eq!(diff.len(), 3);
+    }
+}
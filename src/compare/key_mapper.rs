@@ -0,0 +1,182 @@
+use anyhow::{anyhow, Result};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+// source key 到 target key 的改名规则，按配置顺序依次作用
+// 反向校验时按相反顺序执行逆变换
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyMapRule {
+    // target key = prefix + source key
+    AddPrefix(String),
+    // source key 以 prefix 开头时，target key 去掉该前缀
+    StripPrefix(String),
+    // 正则替换，replacement 支持 $1、${name} 引用分组
+    // reverse_pattern、reverse_replacement 用于反向校验，未配置时无法执行反向校验
+    Replace {
+        pattern: String,
+        replacement: String,
+        #[serde(default)]
+        reverse_pattern: String,
+        #[serde(default)]
+        reverse_replacement: String,
+    },
+}
+
+impl KeyMapRule {
+    pub fn reversible(&self) -> bool {
+        match self {
+            KeyMapRule::Replace {
+                reverse_pattern, ..
+            } => !reverse_pattern.is_empty(),
+            _ => true,
+        }
+    }
+}
+
+enum CompiledRule {
+    AddPrefix(String),
+    StripPrefix(String),
+    Replace {
+        pattern: Regex,
+        replacement: String,
+        reverse: Option<(Regex, String)>,
+    },
+}
+
+// 编译后的改名规则，用于 Comparer 获取 target key 以及反向校验获取 source key
+#[derive(Default)]
+pub struct KeyMapper {
+    rules: Vec<CompiledRule>,
+}
+
+impl KeyMapper {
+    pub fn new(rules: &[KeyMapRule]) -> Result<Self> {
+        let mut compiled = vec![];
+        for rule in rules {
+            let c = match rule {
+                KeyMapRule::AddPrefix(p) => CompiledRule::AddPrefix(p.clone()),
+                KeyMapRule::StripPrefix(p) => CompiledRule::StripPrefix(p.clone()),
+                KeyMapRule::Replace {
+                    pattern,
+                    replacement,
+                    reverse_pattern,
+                    reverse_replacement,
+                } => {
+                    let reverse = match reverse_pattern.is_empty() {
+                        true => None,
+                        false => Some((
+                            Regex::new(reverse_pattern)
+                                .map_err(|e| anyhow!("{}: {}", reverse_pattern, e))?,
+                            reverse_replacement.clone(),
+                        )),
+                    };
+                    CompiledRule::Replace {
+                        pattern: Regex::new(pattern).map_err(|e| anyhow!("{}: {}", pattern, e))?,
+                        replacement: replacement.clone(),
+                        reverse,
+                    }
+                }
+            };
+            compiled.push(c);
+        }
+        Ok(Self { rules: compiled })
+    }
+
+    // source key 在 target 中的名称
+    pub fn to_target(&self, key: &str) -> String {
+        let mut k = key.to_string();
+        for rule in &self.rules {
+            k = match rule {
+                CompiledRule::AddPrefix(p) => p.clone() + &k,
+                CompiledRule::StripPrefix(p) => match k.strip_prefix(p.as_str()) {
+                    Some(s) => s.to_string(),
+                    None => k,
+                },
+                CompiledRule::Replace {
+                    pattern,
+                    replacement,
+                    ..
+                } => pattern.replace(&k, replacement.as_str()).to_string(),
+            };
+        }
+        k
+    }
+
+    // target key 在 source 中可能的名称，key 不可能由该 source 生成时为空
+    // StripPrefix 的 source key 可能带或不带前缀，逆变换得到的候选均需正向映射回 key
+    pub fn to_source(&self, key: &str) -> Vec<String> {
+        let mut candidates = vec![key.to_string()];
+        for rule in self.rules.iter().rev() {
+            let mut next = vec![];
+            for k in candidates {
+                match rule {
+                    CompiledRule::AddPrefix(p) => {
+                        if let Some(s) = k.strip_prefix(p.as_str()) {
+                            next.push(s.to_string());
+                        }
+                    }
+                    CompiledRule::StripPrefix(p) => {
+                        next.push(p.clone() + &k);
+                        next.push(k);
+                    }
+                    CompiledRule::Replace { reverse, .. } => match reverse {
+                        Some((pattern, replacement)) => {
+                            next.push(pattern.replace(&k, replacement.as_str()).to_string())
+                        }
+                        // 未配置逆变换无法还原 source key
+                        None => return vec![],
+                    },
+                }
+            }
+            candidates = next;
+        }
+        candidates.sort();
+        candidates.dedup();
+        candidates.retain(|c| self.to_target(c) == key);
+        candidates
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    //cargo test compare::key_mapper::test::test_key_mapper --  --nocapture
+    #[test]
+    fn test_key_mapper() {
+        let rules = vec![
+            KeyMapRule::StripPrefix("old:".to_string()),
+            KeyMapRule::Replace {
+                pattern: "^user:(.*)$".to_string(),
+                replacement: "tenantA:user:$1".to_string(),
+                reverse_pattern: "^tenantA:user:(.*)$".to_string(),
+                reverse_replacement: "user:$1".to_string(),
+            },
+            KeyMapRule::AddPrefix("{db3}".to_string()),
+        ];
+        let mapper = KeyMapper::new(&rules).unwrap();
+
+        assert_eq!(mapper.to_target("user:1"), "{db3}tenantA:user:1");
+        assert_eq!(mapper.to_target("order:1"), "{db3}order:1");
+        assert_eq!(
+            mapper.to_source("{db3}tenantA:user:1"),
+            vec!["old:user:1".to_string(), "user:1".to_string()]
+        );
+        assert!(mapper.to_source("tenantA:user:1").is_empty());
+        assert_eq!(KeyMapper::new(&[]).unwrap().to_source("k"), vec!["k"]);
+
+        // 带前缀的 target key 只能来自双重前缀的 source key
+        let strip = KeyMapper::new(&[KeyMapRule::StripPrefix("old:".to_string())]).unwrap();
+        assert_eq!(strip.to_source("old:k"), vec!["old:old:k"]);
+
+        let irreversible = KeyMapper::new(&[KeyMapRule::Replace {
+            pattern: "^a:(.*)$".to_string(),
+            replacement: "b:$1".to_string(),
+            reverse_pattern: "".to_string(),
+            reverse_replacement: "".to_string(),
+        }])
+        .unwrap();
+        assert!(irreversible.to_source("b:1").is_empty());
+    }
+}
@@ -1,10 +1,34 @@
+mod big_key;
+mod compare_aof;
+mod compare_config;
 mod compare_db;
 mod compare_error;
 mod compare_from_file;
+mod compare_rdb;
+mod compare_slots;
+mod compare_snapshot;
+mod compare_summary;
 mod comparekey;
+mod key_diff;
+mod key_mapper;
+mod progress;
 mod rediscompare;
+mod repair_script;
+mod result_store;
+mod result_store_sqlite;
+mod run_diff;
+mod run_registry;
 
 pub use compare_db::{CompareDB, CompareDBReverse, FailKeys};
 pub use compare_error::{CompareError, Position};
 pub use compare_from_file::compare_from_file;
-pub use rediscompare::{Compare, InstanceType, RedisInstance, ScenarioType, SourceInstance};
+pub use comparekey::CompareOptions;
+pub use key_diff::{DiffKind, ElementDiff, KeyDiff};
+pub use key_mapper::{KeyMapRule, KeyMapper};
+pub use rediscompare::{
+    Compare, DBMapTarget, InstanceType, RedisInstance, ScenarioType, SourceInstance,
+};
+pub use repair_script::{export_repair_script, ScriptFormat};
+pub use result_store_sqlite::{delete_run, list_runs, load_run};
+pub use run_diff::{diff_runs, summarize_run_diff};
+pub use run_registry::{delete_run_dir, list_run_dirs, load_run_dir, DEFAULT_RESULTS_ROOT};
@@ -0,0 +1,307 @@
+use std::io::{stderr, IsTerminal, Write};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{channel, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+// TTY 下进度条的刷新间隔
+const TTY_REFRESH: Duration = Duration::from_millis(500);
+const BAR_WIDTH: usize = 30;
+
+// 单个 DB 对的校验进度，由校验线程累加，进度线程读取
+pub struct PairProgress {
+    label: String,
+    started: Instant,
+    // 扫描端的 DBSIZE，0 表示未知
+    total: AtomicU64,
+    scanned: AtomicU64,
+    // 不属于 only_slots 而跳过的 key
+    skipped: AtomicU64,
+    compared: AtomicU64,
+    mismatched: AtomicU64,
+    finished: AtomicBool,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct ProgressSnapshot {
+    total: u64,
+    scanned: u64,
+    skipped: u64,
+    compared: u64,
+    mismatched: u64,
+    finished: bool,
+}
+
+impl ProgressSnapshot {
+    // 已处理的 key，跳过的 key 视为已处理
+    fn done(&self) -> u64 {
+        self.compared + self.skipped
+    }
+}
+
+impl PairProgress {
+    pub fn new(label: String) -> Self {
+        Self {
+            label,
+            started: Instant::now(),
+            total: AtomicU64::new(0),
+            scanned: AtomicU64::new(0),
+            skipped: AtomicU64::new(0),
+            compared: AtomicU64::new(0),
+            mismatched: AtomicU64::new(0),
+            finished: AtomicBool::new(false),
+        }
+    }
+
+    pub fn set_total(&self, n: u64) {
+        self.total.store(n, Ordering::Relaxed);
+    }
+
+    pub fn add_scanned(&self, n: u64) {
+        self.scanned.fetch_add(n, Ordering::Relaxed);
+    }
+
+    pub fn add_skipped(&self, n: u64) {
+        self.skipped.fetch_add(n, Ordering::Relaxed);
+    }
+
+    pub fn add_compared(&self, n: u64) {
+        self.compared.fetch_add(n, Ordering::Relaxed);
+    }
+
+    pub fn add_mismatched(&self, n: u64) {
+        self.mismatched.fetch_add(n, Ordering::Relaxed);
+    }
+
+    pub fn finish(&self) {
+        self.finished.store(true, Ordering::Relaxed);
+    }
+
+    fn snapshot(&self) -> ProgressSnapshot {
+        ProgressSnapshot {
+            total: self.total.load(Ordering::Relaxed),
+            scanned: self.scanned.load(Ordering::Relaxed),
+            skipped: self.skipped.load(Ordering::Relaxed),
+            compared: self.compared.load(Ordering::Relaxed),
+            mismatched: self.mismatched.load(Ordering::Relaxed),
+            finished: self.finished.load(Ordering::Relaxed),
+        }
+    }
+}
+
+fn format_duration(secs: u64) -> String {
+    let (h, m, s) = (secs / 3600, secs % 3600 / 60, secs % 60);
+    if h > 0 {
+        return format!("{}h{:02}m{:02}s", h, m, s);
+    }
+    if m > 0 {
+        return format!("{}m{:02}s", m, s);
+    }
+    format!("{}s", s)
+}
+
+// 按当前吞吐估算剩余时间，DBSIZE 未知或吞吐为 0 时无法估算
+fn eta_secs(s: &ProgressSnapshot, rate: f64) -> Option<u64> {
+    if s.finished || s.total == 0 || rate <= 0.0 {
+        return None;
+    }
+    Some((s.total.saturating_sub(s.done()) as f64 / rate).ceil() as u64)
+}
+
+// bar 为 true 时在行首输出进度条，DBSIZE 在校验过程中可能变化，进度不超过 100%
+fn render_line(label: &str, s: &ProgressSnapshot, rate: f64, bar: bool) -> String {
+    let mut line = String::new();
+    if bar {
+        let ratio = match (s.finished, s.total) {
+            (true, _) => 1.0,
+            (_, 0) => 0.0,
+            (_, t) => (s.done() as f64 / t as f64).min(1.0),
+        };
+        let filled = (ratio * BAR_WIDTH as f64) as usize;
+        line.push_str(&format!(
+            "[{}{}] {:5.1}% ",
+            "=".repeat(filled),
+            " ".repeat(BAR_WIDTH - filled),
+            ratio * 100.0
+        ));
+    }
+    let total = match s.total {
+        0 => "?".to_string(),
+        t => t.to_string(),
+    };
+    let eta = match (s.finished, eta_secs(s, rate)) {
+        (true, _) => "done".to_string(),
+        (_, Some(secs)) => format!("eta {}", format_duration(secs)),
+        (_, None) => "eta -".to_string(),
+    };
+    line.push_str(&format!(
+        "{} scanned {}/{} compared {} mismatches {} {:.0} keys/s {}",
+        label, s.scanned, total, s.compared, s.mismatched, rate, eta
+    ));
+    line
+}
+
+// 每个 DB 对上次输出时的已处理数量与时间，用于计算当前吞吐
+struct RateState {
+    at: Instant,
+    done: u64,
+    // 非 TTY 下已结束的 DB 对只输出一次
+    finish_logged: bool,
+}
+
+// 校验进度输出，TTY 下刷新进度条，否则每隔 interval 输出日志
+pub struct ProgressReporter {
+    pairs: Arc<Mutex<Vec<Arc<PairProgress>>>>,
+    stop: Option<Sender<()>>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl ProgressReporter {
+    // interval 为 0 时不输出进度，计数仍可正常累加
+    pub fn start(interval: Duration) -> Self {
+        let pairs: Arc<Mutex<Vec<Arc<PairProgress>>>> = Arc::new(Mutex::new(vec![]));
+        if interval.is_zero() {
+            return Self {
+                pairs,
+                stop: None,
+                handle: None,
+            };
+        }
+
+        let (tx, rx) = channel::<()>();
+        let pairs_move = pairs.clone();
+        let handle = thread::spawn(move || {
+            // IsTerminal 自 Rust 1.70 起稳定，与 Cargo.toml 中的 rust-version 一致
+            let tty = stderr().is_terminal();
+            let period = if tty { TTY_REFRESH } else { interval };
+            let mut rates: Vec<RateState> = vec![];
+            let mut rendered = 0;
+            loop {
+                // 收到停止信号或发送端释放时输出最终进度后退出
+                let stop = !matches!(rx.recv_timeout(period), Err(RecvTimeoutError::Timeout));
+                let pairs = match pairs_move.lock() {
+                    Ok(p) => p.clone(),
+                    Err(e) => {
+                        log::error!("{}", e);
+                        return;
+                    }
+                };
+
+                let now = Instant::now();
+                let mut lines = vec![];
+                for (i, p) in pairs.iter().enumerate() {
+                    let s = p.snapshot();
+                    if rates.len() <= i {
+                        rates.push(RateState {
+                            at: p.started,
+                            done: 0,
+                            finish_logged: false,
+                        });
+                    }
+                    let r = &mut rates[i];
+                    let elapsed = now.duration_since(r.at).as_secs_f64();
+                    let rate = match elapsed > 0.0 {
+                        true => s.done().saturating_sub(r.done) as f64 / elapsed,
+                        false => 0.0,
+                    };
+                    r.at = now;
+                    r.done = s.done();
+                    if !tty && s.finished {
+                        if r.finish_logged {
+                            continue;
+                        }
+                        r.finish_logged = true;
+                    }
+                    lines.push(render_line(&p.label, &s, rate, tty));
+                }
+
+                if tty {
+                    let mut err = stderr().lock();
+                    if rendered > 0 {
+                        let _ = write!(err, "\x1b[{}A", rendered);
+                    }
+                    for l in &lines {
+                        let _ = writeln!(err, "\r\x1b[2K{}", l);
+                    }
+                    let _ = err.flush();
+                    rendered = lines.len();
+                } else {
+                    for l in &lines {
+                        log::info!("progress {}", l);
+                    }
+                }
+
+                if stop {
+                    return;
+                }
+            }
+        });
+        Self {
+            pairs,
+            stop: Some(tx),
+            handle: Some(handle),
+        }
+    }
+
+    pub fn register(&self, label: String) -> Arc<PairProgress> {
+        let progress = Arc::new(PairProgress::new(label));
+        match self.pairs.lock() {
+            Ok(mut pairs) => pairs.push(progress.clone()),
+            Err(e) => log::error!("{}", e),
+        }
+        progress
+    }
+
+    // 输出最终进度并等待进度线程退出
+    pub fn stop(&mut self) {
+        if let Some(tx) = self.stop.take() {
+            let _ = tx.send(());
+        }
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Drop for ProgressReporter {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    //cargo test compare::progress::test::test_render_line --  --nocapture
+    #[test]
+    fn test_render_line() {
+        let mut s = ProgressSnapshot {
+            total: 1000,
+            scanned: 600,
+            skipped: 100,
+            compared: 400,
+            mismatched: 3,
+            finished: false,
+        };
+        assert_eq!(eta_secs(&s, 100.0), Some(5));
+        assert_eq!(
+            render_line("db0 -> db0", &s, 100.0, false),
+            "db0 -> db0 scanned 600/1000 compared 400 mismatches 3 100 keys/s eta 5s"
+        );
+        let line = render_line("db0 -> db0", &s, 0.0, true);
+        assert!(line.starts_with(&format!("[{}{}]  50.0% ", "=".repeat(15), " ".repeat(15))));
+        assert!(line.ends_with("eta -"));
+
+        s.total = 0;
+        assert_eq!(eta_secs(&s, 100.0), None);
+        assert!(render_line("db0", &s, 0.0, false).contains("scanned 600/?"));
+        s.finished = true;
+        assert!(render_line("db0", &s, 0.0, true)
+            .starts_with(&format!("[{}] 100.0% ", "=".repeat(BAR_WIDTH))));
+
+        assert_eq!(format_duration(3725), "1h02m05s");
+        assert_eq!(format_duration(65), "1m05s");
+    }
+}
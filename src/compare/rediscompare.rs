@@ -1,20 +1,27 @@
-use crate::compare::{compare_from_file, CompareDB, CompareDBReverse};
-use crate::util::RedisClient;
-use crate::util::{rand_lettter_number_string, rand_string, RedisClientWithDB};
+use super::big_key::BigKey;
+use super::compare_db::exec_rdb_group;
+use super::comparekey::IffyKey;
+use super::progress::ProgressReporter;
+use super::result_store::{ResultStore, ResultStoreConfig, FIRST_ROUND};
+use crate::compare::{CompareDB, CompareDBReverse, FailKeys};
+use crate::compare::{CompareOptions, KeyMapRule, KeyMapper};
+use crate::util::RedisClientWithDB;
+use crate::util::{cluster_masters, ConnectionTimeouts, RedisClient, SentinelClient};
 use anyhow::{anyhow, Result};
-use chrono::prelude::Local;
 use redis::cluster::ClusterClientBuilder;
-use redis::RedisResult;
-use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::ffi::OsString;
-use std::fs::{self, create_dir, remove_dir_all, File, OpenOptions, ReadDir};
-use std::io::{LineWriter, Read, Write};
-use std::ops::Sub;
-use std::str::FromStr;
+use redis::{
+    ClientTlsConfig, ConnectionAddr, ConnectionInfo, ErrorKind, IntoConnectionInfo,
+    RedisConnectionInfo, RedisError, RedisResult, TlsCertificates, TlsMode,
+};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use std::vec;
 
-pub const COMPARE_STATUS_FILE_NAME: &str = ".compare_status";
+// 首次校验检测到的大 key 报告
+pub const BIG_KEYS_REPORT_FILE_NAME: &str = "big_keys_report.json";
 
 #[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "lowercase")]
@@ -31,17 +38,124 @@ pub enum ScenarioType {
 pub enum InstanceType {
     Single,
     Cluster,
+    // 由 sentinel 管理的主从实例，urls 为 sentinel 地址
+    Sentinel,
+}
+
+// TLS 连接配置，证书与私钥均为 PEM 文件路径
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, Clone)]
+pub struct TlsConfig {
+    #[serde(default = "TlsConfig::enable_default")]
+    pub enable: bool,
+    // CA 证书路径，为空时使用系统根证书
+    #[serde(default = "TlsConfig::path_default")]
+    pub ca_cert: String,
+    // 客户端证书路径，与 client_key 同时配置时启用双向认证
+    #[serde(default = "TlsConfig::path_default")]
+    pub client_cert: String,
+    #[serde(default = "TlsConfig::path_default")]
+    pub client_key: String,
+    // 跳过服务端证书校验
+    #[serde(default = "TlsConfig::insecure_skip_verify_default")]
+    pub insecure_skip_verify: bool,
+}
+
+impl Default for TlsConfig {
+    fn default() -> Self {
+        Self {
+            enable: false,
+            ca_cert: "".to_string(),
+            client_cert: "".to_string(),
+            client_key: "".to_string(),
+            insecure_skip_verify: false,
+        }
+    }
+}
+
+impl TlsConfig {
+    pub fn enable_default() -> bool {
+        false
+    }
+    pub fn path_default() -> String {
+        "".to_string()
+    }
+    pub fn insecure_skip_verify_default() -> bool {
+        false
+    }
+
+    fn has_certs(&self) -> bool {
+        !self.ca_cert.is_empty() || !self.client_cert.is_empty() || !self.client_key.is_empty()
+    }
+
+    // 读取证书文件，生成 redis 客户端使用的证书结构
+    fn to_tls_certificates(&self) -> RedisResult<TlsCertificates> {
+        let read_pem = |path: &str| -> RedisResult<Vec<u8>> {
+            fs::read(path).map_err(|e| {
+                RedisError::from((
+                    ErrorKind::InvalidClientConfig,
+                    "read tls file error",
+                    format!("{}: {}", path, e),
+                ))
+            })
+        };
+
+        let client_tls = match (self.client_cert.is_empty(), self.client_key.is_empty()) {
+            (true, true) => None,
+            (false, false) => Some(ClientTlsConfig {
+                client_cert: read_pem(&self.client_cert)?,
+                client_key: read_pem(&self.client_key)?,
+            }),
+            _ => {
+                return Err(RedisError::from((
+                    ErrorKind::InvalidClientConfig,
+                    "client_cert and client_key must be set together",
+                )))
+            }
+        };
+        let root_cert = match self.ca_cert.is_empty() {
+            true => None,
+            false => Some(read_pem(&self.ca_cert)?),
+        };
+
+        Ok(TlsCertificates {
+            client_tls,
+            root_cert,
+        })
+    }
 }
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "lowercase")]
 pub struct RedisInstance {
+    // 支持 redis://、rediss:// 格式的 url 或 host:port
     #[serde(default = "RedisInstance::urls_default")]
     pub urls: Vec<String>,
+    // 非空时覆盖 url 中的密码
     #[serde(default = "RedisInstance::password_default")]
     pub password: String,
     #[serde(default = "RedisInstance::instance_type_default")]
     pub instance_type: InstanceType,
+    // 结果文件按字段顺序编码，新增字段只能追加在已有字段之后
+    // Redis 6 ACL 用户名，非空时覆盖 url 中的用户名
+    #[serde(default = "RedisInstance::username_default")]
+    pub username: String,
+    #[serde(default = "RedisInstance::tls_default")]
+    pub tls: TlsConfig,
+    // 连接超时，单位毫秒，0 表示不限制
+    #[serde(default = "RedisInstance::timeout_default")]
+    pub connect_timeout: u64,
+    // 读超时，单位毫秒，0 表示不限制
+    #[serde(default = "RedisInstance::timeout_default")]
+    pub read_timeout: u64,
+    // sentinel 模式下监控的 master 名称
+    #[serde(default = "RedisInstance::master_name_default")]
+    pub master_name: String,
+    // sentinel 节点密码，与数据节点密码相互独立
+    #[serde(default = "RedisInstance::password_default")]
+    pub sentinel_password: String,
+    // sentinel 模式下优先从 replica 读取
+    #[serde(default = "RedisInstance::read_from_replica_default")]
+    pub read_from_replica: bool,
 }
 
 impl Default for RedisInstance {
@@ -50,6 +164,13 @@ impl Default for RedisInstance {
             urls: vec!["redis://127.0.0.1:6379".to_string()],
             password: "".to_string(),
             instance_type: InstanceType::Single,
+            username: "".to_string(),
+            tls: TlsConfig::default(),
+            connect_timeout: 0,
+            read_timeout: 0,
+            master_name: RedisInstance::master_name_default(),
+            sentinel_password: "".to_string(),
+            read_from_replica: false,
         }
     }
 }
@@ -62,99 +183,284 @@ impl RedisInstance {
                 "redis://127.0.0.1:6380".to_string(),
                 "redis://127.0.0.1:6381".to_string(),
             ],
-            password: "".to_string(),
             instance_type: InstanceType::Cluster,
+            ..RedisInstance::default()
+        }
+    }
+
+    pub fn default_sentinel() -> Self {
+        Self {
+            urls: vec![
+                "redis://127.0.0.1:26379".to_string(),
+                "redis://127.0.0.1:26380".to_string(),
+                "redis://127.0.0.1:26381".to_string(),
+            ],
+            instance_type: InstanceType::Sentinel,
+            ..RedisInstance::default()
         }
     }
 
     pub fn urls_default() -> Vec<String> {
         vec!["redis://127.0.0.1:6379".to_string()]
     }
+    pub fn username_default() -> String {
+        "".to_string()
+    }
     pub fn password_default() -> String {
         "".to_string()
     }
     pub fn instance_type_default() -> InstanceType {
         InstanceType::Single
     }
+    pub fn tls_default() -> TlsConfig {
+        TlsConfig::default()
+    }
+    pub fn timeout_default() -> u64 {
+        0
+    }
+    pub fn master_name_default() -> String {
+        "mymaster".to_string()
+    }
+    pub fn read_from_replica_default() -> bool {
+        false
+    }
+
+    pub fn timeouts(&self) -> ConnectionTimeouts {
+        let to_duration = |ms: u64| match ms {
+            0 => None,
+            _ => Some(Duration::from_millis(ms)),
+        };
+        ConnectionTimeouts {
+            connect: to_duration(self.connect_timeout),
+            read: to_duration(self.read_timeout),
+        }
+    }
+
+    // 将 url 解析为 ConnectionInfo，并合并 username、password、tls 配置
+    pub fn connection_info(&self, url: &str) -> RedisResult<ConnectionInfo> {
+        let mut info = match url.contains("://") {
+            true => url.into_connection_info()?,
+            false => {
+                let (host, port) = match url.rsplit_once(':') {
+                    Some((h, p)) => {
+                        let port = p.parse::<u16>().map_err(|_| {
+                            RedisError::from((
+                                ErrorKind::InvalidClientConfig,
+                                "invalid port",
+                                url.to_string(),
+                            ))
+                        })?;
+                        (h.to_string(), port)
+                    }
+                    None => (url.to_string(), 6379),
+                };
+                ConnectionInfo {
+                    addr: ConnectionAddr::Tcp(host, port),
+                    redis: RedisConnectionInfo::default(),
+                }
+            }
+        };
+
+        if !self.username.is_empty() {
+            info.redis.username = Some(self.username.clone());
+        }
+        if !self.password.is_empty() {
+            info.redis.password = Some(self.password.clone());
+        }
+
+        info.addr = match info.addr {
+            ConnectionAddr::Tcp(host, port) if self.tls.enable => ConnectionAddr::TcpTls {
+                host,
+                port,
+                insecure: self.tls.insecure_skip_verify,
+                tls_params: None,
+            },
+            ConnectionAddr::TcpTls {
+                host,
+                port,
+                insecure,
+                tls_params,
+            } => ConnectionAddr::TcpTls {
+                host,
+                port,
+                insecure: insecure || self.tls.insecure_skip_verify,
+                tls_params,
+            },
+            addr => addr,
+        };
+        Ok(info)
+    }
+
+    pub fn connection_infos(&self) -> RedisResult<Vec<ConnectionInfo>> {
+        let mut infos = vec![];
+        for url in &self.urls {
+            infos.push(self.connection_info(url)?);
+        }
+        Ok(infos)
+    }
+
+    fn is_tls(info: &ConnectionInfo) -> bool {
+        matches!(info.addr, ConnectionAddr::TcpTls { .. })
+    }
+
+    // 根据 ConnectionInfo 创建单实例 client，配置证书时加载证书
+    fn open_single_client(&self, info: ConnectionInfo) -> RedisResult<redis::Client> {
+        if Self::is_tls(&info) && self.tls.has_certs() {
+            return redis::Client::build_with_tls(info, self.tls.to_tls_certificates()?);
+        }
+        redis::Client::open(info)
+    }
+
+    // 生成 sentinel client，sentinel 节点使用 sentinel_password 认证，数据节点使用实例认证信息
+    pub fn to_sentinel_client(&self) -> RedisResult<SentinelClient> {
+        let mut sentinels = vec![];
+        for url in &self.urls {
+            let mut info = self.connection_info(url)?;
+            info.redis = RedisConnectionInfo::default();
+            if !self.sentinel_password.is_empty() {
+                info.redis.password = Some(self.sentinel_password.clone());
+            }
+            sentinels.push(self.open_single_client(info)?);
+        }
+
+        let node_tls = match self.tls.enable {
+            true => Some(self.tls.insecure_skip_verify),
+            false => None,
+        };
+        let node_certs = match self.tls.enable && self.tls.has_certs() {
+            true => Some(self.tls.to_tls_certificates()?),
+            false => None,
+        };
+        let node_redis = RedisConnectionInfo {
+            db: 0,
+            username: match self.username.is_empty() {
+                true => None,
+                false => Some(self.username.clone()),
+            },
+            password: match self.password.is_empty() {
+                true => None,
+                false => Some(self.password.clone()),
+            },
+        };
+
+        Ok(SentinelClient {
+            sentinels,
+            master_name: self.master_name.clone(),
+            node_redis,
+            node_tls,
+            node_certs,
+            read_from_replica: self.read_from_replica,
+        })
+    }
 
     pub fn to_single_redis_clients(&self) -> RedisResult<Vec<redis::Client>> {
+        if let InstanceType::Sentinel = self.instance_type {
+            return Ok(vec![self.to_sentinel_client()?.master_client()?]);
+        }
+        let infos = self.connection_infos()?;
         return match self.instance_type {
             InstanceType::Single => {
                 let mut vec_client = vec![];
-                let cl = redis::Client::open(self.urls[0].as_str())?;
-                vec_client.push(cl);
+                if let Some(info) = infos.into_iter().next() {
+                    vec_client.push(self.open_single_client(info)?);
+                }
                 Ok(vec_client)
             }
             InstanceType::Cluster => {
                 let mut vec_client = vec![];
-                for url in self.urls.clone() {
-                    if !self.password.is_empty() {
-                        let url_split_rs = url.split(r#"//"#).collect::<Vec<&str>>();
-                        let url_single = url_split_rs[0].to_string()
-                            + "//"
-                            + ":"
-                            + &self.password
-                            + "@"
-                            + url_split_rs[1];
-
-                        let cl = redis::Client::open(url_single)?;
-                        vec_client.push(cl);
-                    } else {
-                        let cl = redis::Client::open(url)?;
-                        vec_client.push(cl);
-                    }
+                for info in infos {
+                    vec_client.push(self.open_single_client(info)?);
                 }
                 Ok(vec_client)
             }
+            InstanceType::Sentinel => Ok(vec![]),
         };
     }
 
+    // cluster 中的 master 节点及其负责的 slot，通过第一个可连接的节点发现全部 master
+    // urls 只配置部分节点或包含 replica 时，结果仍为每个 master 一个 client
+    pub fn cluster_master_clients(&self) -> RedisResult<Vec<(redis::Client, Vec<u16>)>> {
+        let mut last_err = RedisError::from((ErrorKind::InvalidClientConfig, "urls is empty"));
+        for info in self.connection_infos()? {
+            let client = self.open_single_client(info.clone())?;
+            let nodes = self
+                .timeouts()
+                .single_connection(&client)
+                .and_then(|mut conn| cluster_masters(&mut conn));
+            let nodes = match nodes {
+                Ok(n) => n,
+                Err(e) => {
+                    last_err = e;
+                    continue;
+                }
+            };
+
+            let mut masters = vec![];
+            for (host, port, slots) in nodes {
+                // 保留认证信息与 tls 配置，仅替换节点地址
+                let mut node_info = info.clone();
+                node_info.addr = match node_info.addr {
+                    ConnectionAddr::TcpTls {
+                        insecure,
+                        tls_params,
+                        ..
+                    } => ConnectionAddr::TcpTls {
+                        host,
+                        port,
+                        insecure,
+                        tls_params,
+                    },
+                    _ => ConnectionAddr::Tcp(host, port),
+                };
+                masters.push((self.open_single_client(node_info)?, slots));
+            }
+            return Ok(masters);
+        }
+        Err(last_err)
+    }
+
     pub fn to_redis_client(&self) -> RedisResult<RedisClient> {
+        let infos = self.connection_infos()?;
         return match self.instance_type {
             InstanceType::Single => {
-                // let cl = redis::Client::open(self.urls[0].as_str())?;
-                let cl = match !self.password.is_empty() {
-                    true => {
-                        let url = self.urls[0].clone();
-                        let url_split_rs = url.split(r#"//"#).collect::<Vec<&str>>();
-                        let url_single = url_split_rs[0].to_string()
-                            + "//"
-                            + ":"
-                            + &self.password
-                            + "@"
-                            + url_split_rs[1];
-
-                        let cl = redis::Client::open(url_single)?;
-                        cl
+                let info = match infos.into_iter().next() {
+                    Some(i) => i,
+                    None => {
+                        return Err(RedisError::from((
+                            ErrorKind::InvalidClientConfig,
+                            "urls is empty",
+                        )))
                     }
-                    false => {
-                        let cl = redis::Client::open(self.urls[0].clone())?;
-                        cl
-                    } //         let url_split_rs = self.urls[0].split(r#"//"#).collect::<Vec<&str>>();
-                      //         let url_single = url_split_rs[0].to_string()
-                      //             + "//"
-                      //             + ":"
-                      //             + &self.password
-                      //             + "@"
-                      //             + url_split_rs[1];
-
-                      //         let cl = redis::Client::open(url_single)?;
-                      //         vec_client.push(cl);
-                      //     } else {
-                      //         let cl = redis::Client::open(url)?;
-                      //         vec_client.push(cl);
                 };
-
-                Ok(RedisClient::Single(cl))
+                let cl = self.open_single_client(info)?;
+                Ok(RedisClient::Single(cl, self.timeouts()))
             }
             InstanceType::Cluster => {
-                let mut cb = ClusterClientBuilder::new(self.urls.clone());
+                let tls = infos.first().map(Self::is_tls).unwrap_or(false);
+                let mut cb = ClusterClientBuilder::new(infos);
+                if !self.username.is_empty() {
+                    cb = cb.username(self.username.clone());
+                }
                 if !self.password.is_empty() {
                     cb = cb.password(self.password.clone());
                 }
-                let cl = cb.open()?;
-                Ok(RedisClient::Cluster(cl))
+                if tls {
+                    if self.tls.has_certs() {
+                        cb = cb.certs(self.tls.to_tls_certificates()?);
+                    }
+                    cb = match self.tls.insecure_skip_verify {
+                        true => cb.tls(TlsMode::Insecure),
+                        false => cb.tls(TlsMode::Secure),
+                    };
+                }
+                let timeouts = self.timeouts();
+                let cl = timeouts.cluster_client(cb)?;
+                Ok(RedisClient::Cluster(cl, timeouts))
             }
+            InstanceType::Sentinel => Ok(RedisClient::Sentinel(
+                self.to_sentinel_client()?,
+                self.timeouts(),
+            )),
         };
     }
 }
@@ -163,6 +469,12 @@ impl RedisInstance {
 pub struct RedisInstanceWithDB {
     pub instance: RedisInstance,
     pub db: usize,
+    // 仅 source 端使用，描述 source key 在 target 中的命名规则
+    #[serde(default)]
+    pub key_mapping: Vec<KeyMapRule>,
+    // source 端非空时从 RDB 文件读取该 DB 的数据，离线比较 RDB 文件时 target 端记录被比较的文件
+    #[serde(default)]
+    pub rdb_file: String,
 }
 
 impl Default for RedisInstanceWithDB {
@@ -170,6 +482,8 @@ impl Default for RedisInstanceWithDB {
         Self {
             instance: RedisInstance::default(),
             db: 0,
+            key_mapping: vec![],
+            rdb_file: "".to_string(),
         }
     }
 }
@@ -177,7 +491,8 @@ impl Default for RedisInstanceWithDB {
 impl RedisInstanceWithDB {
     pub fn to_single_redis_instance_with_db_vec(&self) -> Vec<RedisInstanceWithDB> {
         let instances: Vec<RedisInstanceWithDB> = match self.instance.instance_type {
-            InstanceType::Single => {
+            // sentinel 实例的数据只需从当前 master 读取
+            InstanceType::Single | InstanceType::Sentinel => {
                 let mut vec: Vec<RedisInstanceWithDB> = vec![];
                 vec.push(self.clone());
                 vec
@@ -185,37 +500,19 @@ impl RedisInstanceWithDB {
             InstanceType::Cluster => {
                 let mut vec: Vec<RedisInstanceWithDB> = vec![];
                 for url in self.instance.urls.clone() {
-                    if !self.instance.password.is_empty() {
-                        let url_split_rs = url.split(r#"//"#).collect::<Vec<&str>>();
-                        let url_single = url_split_rs[0].to_string()
-                            + "//"
-                            + ":"
-                            + &self.instance.password
-                            + "@"
-                            + url_split_rs[1];
-
-                        let redis_instance = RedisInstance {
-                            urls: vec![url_single],
-                            password: String::from(""),
-                            instance_type: InstanceType::Single,
-                        };
-                        let instance = RedisInstanceWithDB {
-                            instance: redis_instance,
-                            db: self.db,
-                        };
-                        vec.push(instance);
-                    } else {
-                        let redis_instance = RedisInstance {
-                            urls: vec![url],
-                            password: String::from(""),
-                            instance_type: InstanceType::Single,
-                        };
-                        let instance = RedisInstanceWithDB {
-                            instance: redis_instance,
-                            db: self.db,
-                        };
-                        vec.push(instance);
-                    }
+                    // 保留 username、password、tls 等连接配置，仅替换节点地址
+                    let redis_instance = RedisInstance {
+                        urls: vec![url],
+                        instance_type: InstanceType::Single,
+                        ..self.instance.clone()
+                    };
+                    let instance = RedisInstanceWithDB {
+                        instance: redis_instance,
+                        db: self.db,
+                        key_mapping: self.key_mapping.clone(),
+                        rdb_file: self.rdb_file.clone(),
+                    };
+                    vec.push(instance);
                 }
                 vec
             }
@@ -223,6 +520,27 @@ impl RedisInstanceWithDB {
         instances
     }
 
+    // 进度输出中的 DB 标识
+    pub fn label(&self) -> String {
+        match self.rdb_file.is_empty() {
+            true => format!("{}/db{}", self.instance.urls.join(","), self.db),
+            false => format!("{}/db{}", self.rdb_file, self.db),
+        }
+    }
+
+    pub fn key_mapper(&self) -> Result<KeyMapper> {
+        KeyMapper::new(&self.key_mapping)
+    }
+
+    // RDB 数据在内存中读取，无法执行 Lua，不使用服务端 SHA1 比较大 string
+    pub fn source_options(&self, options: &CompareOptions) -> CompareOptions {
+        let mut options = options.clone();
+        if !self.rdb_file.is_empty() {
+            options.string_chunk_sha1 = false;
+        }
+        options
+    }
+
     pub fn to_redis_client_with_db(&self) -> RedisResult<RedisClientWithDB> {
         let client = self.instance.to_redis_client()?;
         let rcwb = RedisClientWithDB {
@@ -233,21 +551,55 @@ impl RedisInstanceWithDB {
     }
 }
 
+// dbmapper 中 source db 对应的 target
+// 可直接配置 target db 编号，也可配置为 {db: 0, prefix: "db3:"}
+// cluster 只有 db 0，多 db 合并到 cluster 时以 key 前缀区分 source db
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize, Clone)]
+#[serde(untagged)]
+pub enum DBMapTarget {
+    DB(usize),
+    Prefix { db: usize, prefix: String },
+}
+
+impl DBMapTarget {
+    pub fn db(&self) -> usize {
+        match self {
+            DBMapTarget::DB(db) => *db,
+            DBMapTarget::Prefix { db, .. } => *db,
+        }
+    }
+
+    pub fn prefix(&self) -> Option<&str> {
+        match self {
+            DBMapTarget::DB(_) => None,
+            DBMapTarget::Prefix { prefix, .. } => Some(prefix.as_str()),
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
 pub struct SourceInstance {
     #[serde(default = "SourceInstance::instance_default")]
     pub instance: RedisInstance,
     #[serde(default = "SourceInstance::dbmapper_default")]
-    pub dbmapper: HashMap<usize, usize>,
+    pub dbmapper: HashMap<usize, DBMapTarget>,
+    // source key 在 target 中的改名规则，如增加前缀、去除前缀、正则替换
+    #[serde(default = "SourceInstance::key_mapping_default")]
+    pub key_mapping: Vec<KeyMapRule>,
+    // 离线校验时的 RDB 文件路径，非空时 source 数据从该文件读取，instance 配置被忽略
+    #[serde(default = "SourceInstance::rdb_file_default")]
+    pub rdb_file: String,
 }
 
 impl Default for SourceInstance {
     fn default() -> Self {
         let mut mapper = HashMap::new();
-        mapper.insert(0, 0);
+        mapper.insert(0, DBMapTarget::DB(0));
         Self {
             instance: RedisInstance::default(),
             dbmapper: mapper,
+            key_mapping: vec![],
+            rdb_file: "".to_string(),
         }
     }
 }
@@ -256,11 +608,28 @@ impl SourceInstance {
     pub fn instance_default() -> RedisInstance {
         RedisInstance::default()
     }
-    pub fn dbmapper_default() -> HashMap<usize, usize> {
+    pub fn dbmapper_default() -> HashMap<usize, DBMapTarget> {
         let mut mapper = HashMap::new();
-        mapper.insert(0, 0);
+        mapper.insert(0, DBMapTarget::DB(0));
         mapper
     }
+    pub fn key_mapping_default() -> Vec<KeyMapRule> {
+        vec![]
+    }
+    pub fn rdb_file_default() -> String {
+        "".to_string()
+    }
+
+    // source db 映射到 target 时的 key 改名规则，db 前缀在其他规则之后添加
+    pub fn key_mapping_for(&self, target: &DBMapTarget) -> Vec<KeyMapRule> {
+        let mut rules = self.key_mapping.clone();
+        if let Some(prefix) = target.prefix() {
+            if !prefix.is_empty() {
+                rules.push(KeyMapRule::AddPrefix(prefix.to_string()));
+            }
+        }
+        rules
+    }
 }
 
 #[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
@@ -269,12 +638,19 @@ pub struct Compare {
     pub source: Vec<SourceInstance>,
     #[serde(default = "Compare::target_default")]
     pub target: RedisInstance,
-    #[serde(default = "Compare::batch_size_default")]
+    // 分页读取与比较的元素数量，不能为 0
+    #[serde(
+        default = "Compare::batch_size_default",
+        deserialize_with = "Compare::deserialize_batch_size"
+    )]
     pub batch_size: usize,
     #[serde(default = "Compare::threads_default")]
     pub threads: usize,
     #[serde(default = "Compare::compare_threads_default")]
     pub compare_threads: usize,
+    // 每个 DB 比较大 key 的线程数
+    #[serde(default = "Compare::big_key_threads_default")]
+    pub big_key_threads: usize,
     #[serde(default = "Compare::ttl_diff_default")]
     pub ttl_diff: usize,
     #[serde(default = "Compare::compare_times_default")]
@@ -291,6 +667,21 @@ pub struct Compare {
     // 比较频率，当出现校验失败的key时循环比较的次数
     #[serde(default = "Compare::frequency_default")]
     pub frequency: usize,
+    // compare config 允许存在差异的参数名，正则表达式，如端口、数据目录等实例相关的参数
+    #[serde(default = "Compare::config_allow_diff_default")]
+    pub config_allow_diff: Vec<String>,
+    // compare config 比较的 INFO 字段
+    #[serde(default = "Compare::config_info_fields_default")]
+    pub config_info_fields: Vec<String>,
+    // 校验结果存储，默认为 MessagePack 文件目录
+    #[serde(default = "Compare::result_store_default")]
+    pub result_store: ResultStoreConfig,
+    // 首次校验的进度输出间隔，单位秒，TTY 下固定刷新进度条，0 表示不输出进度
+    #[serde(default = "Compare::progress_interval_default")]
+    pub progress_interval: u64,
+    // 校验选项，如 zset score 误差、排名校验
+    #[serde(flatten)]
+    pub options: CompareOptions,
 }
 
 impl Default for Compare {
@@ -301,6 +692,7 @@ impl Default for Compare {
             batch_size: 10,
             threads: 1,
             compare_threads: 1,
+            big_key_threads: 1,
             ttl_diff: 1,
             compare_times: 1,
             compare_interval: 1,
@@ -308,6 +700,11 @@ impl Default for Compare {
             scenario: ScenarioType::Single2single,
             bothway: false,
             frequency: 1,
+            config_allow_diff: Compare::config_allow_diff_default(),
+            config_info_fields: Compare::config_info_fields_default(),
+            result_store: ResultStoreConfig::default(),
+            progress_interval: Compare::progress_interval_default(),
+            options: CompareOptions::default(),
         }
     }
 }
@@ -322,12 +719,27 @@ impl Compare {
     fn batch_size_default() -> usize {
         10
     }
+    fn deserialize_batch_size<'de, D>(deserializer: D) -> Result<usize, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let batch_size = usize::deserialize(deserializer)?;
+        if batch_size == 0 {
+            return Err(serde::de::Error::custom(
+                "batch_size must be greater than 0",
+            ));
+        }
+        Ok(batch_size)
+    }
     fn threads_default() -> usize {
         1
     }
     fn compare_threads_default() -> usize {
         1
     }
+    fn big_key_threads_default() -> usize {
+        1
+    }
     fn ttl_diff_default() -> usize {
         2
     }
@@ -350,21 +762,67 @@ impl Compare {
     fn frequency_default() -> usize {
         1
     }
+    fn config_allow_diff_default() -> Vec<String> {
+        vec![
+            "^(port|bind|dir|dbfilename|pidfile|logfile|unixsocket|aclfile)$".to_string(),
+            "^(requirepass|masterauth|masteruser|replicaof|slaveof)$".to_string(),
+            "^cluster-config-file$".to_string(),
+            "^cluster-announce-".to_string(),
+            "^tls-.*-file$".to_string(),
+        ]
+    }
+    fn config_info_fields_default() -> Vec<String> {
+        vec![
+            "redis_version".to_string(),
+            "maxmemory_policy".to_string(),
+            "aof_enabled".to_string(),
+            "cluster_enabled".to_string(),
+        ]
+    }
+
+    fn result_store_default() -> ResultStoreConfig {
+        ResultStoreConfig::default()
+    }
+
+    fn progress_interval_default() -> u64 {
+        10
+    }
 
     pub fn exec(&self) {
+        // 反向校验需要将 target key 还原为 source key，改名规则必须可逆
+        if self.bothway {
+            for s in &self.source {
+                // RDB 文件无法按 key 查询是否存在
+                if !s.rdb_file.is_empty() {
+                    log::error!("bothway is not supported for rdb source {}", s.rdb_file);
+                    return;
+                }
+                if s.key_mapping.iter().any(|r| !r.reversible()) {
+                    log::error!(
+                        "key_mapping of source {:?} is not reversible",
+                        s.instance.urls
+                    );
+                    return;
+                }
+            }
+        }
+
         let mut compare_times_remainder = self.frequency;
         // 首次校验
-        // 删除中间文件目录
-        let _ = remove_result_dir();
-        // 生成中间文件目录
-
-        let current_dir = match create_result_dir() {
-            Ok(s) => s,
+        // 打开结果存储，每次运行有独立的运行 ID
+        let result_store: Arc<dyn ResultStore> = match self.result_store.open() {
+            Ok(s) => Arc::from(s),
             Err(e) => {
-                log::error!("{}", e.to_string());
+                log::error!("{}", e);
                 return;
             }
         };
+        log::info!("compare run id: {}", result_store.run_id());
+        println!("run id: {}", result_store.run_id());
+        if let Err(e) = result_store.begin_round(FIRST_ROUND) {
+            log::error!("{}", e);
+            return;
+        }
         // 获取source to target 对应关系
         let map_dbinstance_s_t = match self.map_dbinstance_source_to_target() {
             Ok(map) => map,
@@ -385,20 +843,47 @@ impl Compare {
             }
         };
 
-        let current_dir_move = current_dir.clone();
+        let big_keys: Arc<Mutex<Vec<BigKey>>> = Arc::new(Mutex::new(vec![]));
+        let big_keys_move = big_keys.clone();
+        let result_store_move = result_store.clone();
+        let mut reporter = ProgressReporter::start(Duration::from_secs(self.progress_interval));
+        let reporter_ref = &reporter;
         pool.scope(move |p| {
             // 正向校验
+            // source 为 RDB 文件的 DB 对按文件分组，同一文件只解析一次
+            let mut rdb_groups: BTreeMap<String, Vec<CompareDB>> = BTreeMap::new();
             for (s, t) in map_dbinstance_s_t {
+                let label = format!("{} -> {}", s.label(), t.label());
                 let db_compare = CompareDB {
                     source: s,
                     target: t,
                     batch: self.batch_size,
                     ttl_diff: self.ttl_diff,
                     compare_pool: self.compare_threads,
-                    result_store_dir: current_dir.clone(),
+                    big_key_pool: self.big_key_threads,
+                    result_store: result_store_move.clone(),
+                    options: self.options.clone(),
+                    big_keys: big_keys_move.clone(),
+                    progress: reporter_ref.register(label),
                 };
+                if !db_compare.source.rdb_file.is_empty() {
+                    rdb_groups
+                        .entry(db_compare.source.rdb_file.clone())
+                        .or_default()
+                        .push(db_compare);
+                    continue;
+                }
                 p.spawn(move |_| {
                     db_compare.exec();
+                    db_compare.progress.finish();
+                });
+            }
+            for (_, group) in rdb_groups {
+                p.spawn(move |_| {
+                    exec_rdb_group(&group);
+                    for c in &group {
+                        c.progress.finish();
+                    }
                 });
             }
 
@@ -419,111 +904,138 @@ impl Compare {
                 for (t, s) in map {
                     // 将 目标 redis instance 转化为但实例的的 redis instance 数组
                     for tc in t.to_single_redis_instance_with_db_vec() {
+                        let label = format!("reverse {}", tc.label());
                         let compare_db_reverse = CompareDBReverse {
                             source: s.clone(),
                             target: tc,
                             batch: self.batch_size,
                             ttl_diff: self.ttl_diff,
                             compare_pool: self.compare_threads,
-                            result_store_dir: current_dir.clone(),
+                            result_store: result_store_move.clone(),
+                            options: self.options.clone(),
+                            progress: reporter_ref.register(label),
                         };
                         compare_db_reverse.exec();
+                        compare_db_reverse.progress.finish();
                     }
                 }
             }
         });
+        reporter.stop();
+        if let Err(e) = write_big_keys_report(result_store.as_ref(), &big_keys) {
+            log::error!("{}", e);
+        }
         compare_times_remainder -= 1;
         print!("compare_times_remainder:{}", compare_times_remainder);
 
-        // 执行循环校验
+        // 执行循环校验，每轮复查上一轮校验失败的 key
+        let mut round = FIRST_ROUND;
         loop {
             if compare_times_remainder <= 0 {
-                return;
+                break;
             }
             // Todo 增加错误处理逻辑
             println!("执行循环校验");
-            // 获取上次校验结果的目录
-            let last_result_dir = match fs::read_to_string(COMPARE_STATUS_FILE_NAME) {
-                Ok(dir) => dir,
+            let last_fail_keys = match result_store.load(round) {
+                Ok(fks) => fks,
                 Err(e) => {
                     log::error!("{}", e);
-                    return;
+                    break;
                 }
             };
-            // 创建存储当前结果目录
-            let current_dir = create_result_dir().unwrap();
-            // 遍历目录，反序列化结果文件并重新校验,校验结果写入新的结果目录
-            let entries = match fs::read_dir(last_result_dir.as_str()) {
-                Ok(dir) => dir,
-                Err(e) => {
-                    log::error!("{}", e);
-                    return;
-                }
-            };
-            for e in entries {
-                if let Ok(de) = e {
-                    if let Ok(m) = de.metadata() {
-                        if m.is_file() {
-                            let name = match de.file_name().into_string() {
-                                Ok(s) => s,
-                                Err(_) => {
-                                    log::error!("convert OsString to String error");
-
-                                    continue;
-                                }
-                            };
-
-                            let path = last_result_dir.clone() + "/" + &name;
-                            let fk = match compare_from_file(path.as_str()) {
-                                Ok(fk) => fk,
-                                Err(e) => {
-                                    log::error!("{}", e);
-                                    // 复制文件到当前目录
-                                    continue;
-                                }
-                            };
-                            if !fk.iffy_keys.is_empty() {
-                                log::error!("{:?}", fk);
-                                if let Err(e) = fk.write_to_file(&current_dir) {
-                                    log::error!("{}", e);
-                                };
-                            }
-                        }
+            round += 1;
+            if let Err(e) = result_store.begin_round(round) {
+                log::error!("{}", e);
+                break;
+            }
+            for fk in last_fail_keys {
+                let fk = match fk.recheck() {
+                    Ok(fk) => fk,
+                    Err(e) => {
+                        log::error!("{}", e);
+                        continue;
                     }
                 };
+                if !fk.iffy_keys.is_empty() {
+                    log::error!("{:?}", fk);
+                    if let Err(e) = result_store.save(round, &fk) {
+                        log::error!("{}", e);
+                    };
+                }
             }
 
-            // 清理上次校验生成的结果目录
-            fs::remove_dir_all(last_result_dir).unwrap();
+            // 清理上一轮的结果
+            if let Err(e) = result_store.discard(round - 1) {
+                log::error!("{}", e);
+            }
             compare_times_remainder -= 1;
             print!("compare_times_remainder:{}", compare_times_remainder);
         }
+
+        if let Err(e) = result_store.finish() {
+            log::error!("{}", e);
+        }
     }
 }
 
 impl Compare {
+    // 按本配置的校验参数生成 DB 对的校验结果
+    pub(crate) fn fail_keys(
+        &self,
+        source: RedisInstanceWithDB,
+        target: RedisInstanceWithDB,
+        iffy_keys: Vec<IffyKey>,
+    ) -> FailKeys {
+        FailKeys {
+            source: vec![source],
+            target,
+            iffy_keys,
+            ttl_diff: self.ttl_diff,
+            batch: self.batch_size,
+            reverse: false,
+            options: self.options.clone(),
+        }
+    }
+
+    // AOF、RDB、快照等离线比较的结果写入结果存储，与 key 校验使用相同的报告格式，返回运行 ID
+    pub fn save_fail_keys(&self, fail_keys: &[FailKeys]) -> Result<String> {
+        let store = self.result_store.open()?;
+        store.begin_round(FIRST_ROUND)?;
+        for fk in fail_keys {
+            if !fk.iffy_keys.is_empty() {
+                store.save(FIRST_ROUND, fk)?;
+            }
+        }
+        store.finish()?;
+        Ok(store.run_id())
+    }
+
     // source to target DBInstance 映射
-    fn map_dbinstance_source_to_target(
+    pub(crate) fn map_dbinstance_source_to_target(
         &self,
     ) -> Result<HashMap<RedisInstanceWithDB, RedisInstanceWithDB>> {
         let mut s_t_map: HashMap<RedisInstanceWithDB, RedisInstanceWithDB> = HashMap::new();
 
         for si in &self.source {
             for (s, t) in &si.dbmapper {
-                // 判断 target 是否为 cluster，cluster 不支持非 0 DB
+                // 判断 target 是否为 cluster，cluster 不支持非 0 DB，可通过 prefix 区分 source db
                 if let InstanceType::Cluster = self.target.instance_type {
-                    if !t.eq(&0) {
+                    if !t.db().eq(&0) {
                         return Err(anyhow!("target is cluster db must be 0"));
                     }
                 };
                 let s_instance_with_db = RedisInstanceWithDB {
                     instance: si.instance.clone(),
                     db: *s,
+                    key_mapping: si.key_mapping_for(t),
+                    rdb_file: si.rdb_file.clone(),
                 };
 
                 let t_instance_with_db = RedisInstanceWithDB {
                     instance: self.target.clone(),
-                    db: *t,
+                    db: t.db(),
+                    key_mapping: vec![],
+                    rdb_file: "".to_string(),
                 };
 
                 s_t_map.insert(s_instance_with_db, t_instance_with_db);
@@ -539,31 +1051,35 @@ impl Compare {
         &self,
     ) -> Result<HashMap<RedisInstanceWithDB, Vec<RedisInstanceWithDB>>> {
         return match self.target.instance_type {
-            InstanceType::Single => {
+            InstanceType::Single | InstanceType::Sentinel => {
                 let mut dbinstance_map = HashMap::new();
                 let mut t_db_to_s_instance_map: HashMap<usize, Vec<RedisInstanceWithDB>> =
                     HashMap::new();
                 // 遍历 source 中 dbmapper 生成 target db 与 source DBInstance 的 映射关系
                 for s_instance in &self.source {
                     for (s, t) in &s_instance.dbmapper {
-                        match t_db_to_s_instance_map.get(&t) {
+                        match t_db_to_s_instance_map.get(&t.db()) {
                             Some(vec_dbinstance) => {
                                 let s_dbinstance = RedisInstanceWithDB {
                                     instance: s_instance.instance.clone(),
                                     db: s.clone(),
+                                    key_mapping: s_instance.key_mapping_for(t),
+                                    rdb_file: s_instance.rdb_file.clone(),
                                 };
                                 let mut vec = vec_dbinstance.clone();
                                 vec.push(s_dbinstance);
-                                t_db_to_s_instance_map.insert(*t, vec);
+                                t_db_to_s_instance_map.insert(t.db(), vec);
                             }
                             None => {
                                 let mut vec = vec![];
                                 let s_dbinstance = RedisInstanceWithDB {
                                     instance: s_instance.instance.clone(),
                                     db: s.clone(),
+                                    key_mapping: s_instance.key_mapping_for(t),
+                                    rdb_file: s_instance.rdb_file.clone(),
                                 };
                                 vec.push(s_dbinstance);
-                                t_db_to_s_instance_map.insert(*t, vec);
+                                t_db_to_s_instance_map.insert(t.db(), vec);
                             }
                         };
                     }
@@ -573,6 +1089,8 @@ impl Compare {
                     let t_dbinstance = RedisInstanceWithDB {
                         instance: self.target.clone(),
                         db: k,
+                        key_mapping: vec![],
+                        rdb_file: "".to_string(),
                     };
 
                     dbinstance_map.insert(t_dbinstance, v);
@@ -586,19 +1104,23 @@ impl Compare {
                 let t_dbinstance = RedisInstanceWithDB {
                     instance: self.target.clone(),
                     db: 0,
+                    key_mapping: vec![],
+                    rdb_file: "".to_string(),
                 };
                 for instance in &self.source {
                     for (k, v) in &instance.dbmapper {
-                        if !v.eq(&0) {
+                        if !v.db().eq(&0) {
                             return Err(anyhow!(
                                 "target instance_type is cluster,no db number {} exists",
-                                v
+                                v.db()
                             ));
                         }
 
                         let s_dbinstance = RedisInstanceWithDB {
                             instance: instance.instance.clone(),
                             db: k.clone(),
+                            key_mapping: instance.key_mapping_for(v),
+                            rdb_file: instance.rdb_file.clone(),
                         };
                         source_dbinstances.push(s_dbinstance);
                     }
@@ -610,40 +1132,113 @@ impl Compare {
     }
 }
 
-// 创建结果文件目录
-fn create_result_dir() -> Result<String> {
-    let dt = Local::now();
-    let timestampe = dt.timestamp();
-    let dir = "cr_".to_owned()
-        + &timestampe.to_string()
-        + &"_".to_string()
-        + &rand_lettter_number_string(4);
-    create_dir(dir.clone())?;
-    // ".current_compare" 记录结果目录名称，便于后续处理
-    let mut file = OpenOptions::new()
-        .create(true)
-        .write(true)
-        .truncate(true)
-        .open(COMPARE_STATUS_FILE_NAME)?;
-    file.write_all(dir.as_bytes())?;
-    Ok(dir)
-}
-
-fn remove_result_dir() -> Result<()> {
-    let dir_name = fs::read_to_string(COMPARE_STATUS_FILE_NAME)?;
-    remove_dir_all(dir_name)?;
+// 大 key 按比较耗时降序写入本次运行的结果存储，多次运行的报告互不覆盖
+fn write_big_keys_report(store: &dyn ResultStore, big_keys: &Mutex<Vec<BigKey>>) -> Result<()> {
+    let mut report = big_keys.lock().map_err(|e| anyhow!("{}", e))?.clone();
+    if report.is_empty() {
+        return Ok(());
+    }
+    report.sort_by(|a, b| b.duration_ms.cmp(&a.duration_ms));
+    let location = store.save_report(
+        BIG_KEYS_REPORT_FILE_NAME,
+        serde_json::to_string_pretty(&report)?.as_bytes(),
+    )?;
+    println!("big keys: {}, report: {}", report.len(), location);
     Ok(())
 }
 
-fn write_result_file(path: &str, buf: &Vec<u8>) -> Result<()> {
-    let dt = Local::now();
-    let timestampe = dt.timestamp_nanos();
-    let file_name = path.to_owned() + "/" + &timestampe.to_string() + "_" + &rand_string(4) + ".cr";
-    let mut file = OpenOptions::new()
-        .create(true)
-        .truncate(true)
-        .write(true)
-        .open(file_name)?;
-    file.write_all(buf)?;
-    Ok(())
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    //cargo test compare::rediscompare::test::test_connection_info --  --nocapture
+    #[test]
+    fn test_connection_info() {
+        let mut instance = RedisInstance::default();
+        instance.username = "acl_user".to_string();
+        instance.password = "p@ss//word".to_string();
+
+        let info = instance
+            .connection_info("redis://:old@127.0.0.1:6379/2")
+            .unwrap();
+        assert_eq!(info.redis.username, Some("acl_user".to_string()));
+        assert_eq!(info.redis.password, Some("p@ss//word".to_string()));
+        assert_eq!(info.redis.db, 2);
+
+        instance.tls.enable = true;
+        instance.tls.insecure_skip_verify = true;
+        let info = instance.connection_info("10.0.0.1:7000").unwrap();
+        match info.addr {
+            ConnectionAddr::TcpTls {
+                host,
+                port,
+                insecure,
+                ..
+            } => {
+                assert_eq!(host, "10.0.0.1");
+                assert_eq!(port, 7000);
+                assert!(insecure);
+            }
+            _ => panic!("expect tls address"),
+        }
+
+        let info = RedisInstance::default()
+            .connection_info("rediss://127.0.0.1:6380")
+            .unwrap();
+        assert!(RedisInstance::is_tls(&info));
+    }
+
+    //cargo test compare::rediscompare::test::test_master_name_default --  --nocapture
+    #[test]
+    fn test_master_name_default() {
+        // 配置中省略的 master_name 与 RedisInstance::default() 一致
+        let instance: RedisInstance = serde_yaml::from_str("instance_type: sentinel").unwrap();
+        assert_eq!(instance.master_name, RedisInstance::default().master_name);
+        assert_eq!(
+            RedisInstance::default_sentinel().master_name,
+            RedisInstance::master_name_default()
+        );
+    }
+
+    //cargo test compare::rediscompare::test::test_batch_size --  --nocapture
+    #[test]
+    fn test_batch_size() {
+        let compare: Compare = serde_yaml::from_str("ttl_diff: 2").unwrap();
+        assert_eq!(compare.batch_size, 10);
+        let r = serde_yaml::from_str::<Compare>("batch_size: 0");
+        assert!(r.unwrap_err().to_string().contains("batch_size"));
+    }
+
+    //cargo test compare::rediscompare::test::test_dbmapper_prefix --  --nocapture
+    #[test]
+    fn test_dbmapper_prefix() {
+        let yml = r#"
+source:
+  - instance:
+      urls: ["redis://127.0.0.1:6379"]
+    dbmapper:
+      0: 0
+      3: {db: 0, prefix: "db3:"}
+target:
+  urls: ["redis://127.0.0.1:16379"]
+  instance_type: cluster
+"#;
+        let compare: Compare = serde_yaml::from_str(yml).unwrap();
+        let map = compare.map_dbinstance_source_to_target().unwrap();
+        for (s, t) in &map {
+            assert_eq!(t.db, 0);
+            let mapper = s.key_mapper().unwrap();
+            match s.db {
+                3 => {
+                    assert_eq!(mapper.to_target("k1"), "db3:k1");
+                    assert_eq!(mapper.to_source("db3:k1"), vec!["k1"]);
+                    assert!(mapper.to_source("k1").is_empty());
+                }
+                _ => assert_eq!(mapper.to_target("k1"), "k1"),
+            }
+        }
+
+        let reverse = compare.map_dbinstance_target_to_source().unwrap();
+        assert_eq!(reverse.values().next().unwrap().len(), 2);
+    }
 }
@@ -0,0 +1,364 @@
+use std::fs::OpenOptions;
+use std::io::{BufWriter, Write};
+
+use anyhow::{anyhow, Result};
+use redis::{ConnectionLike, RedisResult};
+use serde::{Deserialize, Serialize};
+
+use crate::rdb::load_db;
+use crate::util::{format_score, now_ms, MemValue};
+
+use super::{compare_db::FailKeys, compare_snapshot::read_key};
+
+// 每条写入命令包含的元素数量，避免单条命令过大
+const REPAIR_CMD_ELEMENTS: usize = 500;
+
+// 修复脚本格式：redis-cli 可执行的文本命令，或 redis-cli --pipe 使用的 RESP 协议
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ScriptFormat {
+    Text,
+    Resp,
+}
+
+// 单个 key 的修复动作，key 为 target 中的 key 名
+#[derive(Debug, Clone, PartialEq)]
+pub enum RepairAction {
+    // source 中不存在的 key
+    Delete {
+        key: Vec<u8>,
+    },
+    // 以 source 的值与过期时间重写
+    Rewrite {
+        key: Vec<u8>,
+        value: MemValue,
+        expire_at_ms: Option<i64>,
+    },
+    // 模块等无法按值重写的类型，以 DUMP 序列化值 RESTORE
+    Restore {
+        key: Vec<u8>,
+        payload: Vec<u8>,
+        expire_at_ms: Option<i64>,
+    },
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct RepairSummary {
+    pub deleted: usize,
+    pub rewritten: usize,
+    pub restored: usize,
+}
+
+fn cmd(parts: &[&[u8]]) -> Vec<Vec<u8>> {
+    parts.iter().map(|p| p.to_vec()).collect()
+}
+
+// 元素按 REPAIR_CMD_ELEMENTS 分批，每批生成一条 <name> key <args...> 命令
+fn batch_cmds(name: &str, key: &[u8], args: Vec<Vec<Vec<u8>>>) -> Vec<Vec<Vec<u8>>> {
+    args.chunks(REPAIR_CMD_ELEMENTS)
+        .map(|chunk| {
+            let mut c = cmd(&[name.as_bytes(), key]);
+            c.extend(chunk.iter().flatten().cloned());
+            c
+        })
+        .collect()
+}
+
+impl RepairAction {
+    // 修复命令，每条命令为参数列表
+    pub fn commands(&self) -> Vec<Vec<Vec<u8>>> {
+        match self {
+            RepairAction::Delete { key } => vec![cmd(&[b"DEL", key])],
+            RepairAction::Rewrite {
+                key,
+                value,
+                expire_at_ms,
+            } => {
+                let mut cmds = vec![cmd(&[b"DEL", key])];
+                match value {
+                    MemValue::String(s) => cmds.push(cmd(&[b"SET", key, s])),
+                    MemValue::List(l) => {
+                        let args = l.iter().map(|v| vec![v.clone()]).collect();
+                        cmds.append(&mut batch_cmds("RPUSH", key, args));
+                    }
+                    MemValue::Set(s) => {
+                        let args = s.iter().map(|m| vec![m.clone()]).collect();
+                        cmds.append(&mut batch_cmds("SADD", key, args));
+                    }
+                    MemValue::ZSet(z) => {
+                        let args = z
+                            .iter()
+                            .map(|(m, s)| vec![format_score(*s).into_bytes(), m.clone()])
+                            .collect();
+                        cmds.append(&mut batch_cmds("ZADD", key, args));
+                    }
+                    MemValue::Hash(h) => {
+                        let args = h.iter().map(|(f, v)| vec![f.clone(), v.clone()]).collect();
+                        cmds.append(&mut batch_cmds("HSET", key, args));
+                    }
+                }
+                if let Some(at) = expire_at_ms {
+                    cmds.push(cmd(&[b"PEXPIREAT", key, at.to_string().as_bytes()]));
+                }
+                cmds
+            }
+            RepairAction::Restore {
+                key,
+                payload,
+                expire_at_ms,
+            } => {
+                let ttl = expire_at_ms.unwrap_or(0).to_string();
+                vec![cmd(&[
+                    b"RESTORE",
+                    key,
+                    ttl.as_bytes(),
+                    payload,
+                    b"REPLACE",
+                    b"ABSTTL",
+                ])]
+            }
+        }
+    }
+}
+
+// RESP 数组编码，与 redis-cli --pipe 的输入格式一致
+fn encode_resp(args: &[Vec<u8>]) -> Vec<u8> {
+    let mut buf = format!("*{}\r\n", args.len()).into_bytes();
+    for a in args {
+        buf.extend_from_slice(format!("${}\r\n", a.len()).as_bytes());
+        buf.extend_from_slice(a);
+        buf.extend_from_slice(b"\r\n");
+    }
+    buf
+}
+
+// redis-cli 文本命令，含空白、引号或不可打印字符的参数以双引号转义
+fn encode_text(args: &[Vec<u8>]) -> Vec<u8> {
+    let mut line = vec![];
+    for (i, a) in args.iter().enumerate() {
+        if i > 0 {
+            line.push(b' ');
+        }
+        let plain = !a.is_empty()
+            && a.iter()
+                .all(|b| b.is_ascii_graphic() && !matches!(b, b'"' | b'\'' | b'\\'));
+        if plain {
+            line.extend_from_slice(a);
+            continue;
+        }
+        line.push(b'"');
+        for b in a {
+            match b {
+                b'"' => line.extend_from_slice(b"\\\""),
+                b'\\' => line.extend_from_slice(b"\\\\"),
+                b'\n' => line.extend_from_slice(b"\\n"),
+                b'\r' => line.extend_from_slice(b"\\r"),
+                b'\t' => line.extend_from_slice(b"\\t"),
+                b' ' => line.push(b' '),
+                b if b.is_ascii_graphic() => line.push(*b),
+                b => line.extend_from_slice(format!("\\x{:02x}", b).as_bytes()),
+            }
+        }
+        line.push(b'"');
+    }
+    line.push(b'\n');
+    line
+}
+
+fn encode(format: ScriptFormat, args: &[Vec<u8>]) -> Vec<u8> {
+    match format {
+        ScriptFormat::Text => encode_text(args),
+        ScriptFormat::Resp => encode_resp(args),
+    }
+}
+
+// 读取 source key 当前的值，生成 target_key 的重写动作，source key 不存在时返回 None
+fn fetch_action(
+    key: &[u8],
+    target_key: &[u8],
+    conn: &mut dyn ConnectionLike,
+) -> RedisResult<Option<RepairAction>> {
+    let key_type: String = redis::cmd("type").arg(key).query(conn)?;
+    match key_type.as_str() {
+        "none" => Ok(None),
+        "string" | "list" | "set" | "zset" | "hash" => Ok(read_key(key, conn)?.map(
+            |(value, expire_at_ms)| RepairAction::Rewrite {
+                key: target_key.to_vec(),
+                value,
+                expire_at_ms,
+            },
+        )),
+        _ => {
+            let payload: Option<Vec<u8>> = redis::cmd("dump").arg(key).query(conn)?;
+            let pttl: i64 = redis::cmd("pttl").arg(key).query(conn)?;
+            Ok(payload.map(|payload| RepairAction::Restore {
+                key: target_key.to_vec(),
+                payload,
+                expire_at_ms: (pttl >= 0).then(|| now_ms() + pttl),
+            }))
+        }
+    }
+}
+
+// 按 source 当前的数据生成 DB 对中校验失败 key 的修复动作
+fn repair_actions(fk: &FailKeys) -> Result<Vec<RepairAction>> {
+    let mut actions = vec![];
+    if fk.reverse {
+        // 反向校验的 key 为 target key，逐个 source 查找对应的 source key
+        let mut sources = vec![];
+        for s in &fk.source {
+            let conn = s
+                .to_redis_client_with_db()?
+                .get_redis_connection()?
+                .get_dyn_connection();
+            sources.push((conn, s.key_mapper()?));
+        }
+        for iffy in &fk.iffy_keys {
+            let t_key = iffy.key.key_name.as_bytes();
+            let mut action = None;
+            for (conn, mapper) in sources.iter_mut() {
+                for s_key in mapper.to_source(&iffy.key.key_name) {
+                    action = fetch_action(s_key.as_bytes(), t_key, conn.as_mut())?;
+                    if action.is_some() {
+                        break;
+                    }
+                }
+                if action.is_some() {
+                    break;
+                }
+            }
+            actions.push(action.unwrap_or(RepairAction::Delete {
+                key: t_key.to_vec(),
+            }));
+        }
+        return Ok(actions);
+    }
+
+    let source = fk
+        .source
+        .first()
+        .ok_or_else(|| anyhow!("fail keys without source"))?;
+    let mapper = source.key_mapper()?;
+    let mut conn: Box<dyn ConnectionLike> = match source.rdb_file.is_empty() {
+        true => source
+            .to_redis_client_with_db()?
+            .get_redis_connection()?
+            .get_dyn_connection(),
+        false => {
+            let names = fk
+                .iffy_keys
+                .iter()
+                .map(|i| i.key.key_name.clone())
+                .collect();
+            Box::new(load_db(&source.rdb_file, source.db, &names)?)
+        }
+    };
+    for iffy in &fk.iffy_keys {
+        let t_key = mapper.to_target(&iffy.key.key_name);
+        let action = fetch_action(
+            iffy.key.key_name.as_bytes(),
+            t_key.as_bytes(),
+            conn.as_mut(),
+        )?;
+        actions.push(action.unwrap_or(RepairAction::Delete {
+            key: t_key.into_bytes(),
+        }));
+    }
+    Ok(actions)
+}
+
+// 导出使 target 与 source 一致的修复脚本，值在导出时从 source 读取
+// 每个 DB 对以 SELECT 切换到 target DB，cluster target 需按 slot 分别在各节点执行
+pub fn export_repair_script(
+    fail_keys: &[FailKeys],
+    path: &str,
+    format: ScriptFormat,
+) -> Result<RepairSummary> {
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(true)
+        .write(true)
+        .open(path)?;
+    let mut writer = BufWriter::new(file);
+    let mut summary = RepairSummary::default();
+    for fk in fail_keys {
+        if fk.iffy_keys.is_empty() {
+            continue;
+        }
+        let db = fk.target.db.to_string();
+        writer.write_all(&encode(format, &cmd(&[b"SELECT", db.as_bytes()])))?;
+        for action in repair_actions(fk)? {
+            match action {
+                RepairAction::Delete { .. } => summary.deleted += 1,
+                RepairAction::Rewrite { .. } => summary.rewritten += 1,
+                RepairAction::Restore { .. } => summary.restored += 1,
+            }
+            for c in action.commands() {
+                writer.write_all(&encode(format, &c))?;
+            }
+        }
+    }
+    writer.flush()?;
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::util::MemoryConnection;
+
+    //cargo test compare::repair_script::test::test_repair_commands --  --nocapture
+    #[test]
+    fn test_repair_commands() {
+        let mut conn = MemoryConnection::default();
+        conn.insert(
+            b"list".to_vec(),
+            MemValue::List(
+                (0..REPAIR_CMD_ELEMENTS + 1)
+                    .map(|i| vec![i as u8])
+                    .collect(),
+            ),
+            Some(now_ms() + 60_000),
+        );
+        conn.insert(
+            b"zset".to_vec(),
+            MemValue::ZSet([(b"m".to_vec(), f64::INFINITY)].into()),
+            None,
+        );
+
+        let action = fetch_action(b"list", b"t:list", &mut conn)
+            .unwrap()
+            .unwrap();
+        let cmds = action.commands();
+        let names: Vec<String> = cmds
+            .iter()
+            .map(|c| String::from_utf8_lossy(&c[0]).to_string())
+            .collect();
+        assert_eq!(names, vec!["DEL", "RPUSH", "RPUSH", "PEXPIREAT"]);
+        assert_eq!(cmds[1].len(), REPAIR_CMD_ELEMENTS + 2);
+        assert_eq!(
+            cmds[2],
+            cmd(&[b"RPUSH", b"t:list", &[REPAIR_CMD_ELEMENTS as u8]])
+        );
+
+        let action = fetch_action(b"zset", b"zset", &mut conn).unwrap().unwrap();
+        assert_eq!(
+            action.commands(),
+            vec![
+                cmd(&[b"DEL", b"zset"]),
+                cmd(&[b"ZADD", b"zset", b"inf", b"m"])
+            ]
+        );
+        assert!(fetch_action(b"none", b"none", &mut conn).unwrap().is_none());
+
+        let args = cmd(&[b"SET", b"a b", b"x\"\n\x01", b""]);
+        assert_eq!(
+            encode_text(&args),
+            b"SET \"a b\" \"x\\\"\\n\\x01\" \"\"\n".to_vec()
+        );
+        assert_eq!(
+            encode_resp(&args),
+            b"*4\r\n$3\r\nSET\r\n$3\r\na b\r\n$4\r\nx\"\n\x01\r\n$0\r\n\r\n".to_vec()
+        );
+    }
+}
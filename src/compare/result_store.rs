@@ -0,0 +1,173 @@
+use std::fs::{self, create_dir, remove_dir_all};
+use std::path::Path;
+use std::sync::Mutex;
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::util::now_ms;
+
+use super::{
+    compare_db::FailKeys,
+    result_store_sqlite::SqliteResultStore,
+    run_registry::{
+        apply_retention, create_run_dir, read_round_dir, round_dir, write_run_info, RunInfo,
+        DEFAULT_RESULTS_ROOT,
+    },
+};
+
+// 首次校验的轮次，之后每次循环校验轮次加 1
+pub const FIRST_ROUND: u32 = 0;
+
+// 校验结果存储，一次 exec 为一次运行，首次校验与每次循环校验各为一轮
+pub trait ResultStore: Send + Sync {
+    // 开始第 round 轮校验
+    fn begin_round(&self, round: u32) -> Result<()>;
+    // 写入第 round 轮校验失败的 key
+    fn save(&self, round: u32, fail_keys: &FailKeys) -> Result<()>;
+    // 读取第 round 轮校验失败的 key，用于下一轮循环校验
+    fn load(&self, round: u32) -> Result<Vec<FailKeys>>;
+    // 第 round 轮结果已复查完毕，目录存储删除该轮目录，SQLite 存储保留作为复查历史
+    fn discard(&self, round: u32) -> Result<()>;
+    // 本次运行的 ID
+    fn run_id(&self) -> String;
+    // 写入本次运行的报告，如大 key 报告，返回报告的位置
+    fn save_report(&self, name: &str, content: &[u8]) -> Result<String>;
+    // 全部轮次结束
+    fn finish(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+// 结果存储配置，默认为 MessagePack 文件目录
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum ResultStoreConfig {
+    // 每次运行在 root 下创建 <run_id> 目录，每轮结果写入其中的 round_<n> 目录
+    Dir {
+        #[serde(default = "ResultStoreConfig::root_default")]
+        root: String,
+        // 保留最近的运行数量，0 为不限制，默认不自动删除运行
+        #[serde(default = "ResultStoreConfig::keep_runs_default")]
+        keep_runs: usize,
+        // 删除多少天前启动的运行，0 为不限制
+        #[serde(default = "ResultStoreConfig::keep_days_default")]
+        keep_days: u64,
+    },
+    // 运行、DB 对、校验失败的 key 及复查历史写入 SQLite 数据库
+    // DB 对包含明文的 password、sentinel_password 及 url 中的密码，供复查与导出修复脚本时连接实例，
+    // 数据库文件创建时仅所有者可读写
+    Sqlite {
+        path: String,
+    },
+}
+
+impl Default for ResultStoreConfig {
+    fn default() -> Self {
+        ResultStoreConfig::Dir {
+            root: ResultStoreConfig::root_default(),
+            keep_runs: ResultStoreConfig::keep_runs_default(),
+            keep_days: ResultStoreConfig::keep_days_default(),
+        }
+    }
+}
+
+impl ResultStoreConfig {
+    fn root_default() -> String {
+        DEFAULT_RESULTS_ROOT.to_string()
+    }
+
+    fn keep_runs_default() -> usize {
+        0
+    }
+
+    fn keep_days_default() -> u64 {
+        0
+    }
+
+    pub fn open(&self) -> Result<Box<dyn ResultStore>> {
+        match self {
+            ResultStoreConfig::Dir {
+                root,
+                keep_runs,
+                keep_days,
+            } => Ok(Box::new(DirResultStore::create_run(
+                root, *keep_runs, *keep_days,
+            )?)),
+            ResultStoreConfig::Sqlite { path } => {
+                Ok(Box::new(SqliteResultStore::create_run(path)?))
+            }
+        }
+    }
+}
+
+// MessagePack 文件目录存储，每个 FailKeys 为一个 .cr 文件
+pub struct DirResultStore {
+    root: String,
+    info: Mutex<RunInfo>,
+}
+
+impl DirResultStore {
+    // 创建新的运行目录，并按保留策略清理旧的运行
+    pub fn create_run(root: &str, keep_runs: usize, keep_days: u64) -> Result<Self> {
+        let info = create_run_dir(root)?;
+        match apply_retention(root, keep_runs, keep_days, &info.id) {
+            Ok(deleted) => {
+                for id in deleted {
+                    log::info!("run {} removed by retention policy", id);
+                }
+            }
+            Err(e) => log::error!("{}", e),
+        }
+        Ok(Self {
+            root: root.to_string(),
+            info: Mutex::new(info),
+        })
+    }
+
+    fn dir(&self, round: u32) -> std::path::PathBuf {
+        round_dir(&self.root, &self.run_id(), round)
+    }
+}
+
+impl ResultStore for DirResultStore {
+    fn begin_round(&self, round: u32) -> Result<()> {
+        let mut info = self.info.lock().map_err(|e| anyhow!("{}", e))?;
+        create_dir(round_dir(&self.root, &info.id, round))?;
+        info.rounds = round + 1;
+        write_run_info(&self.root, &info)
+    }
+
+    fn save(&self, round: u32, fail_keys: &FailKeys) -> Result<()> {
+        fail_keys.write_to_file(&self.dir(round).to_string_lossy())
+    }
+
+    fn load(&self, round: u32) -> Result<Vec<FailKeys>> {
+        read_round_dir(&self.dir(round))
+    }
+
+    fn discard(&self, round: u32) -> Result<()> {
+        remove_dir_all(self.dir(round))?;
+        Ok(())
+    }
+
+    fn run_id(&self) -> String {
+        return match self.info.lock() {
+            Ok(info) => info.id.clone(),
+            Err(e) => e.into_inner().id.clone(),
+        };
+    }
+
+    // 报告写入运行目录 <root>/<run_id>/
+    fn save_report(&self, name: &str, content: &[u8]) -> Result<String> {
+        let path = Path::new(&self.root).join(self.run_id()).join(name);
+        fs::write(&path, content)?;
+        Ok(path.to_string_lossy().to_string())
+    }
+
+    fn finish(&self) -> Result<()> {
+        let mut info = self.info.lock().map_err(|e| anyhow!("{}", e))?;
+        info.finished_at = Some(now_ms());
+        write_run_info(&self.root, &info)
+    }
+}
@@ -0,0 +1,395 @@
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+use anyhow::{anyhow, Result};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+
+use crate::util::now_ms;
+
+use super::{compare_db::FailKeys, comparekey::IffyKey, result_store::ResultStore};
+
+// source、target 以 JSON 保存，包含明文的密码等连接信息，复查与导出修复脚本时用于连接实例
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS runs (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    started_at INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS rounds (
+    run_id INTEGER NOT NULL,
+    round INTEGER NOT NULL,
+    started_at INTEGER NOT NULL,
+    rechecked_at INTEGER,
+    PRIMARY KEY (run_id, round)
+);
+CREATE TABLE IF NOT EXISTS db_pairs (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    run_id INTEGER NOT NULL,
+    source TEXT NOT NULL,
+    target TEXT NOT NULL,
+    reverse INTEGER NOT NULL,
+    ttl_diff INTEGER NOT NULL,
+    batch INTEGER NOT NULL,
+    options TEXT NOT NULL,
+    UNIQUE (run_id, source, target, reverse)
+);
+CREATE TABLE IF NOT EXISTS iffy_keys (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    pair_id INTEGER NOT NULL,
+    round INTEGER NOT NULL,
+    key_name TEXT NOT NULL,
+    key_type TEXT NOT NULL,
+    error_type TEXT NOT NULL,
+    iffy TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS iffy_keys_pair_round ON iffy_keys (pair_id, round);
+CREATE TABLE IF NOT EXISTS reports (
+    run_id INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    content BLOB NOT NULL,
+    PRIMARY KEY (run_id, name)
+);
+";
+
+// 运行概要，iffy_keys 为最后一轮校验失败的 key 数量
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct RunSummary {
+    pub id: i64,
+    // unix 毫秒时间戳
+    pub started_at: i64,
+    pub rounds: u32,
+    pub iffy_keys: usize,
+}
+
+// SQLite 结果存储，每个实例对应 runs 表中的一次运行
+pub struct SqliteResultStore {
+    conn: Mutex<Connection>,
+    run_id: i64,
+}
+
+fn open_db(path: &str) -> Result<Connection> {
+    let conn = Connection::open(path)?;
+    conn.execute_batch(SCHEMA)?;
+    Ok(conn)
+}
+
+// 数据库中保存了连接密码，只允许所有者读写
+#[cfg(unix)]
+fn restrict_permissions(path: &str) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn restrict_permissions(_path: &str) -> Result<()> {
+    Ok(())
+}
+
+impl SqliteResultStore {
+    // 打开或创建数据库，并登记一次新的运行
+    pub fn create_run(path: &str) -> Result<Self> {
+        let conn = open_db(path)?;
+        restrict_permissions(path)?;
+        conn.execute("INSERT INTO runs (started_at) VALUES (?1)", [now_ms()])?;
+        let run_id = conn.last_insert_rowid();
+        Ok(Self {
+            conn: Mutex::new(conn),
+            run_id,
+        })
+    }
+}
+
+impl ResultStore for SqliteResultStore {
+    fn begin_round(&self, round: u32) -> Result<()> {
+        let conn = self.conn.lock().map_err(|e| anyhow!("{}", e))?;
+        conn.execute(
+            "INSERT INTO rounds (run_id, round, started_at) VALUES (?1, ?2, ?3)",
+            params![self.run_id, round, now_ms()],
+        )?;
+        Ok(())
+    }
+
+    fn save(&self, round: u32, fail_keys: &FailKeys) -> Result<()> {
+        let source = serde_json::to_string(&fail_keys.source)?;
+        let target = serde_json::to_string(&fail_keys.target)?;
+        let options = serde_json::to_string(&fail_keys.options)?;
+
+        let mut conn = self.conn.lock().map_err(|e| anyhow!("{}", e))?;
+        let tx = conn.transaction()?;
+        tx.execute(
+            "INSERT OR IGNORE INTO db_pairs (run_id, source, target, reverse, ttl_diff, batch, options)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                self.run_id,
+                source,
+                target,
+                fail_keys.reverse,
+                fail_keys.ttl_diff,
+                fail_keys.batch,
+                options
+            ],
+        )?;
+        let pair_id: i64 = tx.query_row(
+            "SELECT id FROM db_pairs WHERE run_id = ?1 AND source = ?2 AND target = ?3 AND reverse = ?4",
+            params![self.run_id, source, target, fail_keys.reverse],
+            |row| row.get(0),
+        )?;
+        {
+            let mut stmt = tx.prepare(
+                "INSERT INTO iffy_keys (pair_id, round, key_name, key_type, error_type, iffy)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            )?;
+            for iffy in &fail_keys.iffy_keys {
+                stmt.execute(params![
+                    pair_id,
+                    round,
+                    iffy.key.key_name,
+                    iffy.key.key_type.to_string(),
+                    format!("{:?}", iffy.error.error_type),
+                    serde_json::to_string(iffy)?
+                ])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn load(&self, round: u32) -> Result<Vec<FailKeys>> {
+        let conn = self.conn.lock().map_err(|e| anyhow!("{}", e))?;
+        load_round(&conn, self.run_id, round)
+    }
+
+    fn discard(&self, round: u32) -> Result<()> {
+        let conn = self.conn.lock().map_err(|e| anyhow!("{}", e))?;
+        conn.execute(
+            "UPDATE rounds SET rechecked_at = ?1 WHERE run_id = ?2 AND round = ?3",
+            params![now_ms(), self.run_id, round],
+        )?;
+        Ok(())
+    }
+
+    fn run_id(&self) -> String {
+        self.run_id.to_string()
+    }
+
+    // 报告写入 reports 表
+    fn save_report(&self, name: &str, content: &[u8]) -> Result<String> {
+        let conn = self.conn.lock().map_err(|e| anyhow!("{}", e))?;
+        conn.execute(
+            "INSERT OR REPLACE INTO reports (run_id, name, content) VALUES (?1, ?2, ?3)",
+            params![self.run_id, name, content],
+        )?;
+        Ok(format!("reports table, run {} name {}", self.run_id, name))
+    }
+}
+
+// 读取运行中某一轮校验失败的 key，按 DB 对合并为 FailKeys
+fn load_round(conn: &Connection, run_id: i64, round: u32) -> Result<Vec<FailKeys>> {
+    let mut stmt = conn.prepare(
+        "SELECT p.id, p.source, p.target, p.reverse, p.ttl_diff, p.batch, p.options, k.iffy
+         FROM iffy_keys k JOIN db_pairs p ON k.pair_id = p.id
+         WHERE p.run_id = ?1 AND k.round = ?2
+         ORDER BY p.id, k.id",
+    )?;
+    let mut rows = stmt.query(params![run_id, round])?;
+    let mut pairs: BTreeMap<i64, FailKeys> = BTreeMap::new();
+    while let Some(row) = rows.next()? {
+        let pair_id: i64 = row.get(0)?;
+        let iffy: IffyKey = serde_json::from_str(&row.get::<_, String>(7)?)?;
+        if let Some(fk) = pairs.get_mut(&pair_id) {
+            fk.iffy_keys.push(iffy);
+            continue;
+        }
+        let fk = FailKeys {
+            source: serde_json::from_str(&row.get::<_, String>(1)?)?,
+            target: serde_json::from_str(&row.get::<_, String>(2)?)?,
+            iffy_keys: vec![iffy],
+            ttl_diff: row.get(4)?,
+            batch: row.get(5)?,
+            reverse: row.get(3)?,
+            options: serde_json::from_str(&row.get::<_, String>(6)?)?,
+        };
+        pairs.insert(pair_id, fk);
+    }
+    Ok(pairs.into_values().collect())
+}
+
+// 数据库中的全部运行
+pub fn list_runs(path: &str) -> Result<Vec<RunSummary>> {
+    let conn = open_db(path)?;
+    let mut stmt = conn.prepare(
+        "SELECT r.id, r.started_at, COUNT(d.round), MAX(d.round)
+         FROM runs r LEFT JOIN rounds d ON d.run_id = r.id
+         GROUP BY r.id ORDER BY r.id",
+    )?;
+    let runs: Vec<(i64, i64, u32, Option<u32>)> = stmt
+        .query_map([], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+        })?
+        .collect::<rusqlite::Result<_>>()?;
+
+    let mut result = vec![];
+    for (id, started_at, rounds, last_round) in runs {
+        let iffy_keys: usize = match last_round {
+            Some(round) => conn.query_row(
+                "SELECT COUNT(*) FROM iffy_keys k JOIN db_pairs p ON k.pair_id = p.id
+                 WHERE p.run_id = ?1 AND k.round = ?2",
+                params![id, round],
+                |row| row.get(0),
+            )?,
+            None => 0,
+        };
+        result.push(RunSummary {
+            id,
+            started_at,
+            rounds,
+            iffy_keys,
+        });
+    }
+    Ok(result)
+}
+
+// 读取运行中某一轮校验失败的 key，round 为 None 时读取最后一轮
+pub fn load_run(path: &str, run_id: i64, round: Option<u32>) -> Result<Vec<FailKeys>> {
+    let conn = open_db(path)?;
+    let round = match round {
+        Some(r) => r,
+        None => conn
+            .query_row(
+                "SELECT MAX(round) FROM rounds WHERE run_id = ?1",
+                [run_id],
+                |row| row.get::<_, Option<u32>>(0),
+            )
+            .optional()?
+            .flatten()
+            .ok_or_else(|| anyhow!("run {} not found", run_id))?,
+    };
+    load_round(&conn, run_id, round)
+}
+
+// 删除运行及其全部轮次、DB 对、校验失败的 key 与报告
+pub fn delete_run(path: &str, run_id: i64) -> Result<()> {
+    let mut conn = open_db(path)?;
+    let tx = conn.transaction()?;
+    tx.execute(
+        "DELETE FROM iffy_keys WHERE pair_id IN (SELECT id FROM db_pairs WHERE run_id = ?1)",
+        [run_id],
+    )?;
+    tx.execute("DELETE FROM db_pairs WHERE run_id = ?1", [run_id])?;
+    tx.execute("DELETE FROM rounds WHERE run_id = ?1", [run_id])?;
+    tx.execute("DELETE FROM reports WHERE run_id = ?1", [run_id])?;
+    if tx.execute("DELETE FROM runs WHERE id = ?1", [run_id])? == 0 {
+        return Err(anyhow!("run {} not found", run_id));
+    }
+    tx.commit()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::compare::compare_error::CompareErrorType;
+    use crate::compare::rediscompare::RedisInstanceWithDB;
+    use crate::compare::{CompareError, CompareOptions};
+    use crate::util::{RedisKey, RedisKeyType};
+
+    fn fail_keys(db: usize, keys: &[&str]) -> FailKeys {
+        let source = RedisInstanceWithDB {
+            db,
+            ..Default::default()
+        };
+        FailKeys {
+            source: vec![source.clone()],
+            target: source,
+            iffy_keys: keys
+                .iter()
+                .map(|k| IffyKey {
+                    key: RedisKey {
+                        key_name: k.to_string(),
+                        key_type: RedisKeyType::TypeString,
+                    },
+                    error: CompareError::from_str("diff", CompareErrorType::StringValueNotEqual),
+                    diff: None,
+                })
+                .collect(),
+            ttl_diff: 2,
+            batch: 10,
+            reverse: false,
+            options: CompareOptions::default(),
+        }
+    }
+
+    //cargo test compare::result_store_sqlite::test::test_sqlite_result_store --  --nocapture
+    #[test]
+    fn test_sqlite_result_store() {
+        let path = std::env::temp_dir().join(format!("rc_store_{}.db", now_ms()));
+        let path = path.to_string_lossy().to_string();
+
+        let store = SqliteResultStore::create_run(&path).unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        store.begin_round(0).unwrap();
+        store.save(0, &fail_keys(0, &["a", "b"])).unwrap();
+        store.save(0, &fail_keys(0, &["c"])).unwrap();
+        store.save(0, &fail_keys(1, &["d"])).unwrap();
+
+        let loaded = store.load(0).unwrap();
+        assert_eq!(loaded.len(), 2);
+        let names: Vec<&str> = loaded[0]
+            .iffy_keys
+            .iter()
+            .map(|i| i.key.key_name.as_str())
+            .collect();
+        assert_eq!(names, vec!["a", "b", "c"]);
+        assert_eq!(loaded[1].source[0].db, 1);
+
+        store.discard(0).unwrap();
+        store.begin_round(1).unwrap();
+        store.save(1, &fail_keys(0, &["b"])).unwrap();
+        store.save_report("report.json", b"[]").unwrap();
+        store.save_report("report.json", b"[1]").unwrap();
+
+        let second = SqliteResultStore::create_run(&path).unwrap();
+        second.begin_round(0).unwrap();
+
+        let runs = list_runs(&path).unwrap();
+        assert_eq!(runs.len(), 2);
+        assert_eq!((runs[0].rounds, runs[0].iffy_keys), (2, 1));
+        assert_eq!((runs[1].rounds, runs[1].iffy_keys), (1, 0));
+        assert_eq!(load_run(&path, runs[0].id, Some(0)).unwrap().len(), 2);
+        assert_eq!(
+            load_run(&path, runs[0].id, None).unwrap()[0]
+                .iffy_keys
+                .len(),
+            1
+        );
+        assert!(load_run(&path, 100, None).is_err());
+
+        delete_run(&path, runs[0].id).unwrap();
+        assert!(delete_run(&path, runs[0].id).is_err());
+        let runs = list_runs(&path).unwrap();
+        assert_eq!(runs.len(), 1);
+        let conn = open_db(&path).unwrap();
+        for table in ["rounds", "db_pairs", "reports"] {
+            let n: i64 = conn
+                .query_row(
+                    &format!("SELECT COUNT(*) FROM {} WHERE run_id != ?1", table),
+                    [runs[0].id],
+                    |row| row.get(0),
+                )
+                .unwrap();
+            assert_eq!(n, 0, "{}", table);
+        }
+        let n: i64 = conn
+            .query_row("SELECT COUNT(*) FROM iffy_keys", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(n, 0);
+
+        let _ = std::fs::remove_file(path);
+    }
+}
@@ -0,0 +1,275 @@
+use std::fs::{self, create_dir, create_dir_all, remove_dir_all};
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Result};
+use chrono::prelude::Local;
+use serde::{Deserialize, Serialize};
+
+use crate::util::{now_ms, rand_lettter_number_string};
+
+use super::compare_db::FailKeys;
+
+// 目录存储的默认结果根目录
+pub const DEFAULT_RESULTS_ROOT: &str = "compare_results";
+// 运行目录中记录运行信息的文件
+const RUN_INFO_FILE_NAME: &str = "run.json";
+const DAY_MS: i64 = 24 * 3600 * 1000;
+// 运行 ID 冲突时重新生成的次数
+const RUN_ID_RETRY: usize = 16;
+
+// 运行信息，保存在 <root>/<id>/run.json
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct RunInfo {
+    pub id: String,
+    // unix 毫秒时间戳
+    pub started_at: i64,
+    pub pid: u32,
+    // 已开始的轮数，最后一轮为 rounds - 1
+    pub rounds: u32,
+    // 运行结束时间，未结束或异常退出时为 None
+    pub finished_at: Option<i64>,
+}
+
+// 运行 ID 由启动时间与随机串组成，按字典序即按时间排序
+fn new_run_id() -> String {
+    format!(
+        "{}_{}",
+        Local::now().format("%Y%m%d%H%M%S"),
+        rand_lettter_number_string(6)
+    )
+}
+
+// 运行 ID 只能是运行目录名，防止删除根目录之外的文件
+fn check_run_id(id: &str) -> Result<()> {
+    if id.is_empty() || id.starts_with('.') || id.contains(['/', '\\']) {
+        return Err(anyhow!("invalid run id {}", id));
+    }
+    Ok(())
+}
+
+pub(crate) fn round_dir(root: &str, id: &str, round: u32) -> PathBuf {
+    Path::new(root).join(id).join(format!("round_{}", round))
+}
+
+pub(crate) fn write_run_info(root: &str, info: &RunInfo) -> Result<()> {
+    let path = Path::new(root).join(&info.id).join(RUN_INFO_FILE_NAME);
+    fs::write(path, serde_json::to_vec_pretty(info)?)?;
+    Ok(())
+}
+
+pub fn read_run_info(root: &str, id: &str) -> Result<RunInfo> {
+    check_run_id(id)?;
+    let path = Path::new(root).join(id).join(RUN_INFO_FILE_NAME);
+    let buf = fs::read(&path).map_err(|e| anyhow!("run {} not found: {}", id, e))?;
+    Ok(serde_json::from_slice(&buf)?)
+}
+
+// 在根目录下创建新的运行目录，目录已存在说明 ID 冲突，重新生成 ID
+pub(crate) fn create_run_dir(root: &str) -> Result<RunInfo> {
+    create_dir_all(root)?;
+    for _ in 0..RUN_ID_RETRY {
+        let id = new_run_id();
+        match create_dir(Path::new(root).join(&id)) {
+            Ok(_) => {
+                let info = RunInfo {
+                    id,
+                    started_at: now_ms(),
+                    pid: std::process::id(),
+                    rounds: 0,
+                    finished_at: None,
+                };
+                write_run_info(root, &info)?;
+                return Ok(info);
+            }
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Err(anyhow!("failed to create run dir in {}", root))
+}
+
+// 读取一轮结果目录中的全部 .cr 文件
+pub(crate) fn read_round_dir(dir: &Path) -> Result<Vec<FailKeys>> {
+    let mut result = vec![];
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if !path.is_file() {
+            continue;
+        }
+        match FailKeys::from_file(&path.to_string_lossy()) {
+            Ok(fk) => result.push(fk),
+            Err(e) => log::error!("{}", e),
+        }
+    }
+    Ok(result)
+}
+
+// 根目录下的全部运行，按启动时间排序，根目录不存在时为空
+pub fn list_run_dirs(root: &str) -> Result<Vec<RunInfo>> {
+    let entries = match fs::read_dir(root) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(e.into()),
+    };
+    let mut runs = vec![];
+    for entry in entries {
+        let entry = entry?;
+        if !entry.path().is_dir() {
+            continue;
+        }
+        let id = entry.file_name().to_string_lossy().to_string();
+        match read_run_info(root, &id) {
+            Ok(info) => runs.push(info),
+            Err(e) => log::warn!("skip {}: {}", entry.path().display(), e),
+        }
+    }
+    runs.sort_by(|a, b| (a.started_at, &a.id).cmp(&(b.started_at, &b.id)));
+    Ok(runs)
+}
+
+// 读取运行中某一轮校验失败的 key，round 为 None 时读取最后一轮
+// 目录存储复查后删除上一轮目录，只有最后一轮可以读取
+pub fn load_run_dir(root: &str, id: &str, round: Option<u32>) -> Result<Vec<FailKeys>> {
+    let info = read_run_info(root, id)?;
+    let round = match round {
+        Some(r) => r,
+        None => info
+            .rounds
+            .checked_sub(1)
+            .ok_or_else(|| anyhow!("run {} has no round", id))?,
+    };
+    let dir = round_dir(root, id, round);
+    if !dir.is_dir() {
+        return Err(anyhow!("round {} of run {} not found", round, id));
+    }
+    read_round_dir(&dir)
+}
+
+pub fn delete_run_dir(root: &str, id: &str) -> Result<()> {
+    // 确认是运行目录后再删除
+    read_run_info(root, id)?;
+    remove_dir_all(Path::new(root).join(id))?;
+    Ok(())
+}
+
+// 保留策略：保留最近 keep_runs 次运行（包含当前运行），删除 keep_days 天前启动的运行，0 为不限制
+// 未结束的运行可能仍在执行，只在过期后删除，返回被删除的运行 ID
+pub fn apply_retention(
+    root: &str,
+    keep_runs: usize,
+    keep_days: u64,
+    current: &str,
+) -> Result<Vec<String>> {
+    let mut runs = list_run_dirs(root)?;
+    runs.retain(|r| r.id != current);
+    let excess = match keep_runs {
+        0 => 0,
+        n => (runs.len() + 1).saturating_sub(n),
+    };
+    let now = now_ms();
+
+    let mut deleted = vec![];
+    for (i, run) in runs.iter().enumerate() {
+        let expired = keep_days > 0 && now - run.started_at > keep_days as i64 * DAY_MS;
+        let over = i < excess && run.finished_at.is_some();
+        if !expired && !over {
+            continue;
+        }
+        remove_dir_all(Path::new(root).join(&run.id))?;
+        deleted.push(run.id.clone());
+    }
+    Ok(deleted)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::compare::compare_error::CompareErrorType;
+    use crate::compare::comparekey::IffyKey;
+    use crate::compare::rediscompare::RedisInstanceWithDB;
+    use crate::compare::result_store::{DirResultStore, ResultStore};
+    use crate::compare::{CompareError, CompareOptions};
+    use crate::util::{RedisKey, RedisKeyType};
+
+    fn fail_keys(keys: &[&str]) -> FailKeys {
+        FailKeys {
+            source: vec![RedisInstanceWithDB::default()],
+            target: RedisInstanceWithDB::default(),
+            iffy_keys: keys
+                .iter()
+                .map(|k| IffyKey {
+                    key: RedisKey {
+                        key_name: k.to_string(),
+                        key_type: RedisKeyType::TypeString,
+                    },
+                    error: CompareError::from_str("diff", CompareErrorType::StringValueNotEqual),
+                    diff: None,
+                })
+                .collect(),
+            ttl_diff: 2,
+            batch: 10,
+            reverse: false,
+            options: CompareOptions::default(),
+        }
+    }
+
+    //cargo test compare::run_registry::test::test_run_registry --  --nocapture
+    #[test]
+    fn test_run_registry() {
+        let root = std::env::temp_dir().join(format!("rc_runs_{}", now_ms()));
+        let root = root.to_string_lossy().to_string();
+
+        let first = DirResultStore::create_run(&root, 0, 0).unwrap();
+        first.begin_round(0).unwrap();
+        first.save(0, &fail_keys(&["a", "b"])).unwrap();
+        first.begin_round(1).unwrap();
+        first.save(1, &fail_keys(&["b"])).unwrap();
+        first.discard(0).unwrap();
+        first.finish().unwrap();
+
+        let first_id = first.run_id();
+        let report = first.save_report("report.json", b"[]").unwrap();
+        assert_eq!(
+            Path::new(&report),
+            Path::new(&root).join(&first_id).join("report.json")
+        );
+        let loaded = load_run_dir(&root, &first_id, None).unwrap();
+        assert_eq!(loaded[0].iffy_keys.len(), 1);
+        assert!(load_run_dir(&root, &first_id, Some(0)).is_err());
+
+        // 第二次运行未结束，不受数量限制影响
+        std::thread::sleep(std::time::Duration::from_millis(2));
+        let second = DirResultStore::create_run(&root, 0, 0).unwrap();
+        second.begin_round(0).unwrap();
+        let runs = list_run_dirs(&root).unwrap();
+        assert_eq!(runs.len(), 2);
+        assert_eq!(runs[0].id, first_id);
+        assert_eq!((runs[0].rounds, runs[0].finished_at.is_some()), (2, true));
+        assert_eq!((runs[1].rounds, runs[1].finished_at.is_some()), (1, false));
+
+        std::thread::sleep(std::time::Duration::from_millis(2));
+        let third = DirResultStore::create_run(&root, 1, 0).unwrap();
+        let ids: Vec<String> = list_run_dirs(&root)
+            .unwrap()
+            .into_iter()
+            .map(|r| r.id)
+            .collect();
+        assert_eq!(ids, vec![second.run_id(), third.run_id()]);
+
+        // 过期的运行即使未结束也会删除
+        let mut info = read_run_info(&root, &second.run_id()).unwrap();
+        info.started_at -= 2 * DAY_MS;
+        write_run_info(&root, &info).unwrap();
+        assert_eq!(
+            apply_retention(&root, 0, 1, &third.run_id()).unwrap(),
+            vec![second.run_id()]
+        );
+
+        assert!(delete_run_dir(&root, "../x").is_err());
+        delete_run_dir(&root, &third.run_id()).unwrap();
+        assert!(list_run_dirs(&root).unwrap().is_empty());
+
+        let _ = remove_dir_all(root);
+    }
+}
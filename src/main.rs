@@ -2,12 +2,14 @@ extern crate core;
 
 use logger::init_log;
 
+mod aof;
 mod cmd;
 mod commons;
 mod compare;
 mod configure;
 mod interact;
 mod logger;
+mod rdb;
 // mod request;
 mod redisdatagen;
 mod util;
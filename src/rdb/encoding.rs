@@ -0,0 +1,306 @@
+use anyhow::{anyhow, Result};
+
+// RDB 中紧凑编码的解析：ziplist、listpack、intset、zipmap 以及 LZF 压缩
+
+fn take<'a>(buf: &'a [u8], pos: &mut usize, len: usize) -> Result<&'a [u8]> {
+    let bytes = buf
+        .get(*pos..*pos + len)
+        .ok_or_else(|| anyhow!("unexpected end of encoded data at {}", *pos))?;
+    *pos += len;
+    Ok(bytes)
+}
+
+fn le_int(bytes: &[u8]) -> i64 {
+    let mut v: u64 = 0;
+    for (i, b) in bytes.iter().enumerate() {
+        v |= (*b as u64) << (8 * i);
+    }
+    // 按字节数做符号扩展
+    let shift = 64 - 8 * bytes.len() as u32;
+    ((v << shift) as i64) >> shift
+}
+
+// ziplist: zlbytes(4) zltail(4) zllen(2) entries... 0xFF
+pub fn ziplist_entries(buf: &[u8]) -> Result<Vec<Vec<u8>>> {
+    let mut pos = 10;
+    let mut entries = vec![];
+    loop {
+        let first = *take(buf, &mut pos, 1)?.first().unwrap();
+        if first == 0xFF {
+            break;
+        }
+        // prevlen
+        if first == 0xFE {
+            take(buf, &mut pos, 4)?;
+        }
+        let enc = *take(buf, &mut pos, 1)?.first().unwrap();
+        let entry = match enc >> 6 {
+            0 => take(buf, &mut pos, (enc & 0x3F) as usize)?.to_vec(),
+            1 => {
+                let next = *take(buf, &mut pos, 1)?.first().unwrap();
+                let len = (((enc & 0x3F) as usize) << 8) | next as usize;
+                take(buf, &mut pos, len)?.to_vec()
+            }
+            2 => {
+                let b = take(buf, &mut pos, 4)?;
+                let len = u32::from_be_bytes([b[0], b[1], b[2], b[3]]) as usize;
+                take(buf, &mut pos, len)?.to_vec()
+            }
+            _ => {
+                let v = match enc {
+                    0xC0 => le_int(take(buf, &mut pos, 2)?),
+                    0xD0 => le_int(take(buf, &mut pos, 4)?),
+                    0xE0 => le_int(take(buf, &mut pos, 8)?),
+                    0xF0 => le_int(take(buf, &mut pos, 3)?),
+                    0xFE => le_int(take(buf, &mut pos, 1)?),
+                    0xF1..=0xFD => (enc & 0x0F) as i64 - 1,
+                    _ => return Err(anyhow!("invalid ziplist encoding {:#x}", enc)),
+                };
+                v.to_string().into_bytes()
+            }
+        };
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+// listpack 元素后的 backlen 占用字节数
+fn backlen_size(len: usize) -> usize {
+    match len {
+        0..=127 => 1,
+        128..=16382 => 2,
+        16383..=2097150 => 3,
+        2097151..=268435454 => 4,
+        _ => 5,
+    }
+}
+
+// listpack: total(4) num(2) entries... 0xFF，每个元素后跟 backlen
+pub fn listpack_entries(buf: &[u8]) -> Result<Vec<Vec<u8>>> {
+    let mut pos = 6;
+    let mut entries = vec![];
+    loop {
+        let start = pos;
+        let enc = *take(buf, &mut pos, 1)?.first().unwrap();
+        if enc == 0xFF {
+            break;
+        }
+        let entry = if enc & 0x80 == 0 {
+            (enc & 0x7F).to_string().into_bytes()
+        } else if enc & 0xC0 == 0x80 {
+            take(buf, &mut pos, (enc & 0x3F) as usize)?.to_vec()
+        } else if enc & 0xE0 == 0xC0 {
+            let next = *take(buf, &mut pos, 1)?.first().unwrap();
+            let mut v = (((enc & 0x1F) as i64) << 8) | next as i64;
+            if v >= 1 << 12 {
+                v -= 1 << 13;
+            }
+            v.to_string().into_bytes()
+        } else if enc & 0xF0 == 0xE0 {
+            let next = *take(buf, &mut pos, 1)?.first().unwrap();
+            let len = (((enc & 0x0F) as usize) << 8) | next as usize;
+            take(buf, &mut pos, len)?.to_vec()
+        } else {
+            match enc {
+                0xF0 => {
+                    let b = take(buf, &mut pos, 4)?;
+                    let len = u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize;
+                    take(buf, &mut pos, len)?.to_vec()
+                }
+                0xF1 => le_int(take(buf, &mut pos, 2)?).to_string().into_bytes(),
+                0xF2 => le_int(take(buf, &mut pos, 3)?).to_string().into_bytes(),
+                0xF3 => le_int(take(buf, &mut pos, 4)?).to_string().into_bytes(),
+                0xF4 => le_int(take(buf, &mut pos, 8)?).to_string().into_bytes(),
+                _ => return Err(anyhow!("invalid listpack encoding {:#x}", enc)),
+            }
+        };
+        let backlen = backlen_size(pos - start);
+        take(buf, &mut pos, backlen)?;
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+// intset: encoding(4) length(4) contents，整数均为小端
+pub fn intset_entries(buf: &[u8]) -> Result<Vec<Vec<u8>>> {
+    let mut pos = 0;
+    let h = take(buf, &mut pos, 8)?;
+    let width = u32::from_le_bytes([h[0], h[1], h[2], h[3]]) as usize;
+    let len = u32::from_le_bytes([h[4], h[5], h[6], h[7]]) as usize;
+    if !matches!(width, 2 | 4 | 8) {
+        return Err(anyhow!("invalid intset encoding {}", width));
+    }
+    let mut entries = vec![];
+    for _ in 0..len {
+        entries.push(le_int(take(buf, &mut pos, width)?).to_string().into_bytes());
+    }
+    Ok(entries)
+}
+
+// zipmap: zmlen(1) (len key len free value)... 0xFF，依次返回 field、value
+pub fn zipmap_entries(buf: &[u8]) -> Result<Vec<Vec<u8>>> {
+    let mut pos = 1;
+    let mut entries = vec![];
+    let read_len = |pos: &mut usize| -> Result<Option<usize>> {
+        let first = *take(buf, pos, 1)?.first().unwrap();
+        match first {
+            0xFF => Ok(None),
+            0xFE => {
+                let b = take(buf, pos, 4)?;
+                Ok(Some(u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize))
+            }
+            l => Ok(Some(l as usize)),
+        }
+    };
+    while let Some(len) = read_len(&mut pos)? {
+        entries.push(take(buf, &mut pos, len)?.to_vec());
+        let len = read_len(&mut pos)?.ok_or_else(|| anyhow!("zipmap value missing"))?;
+        let free = *take(buf, &mut pos, 1)?.first().unwrap() as usize;
+        entries.push(take(buf, &mut pos, len)?.to_vec());
+        take(buf, &mut pos, free)?;
+    }
+    Ok(entries)
+}
+
+// LZF 解压，out_len 为解压后长度
+pub fn lzf_decompress(input: &[u8], out_len: usize) -> Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(out_len);
+    let mut pos = 0;
+    while pos < input.len() {
+        let ctrl = input[pos] as usize;
+        pos += 1;
+        if ctrl < 32 {
+            // 字面量
+            out.extend_from_slice(take(input, &mut pos, ctrl + 1)?);
+            continue;
+        }
+        // 回溯引用
+        let mut len = ctrl >> 5;
+        if len == 7 {
+            len += *take(input, &mut pos, 1)?.first().unwrap() as usize;
+        }
+        let low = *take(input, &mut pos, 1)?.first().unwrap() as usize;
+        let back = ((ctrl & 0x1F) << 8) + low + 1;
+        if back > out.len() {
+            return Err(anyhow!("invalid lzf back reference"));
+        }
+        let from = out.len() - back;
+        for i in 0..len + 2 {
+            out.push(out[from + i]);
+        }
+    }
+    if out.len() != out_len {
+        return Err(anyhow!(
+            "lzf decompressed length {} not equal {}",
+            out.len(),
+            out_len
+        ));
+    }
+    Ok(out)
+}
+
+// LZF 压缩，与 lzf_decompress 对应，无法压缩时输出略大于输入
+pub fn lzf_compress(input: &[u8]) -> Vec<u8> {
+    const HASH_LOG: usize = 14;
+    const MAX_OFFSET: usize = 1 << 13;
+    // 回溯长度上限，长度减 2 后需能以 7 + 1 字节表示
+    const MAX_MATCH: usize = 264;
+    const MAX_LITERAL: usize = 32;
+
+    let flush_literal = |out: &mut Vec<u8>, literal: &[u8]| {
+        for chunk in literal.chunks(MAX_LITERAL) {
+            out.push((chunk.len() - 1) as u8);
+            out.extend_from_slice(chunk);
+        }
+    };
+
+    let mut out = Vec::with_capacity(input.len() + input.len() / 16 + 8);
+    // 保存位置加 1，0 表示空
+    let mut table = vec![0usize; 1 << HASH_LOG];
+    let mut literal_start = 0;
+    let mut i = 0;
+    while i + 2 < input.len() {
+        let v = ((input[i] as u32) << 16) | ((input[i + 1] as u32) << 8) | input[i + 2] as u32;
+        let h = (v.wrapping_mul(2654435761) >> (32 - HASH_LOG)) as usize;
+        let candidate = table[h];
+        table[h] = i + 1;
+        if candidate > 0 {
+            let r = candidate - 1;
+            let offset = i - r - 1;
+            if offset < MAX_OFFSET && input[r..r + 3] == input[i..i + 3] {
+                let max_len = (input.len() - i).min(MAX_MATCH);
+                let mut len = 3;
+                while len < max_len && input[r + len] == input[i + len] {
+                    len += 1;
+                }
+                flush_literal(&mut out, &input[literal_start..i]);
+                let l = len - 2;
+                if l < 7 {
+                    out.push(((offset >> 8) + (l << 5)) as u8);
+                } else {
+                    out.push(((offset >> 8) + (7 << 5)) as u8);
+                    out.push((l - 7) as u8);
+                }
+                out.push(offset as u8);
+                i += len;
+                literal_start = i;
+                continue;
+            }
+        }
+        i += 1;
+    }
+    flush_literal(&mut out, &input[literal_start..]);
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    //cargo test rdb::encoding::test::test_compact_encodings --  --nocapture
+    #[test]
+    fn test_compact_encodings() {
+        // ziplist: "ab", 5 (4 bit 立即数), -2 (int8)
+        let ziplist = [
+            0, 0, 0, 0, 0, 0, 0, 0, 3, 0, 0x00, 0x02, b'a', b'b', 0x04, 0xF6, 0x02, 0xFE, 0xFE,
+            0xFF,
+        ];
+        assert_eq!(
+            ziplist_entries(&ziplist).unwrap(),
+            vec![b"ab".to_vec(), b"5".to_vec(), b"-2".to_vec()]
+        );
+
+        // listpack: 7 (7 bit uint), "xy", -1 (13 bit int)
+        let listpack = [
+            0, 0, 0, 0, 3, 0, 0x07, 0x01, 0x82, b'x', b'y', 0x03, 0xDF, 0xFF, 0x02, 0xFF,
+        ];
+        assert_eq!(
+            listpack_entries(&listpack).unwrap(),
+            vec![b"7".to_vec(), b"xy".to_vec(), b"-1".to_vec()]
+        );
+
+        let intset = [2, 0, 0, 0, 2, 0, 0, 0, 0xFF, 0xFF, 0x10, 0x00];
+        assert_eq!(
+            intset_entries(&intset).unwrap(),
+            vec![b"-1".to_vec(), b"16".to_vec()]
+        );
+
+        let zipmap = [1, 1, b'f', 2, 0, b'v', b'v', 0xFF];
+        assert_eq!(
+            zipmap_entries(&zipmap).unwrap(),
+            vec![b"f".to_vec(), b"vv".to_vec()]
+        );
+
+        // "aaaaaaaa": 字面量 "a" 加回溯 7 字节
+        let lzf = [0x00, b'a', 0xA0, 0x00];
+        assert_eq!(lzf_decompress(&lzf, 8).unwrap(), b"aaaaaaaa".to_vec());
+
+        let data: Vec<u8> = (0..20000u32)
+            .flat_map(|i| format!("key:{}:{}", i % 97, i % 13).into_bytes())
+            .collect();
+        let compressed = lzf_compress(&data);
+        assert!(compressed.len() < data.len() / 2);
+        assert_eq!(lzf_decompress(&compressed, data.len()).unwrap(), data);
+        assert_eq!(lzf_compress(b"").len(), 0);
+    }
+}
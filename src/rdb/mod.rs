@@ -0,0 +1,5 @@
+mod encoding;
+mod reader;
+
+pub use encoding::{lzf_compress, lzf_decompress};
+pub use reader::{load_all, load_db, RdbReader};
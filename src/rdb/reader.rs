@@ -0,0 +1,555 @@
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::fs::File;
+use std::io::{BufReader, Read};
+
+use anyhow::{anyhow, Result};
+
+use crate::util::{MemValue, MemoryConnection};
+
+use super::encoding::{
+    intset_entries, listpack_entries, lzf_decompress, ziplist_entries, zipmap_entries,
+};
+
+pub const RDB_VERSION_MAX: u32 = 11;
+
+const OPCODE_FUNCTION2: u8 = 0xF5;
+const OPCODE_MODULE_AUX: u8 = 0xF7;
+const OPCODE_IDLE: u8 = 0xF8;
+const OPCODE_FREQ: u8 = 0xF9;
+const OPCODE_AUX: u8 = 0xFA;
+const OPCODE_RESIZEDB: u8 = 0xFB;
+const OPCODE_EXPIRETIME_MS: u8 = 0xFC;
+const OPCODE_EXPIRETIME: u8 = 0xFD;
+const OPCODE_SELECTDB: u8 = 0xFE;
+const OPCODE_EOF: u8 = 0xFF;
+
+const TYPE_STRING: u8 = 0;
+const TYPE_LIST: u8 = 1;
+const TYPE_SET: u8 = 2;
+const TYPE_ZSET: u8 = 3;
+const TYPE_HASH: u8 = 4;
+const TYPE_ZSET_2: u8 = 5;
+const TYPE_MODULE_2: u8 = 7;
+const TYPE_HASH_ZIPMAP: u8 = 9;
+const TYPE_LIST_ZIPLIST: u8 = 10;
+const TYPE_SET_INTSET: u8 = 11;
+const TYPE_ZSET_ZIPLIST: u8 = 12;
+const TYPE_HASH_ZIPLIST: u8 = 13;
+const TYPE_LIST_QUICKLIST: u8 = 14;
+const TYPE_STREAM_LISTPACKS: u8 = 15;
+const TYPE_HASH_LISTPACK: u8 = 16;
+const TYPE_ZSET_LISTPACK: u8 = 17;
+const TYPE_LIST_QUICKLIST_2: u8 = 18;
+const TYPE_STREAM_LISTPACKS_2: u8 = 19;
+const TYPE_SET_LISTPACK: u8 = 20;
+const TYPE_STREAM_LISTPACKS_3: u8 = 21;
+
+// quicklist 2 节点类型
+const QUICKLIST_NODE_PLAIN: u64 = 1;
+
+// RDB 中的一个 key
+#[derive(Debug, Clone, PartialEq)]
+pub struct RdbEntry {
+    pub db: usize,
+    pub key: Vec<u8>,
+    pub value: MemValue,
+    pub expire_at_ms: Option<i64>,
+}
+
+// 长度编码，特殊编码的字符串以 Encoded 返回编码类型
+enum Length {
+    Len(u64),
+    Encoded(u8),
+}
+
+// 流式读取 RDB 文件，逐个返回 key
+// stream、module 等校验不支持的类型会被跳过
+pub struct RdbReader<R: Read> {
+    reader: R,
+    pub version: u32,
+    // AUX 字段，如 redis-ver、repl-id
+    pub aux: BTreeMap<String, String>,
+    // FUNCTION 库代码
+    pub functions: Vec<Vec<u8>>,
+    pub skipped: usize,
+    db: usize,
+    done: bool,
+}
+
+impl RdbReader<BufReader<File>> {
+    pub fn open(path: &str) -> Result<Self> {
+        let file = File::open(path).map_err(|e| anyhow!("open rdb file {}: {}", path, e))?;
+        RdbReader::new(BufReader::new(file))
+    }
+}
+
+impl<R: Read> RdbReader<R> {
+    pub fn new(mut reader: R) -> Result<Self> {
+        let mut header = [0u8; 9];
+        reader.read_exact(&mut header)?;
+        if &header[..5] != b"REDIS" {
+            return Err(anyhow!("invalid rdb header"));
+        }
+        let version: u32 = std::str::from_utf8(&header[5..])?
+            .parse()
+            .map_err(|_| anyhow!("invalid rdb version"))?;
+        if version > RDB_VERSION_MAX {
+            return Err(anyhow!(
+                "rdb version {} not supported, max {}",
+                version,
+                RDB_VERSION_MAX
+            ));
+        }
+        Ok(Self {
+            reader,
+            version,
+            aux: BTreeMap::new(),
+            functions: vec![],
+            skipped: 0,
+            db: 0,
+            done: false,
+        })
+    }
+
+    // 读取到 EOF 后返回底层 reader，用于继续读取 RDB 之后的数据
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    fn read_bytes(&mut self, len: usize) -> Result<Vec<u8>> {
+        let mut buf = vec![0u8; len];
+        self.reader.read_exact(&mut buf)?;
+        Ok(buf)
+    }
+
+    fn read_u8(&mut self) -> Result<u8> {
+        let mut buf = [0u8; 1];
+        self.reader.read_exact(&mut buf)?;
+        Ok(buf[0])
+    }
+
+    fn read_u64_le(&mut self) -> Result<u64> {
+        let mut buf = [0u8; 8];
+        self.reader.read_exact(&mut buf)?;
+        Ok(u64::from_le_bytes(buf))
+    }
+
+    fn read_length_encoding(&mut self) -> Result<Length> {
+        let first = self.read_u8()?;
+        match first >> 6 {
+            0 => Ok(Length::Len((first & 0x3F) as u64)),
+            1 => {
+                let next = self.read_u8()?;
+                Ok(Length::Len((((first & 0x3F) as u64) << 8) | next as u64))
+            }
+            2 => match first {
+                0x80 => {
+                    let b = self.read_bytes(4)?;
+                    Ok(Length::Len(
+                        u32::from_be_bytes([b[0], b[1], b[2], b[3]]) as u64
+                    ))
+                }
+                0x81 => {
+                    let mut buf = [0u8; 8];
+                    self.reader.read_exact(&mut buf)?;
+                    Ok(Length::Len(u64::from_be_bytes(buf)))
+                }
+                _ => Err(anyhow!("invalid length encoding {:#x}", first)),
+            },
+            _ => Ok(Length::Encoded(first & 0x3F)),
+        }
+    }
+
+    fn read_length(&mut self) -> Result<u64> {
+        match self.read_length_encoding()? {
+            Length::Len(l) => Ok(l),
+            Length::Encoded(e) => Err(anyhow!("unexpected string encoding {}", e)),
+        }
+    }
+
+    fn read_string(&mut self) -> Result<Vec<u8>> {
+        match self.read_length_encoding()? {
+            Length::Len(l) => self.read_bytes(l as usize),
+            Length::Encoded(0) => Ok((self.read_u8()? as i8).to_string().into_bytes()),
+            Length::Encoded(1) => {
+                let b = self.read_bytes(2)?;
+                Ok(i16::from_le_bytes([b[0], b[1]]).to_string().into_bytes())
+            }
+            Length::Encoded(2) => {
+                let b = self.read_bytes(4)?;
+                Ok(i32::from_le_bytes([b[0], b[1], b[2], b[3]])
+                    .to_string()
+                    .into_bytes())
+            }
+            Length::Encoded(3) => {
+                let clen = self.read_length()? as usize;
+                let len = self.read_length()? as usize;
+                let compressed = self.read_bytes(clen)?;
+                lzf_decompress(&compressed, len)
+            }
+            Length::Encoded(e) => Err(anyhow!("invalid string encoding {}", e)),
+        }
+    }
+
+    // TYPE_ZSET 中以字符串保存的 score
+    fn read_string_score(&mut self) -> Result<f64> {
+        let len = self.read_u8()?;
+        match len {
+            253 => Ok(f64::NAN),
+            254 => Ok(f64::INFINITY),
+            255 => Ok(f64::NEG_INFINITY),
+            l => {
+                let s = self.read_bytes(l as usize)?;
+                parse_score(&s)
+            }
+        }
+    }
+
+    fn read_binary_score(&mut self) -> Result<f64> {
+        Ok(f64::from_bits(self.read_u64_le()?))
+    }
+
+    fn read_strings(&mut self) -> Result<Vec<Vec<u8>>> {
+        let len = self.read_length()?;
+        let mut items = vec![];
+        for _ in 0..len {
+            items.push(self.read_string()?);
+        }
+        Ok(items)
+    }
+
+    // 读取一个 value，不支持的类型返回 None
+    fn read_value(&mut self, value_type: u8) -> Result<Option<MemValue>> {
+        let value = match value_type {
+            TYPE_STRING => MemValue::String(self.read_string()?),
+            TYPE_LIST => MemValue::List(self.read_strings()?.into()),
+            TYPE_SET => MemValue::Set(self.read_strings()?.into_iter().collect()),
+            TYPE_ZSET | TYPE_ZSET_2 => {
+                let len = self.read_length()?;
+                let mut zset = BTreeMap::new();
+                for _ in 0..len {
+                    let member = self.read_string()?;
+                    let score = match value_type {
+                        TYPE_ZSET => self.read_string_score()?,
+                        _ => self.read_binary_score()?,
+                    };
+                    zset.insert(member, score);
+                }
+                MemValue::ZSet(zset)
+            }
+            TYPE_HASH => {
+                let len = self.read_length()?;
+                let mut hash = BTreeMap::new();
+                for _ in 0..len {
+                    let field = self.read_string()?;
+                    let value = self.read_string()?;
+                    hash.insert(field, value);
+                }
+                MemValue::Hash(hash)
+            }
+            TYPE_HASH_ZIPMAP => MemValue::Hash(to_pairs(zipmap_entries(&self.read_string()?)?)),
+            TYPE_LIST_ZIPLIST => MemValue::List(ziplist_entries(&self.read_string()?)?.into()),
+            TYPE_SET_INTSET => {
+                MemValue::Set(intset_entries(&self.read_string()?)?.into_iter().collect())
+            }
+            TYPE_SET_LISTPACK => MemValue::Set(
+                listpack_entries(&self.read_string()?)?
+                    .into_iter()
+                    .collect(),
+            ),
+            TYPE_ZSET_ZIPLIST | TYPE_ZSET_LISTPACK => {
+                let buf = self.read_string()?;
+                let entries = match value_type {
+                    TYPE_ZSET_ZIPLIST => ziplist_entries(&buf)?,
+                    _ => listpack_entries(&buf)?,
+                };
+                let mut zset = BTreeMap::new();
+                for (member, score) in to_pairs(entries) {
+                    zset.insert(member, parse_score(&score)?);
+                }
+                MemValue::ZSet(zset)
+            }
+            TYPE_HASH_ZIPLIST => MemValue::Hash(to_pairs(ziplist_entries(&self.read_string()?)?)),
+            TYPE_HASH_LISTPACK => MemValue::Hash(to_pairs(listpack_entries(&self.read_string()?)?)),
+            TYPE_LIST_QUICKLIST | TYPE_LIST_QUICKLIST_2 => {
+                let len = self.read_length()?;
+                let mut list = VecDeque::new();
+                for _ in 0..len {
+                    if value_type == TYPE_LIST_QUICKLIST {
+                        list.extend(ziplist_entries(&self.read_string()?)?);
+                        continue;
+                    }
+                    let container = self.read_length()?;
+                    let node = self.read_string()?;
+                    match container {
+                        QUICKLIST_NODE_PLAIN => list.push_back(node),
+                        _ => list.extend(listpack_entries(&node)?),
+                    }
+                }
+                MemValue::List(list)
+            }
+            TYPE_MODULE_2 => {
+                self.skip_module()?;
+                return Ok(None);
+            }
+            TYPE_STREAM_LISTPACKS | TYPE_STREAM_LISTPACKS_2 | TYPE_STREAM_LISTPACKS_3 => {
+                self.skip_stream(value_type)?;
+                return Ok(None);
+            }
+            t => return Err(anyhow!("unsupported rdb value type {}", t)),
+        };
+        Ok(Some(value))
+    }
+
+    // module value 以 opcode 序列保存，0 为结束
+    fn skip_module(&mut self) -> Result<()> {
+        let _module_id = self.read_length()?;
+        loop {
+            match self.read_length()? {
+                0 => return Ok(()),
+                1 | 2 => {
+                    self.read_length()?;
+                }
+                3 => {
+                    self.read_bytes(4)?;
+                }
+                4 => {
+                    self.read_bytes(8)?;
+                }
+                5 => {
+                    self.read_string()?;
+                }
+                op => return Err(anyhow!("invalid module opcode {}", op)),
+            }
+        }
+    }
+
+    fn skip_stream(&mut self, value_type: u8) -> Result<()> {
+        let listpacks = self.read_length()?;
+        for _ in 0..listpacks {
+            self.read_string()?;
+            self.read_string()?;
+        }
+        // length、last_id
+        for _ in 0..3 {
+            self.read_length()?;
+        }
+        if value_type != TYPE_STREAM_LISTPACKS {
+            // first_id、max_deleted_entry_id、entries_added
+            for _ in 0..5 {
+                self.read_length()?;
+            }
+        }
+        let groups = self.read_length()?;
+        for _ in 0..groups {
+            self.read_string()?;
+            self.read_length()?;
+            self.read_length()?;
+            if value_type != TYPE_STREAM_LISTPACKS {
+                self.read_length()?;
+            }
+            let pel = self.read_length()?;
+            for _ in 0..pel {
+                self.read_bytes(16 + 8)?;
+                self.read_length()?;
+            }
+            let consumers = self.read_length()?;
+            for _ in 0..consumers {
+                self.read_string()?;
+                self.read_bytes(8)?;
+                if value_type == TYPE_STREAM_LISTPACKS_3 {
+                    self.read_bytes(8)?;
+                }
+                let pel = self.read_length()?;
+                self.read_bytes(16 * pel as usize)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn next_entry(&mut self) -> Result<Option<RdbEntry>> {
+        let mut expire_at_ms = None;
+        loop {
+            let op = self.read_u8()?;
+            match op {
+                OPCODE_EOF => {
+                    self.done = true;
+                    return Ok(None);
+                }
+                OPCODE_SELECTDB => {
+                    self.db = self.read_length()? as usize;
+                }
+                OPCODE_RESIZEDB => {
+                    self.read_length()?;
+                    self.read_length()?;
+                }
+                OPCODE_AUX => {
+                    let k = self.read_string()?;
+                    let v = self.read_string()?;
+                    self.aux.insert(
+                        String::from_utf8_lossy(&k).to_string(),
+                        String::from_utf8_lossy(&v).to_string(),
+                    );
+                }
+                OPCODE_FUNCTION2 => {
+                    let code = self.read_string()?;
+                    self.functions.push(code);
+                }
+                OPCODE_MODULE_AUX => {
+                    self.skip_module()?;
+                }
+                OPCODE_EXPIRETIME_MS => {
+                    expire_at_ms = Some(self.read_u64_le()? as i64);
+                }
+                OPCODE_EXPIRETIME => {
+                    let b = self.read_bytes(4)?;
+                    let secs = u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as i64;
+                    expire_at_ms = Some(secs * 1000);
+                }
+                OPCODE_FREQ => {
+                    self.read_u8()?;
+                }
+                OPCODE_IDLE => {
+                    self.read_length()?;
+                }
+                value_type => {
+                    let key = self.read_string()?;
+                    match self.read_value(value_type)? {
+                        Some(value) => {
+                            return Ok(Some(RdbEntry {
+                                db: self.db,
+                                key,
+                                value,
+                                expire_at_ms,
+                            }))
+                        }
+                        None => {
+                            log::info!(
+                                "skip rdb key {} with type {}",
+                                String::from_utf8_lossy(&key),
+                                value_type
+                            );
+                            self.skipped += 1;
+                            expire_at_ms = None;
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl<R: Read> Iterator for RdbReader<R> {
+    type Item = Result<RdbEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        match self.next_entry() {
+            Ok(Some(entry)) => Some(Ok(entry)),
+            Ok(None) => None,
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+fn to_pairs(entries: Vec<Vec<u8>>) -> BTreeMap<Vec<u8>, Vec<u8>> {
+    let mut map = BTreeMap::new();
+    let mut iter = entries.into_iter();
+    while let (Some(k), Some(v)) = (iter.next(), iter.next()) {
+        map.insert(k, v);
+    }
+    map
+}
+
+fn parse_score(s: &[u8]) -> Result<f64> {
+    let s = std::str::from_utf8(s)?;
+    match s {
+        "inf" | "+inf" => Ok(f64::INFINITY),
+        "-inf" => Ok(f64::NEG_INFINITY),
+        _ => s.parse().map_err(|_| anyhow!("invalid zset score {}", s)),
+    }
+}
+
+// 读取 RDB 中指定 DB 的 key，keys 非空时只保留其中的 key
+pub fn load_db(path: &str, db: usize, keys: &BTreeSet<String>) -> Result<MemoryConnection> {
+    let mut conn = MemoryConnection::default();
+    for entry in RdbReader::open(path)? {
+        let entry = entry?;
+        if entry.db != db {
+            continue;
+        }
+        if !keys.is_empty() && !keys.contains(String::from_utf8_lossy(&entry.key).as_ref()) {
+            continue;
+        }
+        conn.insert(entry.key, entry.value, entry.expire_at_ms);
+    }
+    Ok(conn)
+}
+
+// 读取 RDB 中全部 DB 的 key
+pub fn load_all(path: &str) -> Result<BTreeMap<usize, MemoryConnection>> {
+    let mut dbs: BTreeMap<usize, MemoryConnection> = BTreeMap::new();
+    for entry in RdbReader::open(path)? {
+        let entry = entry?;
+        dbs.entry(entry.db)
+            .or_default()
+            .insert(entry.key, entry.value, entry.expire_at_ms);
+    }
+    Ok(dbs)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    //cargo test rdb::reader::test::test_rdb_reader --  --nocapture
+    #[test]
+    fn test_rdb_reader() {
+        let mut rdb = b"REDIS0011".to_vec();
+        // aux redis-ver 7.2.0
+        rdb.extend_from_slice(&[OPCODE_AUX, 9]);
+        rdb.extend_from_slice(b"redis-ver");
+        rdb.extend_from_slice(&[5]);
+        rdb.extend_from_slice(b"7.2.0");
+        rdb.extend_from_slice(&[OPCODE_SELECTDB, 2, OPCODE_RESIZEDB, 3, 1]);
+        // string，int8 编码的值
+        rdb.extend_from_slice(&[TYPE_STRING, 1, b's', 0xC0, 0x7B]);
+        // 带过期时间的 set
+        rdb.push(OPCODE_EXPIRETIME_MS);
+        rdb.extend_from_slice(&1_700_000_000_000u64.to_le_bytes());
+        rdb.extend_from_slice(&[TYPE_SET, 1, b't', 2, 1, b'a', 1, b'b']);
+        // zset2，二进制 score
+        rdb.extend_from_slice(&[TYPE_ZSET_2, 1, b'z', 1, 1, b'm']);
+        rdb.extend_from_slice(&1.5f64.to_le_bytes());
+        // quicklist 2，一个 plain 节点
+        rdb.extend_from_slice(&[TYPE_LIST_QUICKLIST_2, 1, b'l', 1, 1, 2, b'x', b'y']);
+        rdb.push(OPCODE_EOF);
+        rdb.extend_from_slice(&[0u8; 8]);
+
+        let mut reader = RdbReader::new(rdb.as_slice()).unwrap();
+        let entries: Vec<RdbEntry> = reader.by_ref().map(|e| e.unwrap()).collect();
+        assert_eq!(reader.version, 11);
+        assert_eq!(reader.aux.get("redis-ver").unwrap(), "7.2.0");
+        assert_eq!(entries.len(), 4);
+        assert!(entries.iter().all(|e| e.db == 2));
+        assert_eq!(entries[0].value, MemValue::String(b"123".to_vec()));
+        assert_eq!(entries[0].expire_at_ms, None);
+        assert_eq!(entries[1].expire_at_ms, Some(1_700_000_000_000));
+        assert_eq!(entries[1].value.type_name(), "set");
+        match &entries[2].value {
+            MemValue::ZSet(z) => assert_eq!(z.get(b"m".as_slice()), Some(&1.5)),
+            v => panic!("unexpected {:?}", v),
+        }
+        assert_eq!(
+            entries[3].value,
+            MemValue::List(vec![b"xy".to_vec()].into())
+        );
+
+        assert!(RdbReader::new(b"REDIS0012".as_slice()).is_err());
+    }
+}
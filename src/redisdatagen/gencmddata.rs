@@ -1,6 +1,6 @@
 // 通过命令组合生成key，尽量覆盖redis所有命令操作
 
-use crate::util::rand_string;
+use crate::util::{rand_lettter_number_string, rand_string};
 use rand::Rng;
 use redis::ConnectionLike;
 use redis::{RedisResult, ToRedisArgs};
@@ -11,6 +11,9 @@ use strum::IntoEnumIterator;
 use strum_macros::EnumIter;
 use tokio::time::Instant;
 
+// FUNCTION LOAD、ACL SETUSER 使用的函数库与用户数量
+const SCENARIO_NAME_POOL: usize = 8;
+
 #[derive(Debug, PartialEq, EnumIter, Clone)]
 pub enum OptType {
     OptAppend,
@@ -39,6 +42,11 @@ pub enum OptType {
     OptBzpopmaxBzpopmin,
     OptZremrangebylexZremrangebyrankZremrangebyscore,
     OptZunionstoreZinterstore,
+    OptGeoadd,
+    OptGeosearchstore,
+    OptGeoradiusStore,
+    OptFunctionLoadFcall,
+    OptAclSetuser,
 }
 
 impl fmt::Display for OptType {
@@ -122,6 +130,21 @@ impl fmt::Display for OptType {
             OptType::OptZunionstoreZinterstore => {
                 write!(f, "opt_zunionstore_zinterstore")
             }
+            OptType::OptGeoadd => {
+                write!(f, "opt_geoadd")
+            }
+            OptType::OptGeosearchstore => {
+                write!(f, "opt_geosearchstore")
+            }
+            OptType::OptGeoradiusStore => {
+                write!(f, "opt_georadius_store")
+            }
+            OptType::OptFunctionLoadFcall => {
+                write!(f, "opt_function_load_fcall")
+            }
+            OptType::OptAclSetuser => {
+                write!(f, "opt_acl_setuser")
+            }
         }
     }
 }
@@ -179,6 +202,11 @@ impl<'a> RedisOpt<'a> {
                 self.opt_zremrangebylex_zremrangebyrank_zremrangebyscore()
             }
             OptType::OptZunionstoreZinterstore => self.opt_zunionstore_zinterstore(),
+            OptType::OptGeoadd => self.opt_geoadd(),
+            OptType::OptGeosearchstore => self.opt_geosearchstore(),
+            OptType::OptGeoradiusStore => self.opt_georadius_store(),
+            OptType::OptFunctionLoadFcall => self.opt_function_load_fcall(),
+            OptType::OptAclSetuser => self.opt_acl_setuser(),
         };
 
         let result = ExecuteResult {
@@ -1635,6 +1663,163 @@ impl<'a> RedisOpt<'a> {
         Ok(())
     }
 
+    // 向 geo 索引写入 loopstep 个随机坐标的 member
+    fn geoadd_random(&mut self, key: &str) -> RedisResult<()> {
+        let cmd_geoadd = redis::cmd("geoadd");
+        let mut rng = rand::thread_rng();
+        for i in 0..self.loopstep {
+            self.redis_conn.req_command(
+                cmd_geoadd
+                    .clone()
+                    .arg(key)
+                    .arg(rng.gen_range(-180.0..180.0))
+                    .arg(rng.gen_range(-85.0..85.0))
+                    .arg(key.to_string() + &*i.to_string()),
+            )?;
+        }
+        Ok(())
+    }
+
+    //GEOADD
+    pub fn opt_geoadd(&mut self) -> RedisResult<()> {
+        let geoadd = "geoadd_".to_string() + &*self.key_suffix.clone();
+        self.geoadd_random(&geoadd)?;
+
+        let cmd_expire = redis::cmd("expire");
+        self.redis_conn.req_command(
+            cmd_expire
+                .clone()
+                .arg(geoadd)
+                .arg(&*self.expire.to_redis_args()),
+        )?;
+        Ok(())
+    }
+
+    //GEOSEARCHSTORE
+    pub fn opt_geosearchstore(&mut self) -> RedisResult<()> {
+        let geo = "geo_".to_string() + &*self.key_suffix.clone();
+        let geosearchstore = "geosearchstore_".to_string() + &*self.key_suffix.clone();
+        self.geoadd_random(&geo)?;
+
+        let cmd_geosearchstore = redis::cmd("geosearchstore");
+        let cmd_del = redis::cmd("del");
+        let mut rng = rand::thread_rng();
+        self.redis_conn.req_command(
+            cmd_geosearchstore
+                .clone()
+                .arg(geosearchstore.clone())
+                .arg(geo.clone())
+                .arg("fromlonlat")
+                .arg(rng.gen_range(-180.0..180.0))
+                .arg(rng.gen_range(-85.0..85.0))
+                .arg("byradius")
+                .arg(10000)
+                .arg("km"),
+        )?;
+        self.redis_conn.req_command(cmd_del.clone().arg(geo))?;
+
+        let cmd_expire = redis::cmd("expire");
+        self.redis_conn.req_command(
+            cmd_expire
+                .clone()
+                .arg(geosearchstore)
+                .arg(&*self.expire.to_redis_args()),
+        )?;
+        Ok(())
+    }
+
+    //GEORADIUS STORE
+    pub fn opt_georadius_store(&mut self) -> RedisResult<()> {
+        let geo = "geo_radius_".to_string() + &*self.key_suffix.clone();
+        let georadius = "georadius_".to_string() + &*self.key_suffix.clone();
+        self.geoadd_random(&geo)?;
+
+        let cmd_georadius = redis::cmd("georadius");
+        let cmd_del = redis::cmd("del");
+        let mut rng = rand::thread_rng();
+        self.redis_conn.req_command(
+            cmd_georadius
+                .clone()
+                .arg(geo.clone())
+                .arg(rng.gen_range(-180.0..180.0))
+                .arg(rng.gen_range(-85.0..85.0))
+                .arg(10000)
+                .arg("km")
+                .arg("store")
+                .arg(georadius.clone()),
+        )?;
+        self.redis_conn.req_command(cmd_del.clone().arg(geo))?;
+
+        let cmd_expire = redis::cmd("expire");
+        self.redis_conn.req_command(
+            cmd_expire
+                .clone()
+                .arg(georadius)
+                .arg(&*self.expire.to_redis_args()),
+        )?;
+        Ok(())
+    }
+
+    //FUNCTION LOAD FCALL
+    // 库名、函数名只能包含字母、数字与下划线，不使用 key_suffix
+    pub fn opt_function_load_fcall(&mut self) -> RedisResult<()> {
+        let function = "function_".to_string() + &*self.key_suffix.clone();
+        // 函数库名取自固定的名称池，以 FUNCTION LOAD REPLACE 重复加载，避免库的数量无限增长
+        let name = format!(
+            "scenario_{}",
+            rand::thread_rng().gen_range(0..SCENARIO_NAME_POOL)
+        );
+        let code = format!(
+            "#!lua name=lib_{}\nredis.register_function('fn_{}', function(keys, args) return redis.call('set', keys[1], args[1]) end)",
+            name, name
+        );
+
+        let cmd_function = redis::cmd("function");
+        let cmd_fcall = redis::cmd("fcall");
+        self.redis_conn
+            .req_command(cmd_function.clone().arg("load").arg("replace").arg(code))?;
+        self.redis_conn.req_command(
+            cmd_fcall
+                .clone()
+                .arg("fn_".to_string() + &name)
+                .arg(1)
+                .arg(function.clone())
+                .arg(rand_string(self.loopstep)),
+        )?;
+
+        let cmd_expire = redis::cmd("expire");
+        self.redis_conn.req_command(
+            cmd_expire
+                .clone()
+                .arg(function)
+                .arg(&*self.expire.to_redis_args()),
+        )?;
+        Ok(())
+    }
+
+    //ACL SETUSER
+    pub fn opt_acl_setuser(&mut self) -> RedisResult<()> {
+        // 用户名取自固定的名称池，reset 清除上次设置的密码与规则后重新设置
+        let user = format!(
+            "user_scenario_{}",
+            rand::thread_rng().gen_range(0..SCENARIO_NAME_POOL)
+        );
+        let cmd_acl = redis::cmd("acl");
+        self.redis_conn.req_command(
+            cmd_acl
+                .clone()
+                .arg("setuser")
+                .arg(user.clone())
+                .arg("reset")
+                .arg("on")
+                .arg(">".to_string() + &rand_lettter_number_string(16))
+                .arg("~".to_string() + &user + ":*")
+                .arg("+@read")
+                .arg("+set"),
+        )?;
+        Ok(())
+    }
+
     // ToDo Stream 类型相关操作
 }
 
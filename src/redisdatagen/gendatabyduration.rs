@@ -1,7 +1,7 @@
 use crate::compare::RedisInstance;
 use crate::redisdatagen::OptType;
 use crate::redisdatagen::RedisOpt;
-use crate::util::rand_string;
+use crate::util::{rand_string, RedisClient};
 use anyhow::{anyhow, Result};
 use crossbeam::channel::{at, select};
 use redis::ConnectionLike;
@@ -92,16 +92,38 @@ impl GeneratorByDuration {
             Ok(c) => {
                 pool.scope(|s| {
                     for i in 0..self.threads {
-                        let r_conn = c.get_redis_connection();
+                        let r_conn = c.get_master_connection();
                         match r_conn {
                             Ok(mut conn) => {
+                                let c = &c;
                                 s.spawn(move |_| {
+                                    let mut master_addr = match c {
+                                        RedisClient::Sentinel(sc, _) => sc.master_addr().ok(),
+                                        _ => None,
+                                    };
                                     loop {
                                         select! {
                                             recv(at(deadline)) -> _ => {
                                                 break;
                                             },
                                             default => {
+                                                // sentinel 发生故障转移时重新连接新的 master
+                                                if let RedisClient::Sentinel(sc, _) = c {
+                                                    match sc.master_addr() {
+                                                        Ok(addr) if Some(&addr) != master_addr.as_ref() => {
+                                                            match c.get_master_connection() {
+                                                                Ok(new_conn) => {
+                                                                    log::info!("sentinel master changed to {:?}", addr);
+                                                                    conn = new_conn;
+                                                                    master_addr = Some(addr);
+                                                                }
+                                                                Err(e) => log::error!("{}", e),
+                                                            }
+                                                        }
+                                                        Ok(_) => {}
+                                                        Err(e) => log::error!("{}", e),
+                                                    }
+                                                }
                                                 // do your task
                                                 let cmd_select = redis::cmd("select");
                                                 // conn.req_packed_command(cmd_select,);
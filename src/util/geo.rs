@@ -0,0 +1,77 @@
+// Redis geo 索引的 geohash score 解码与距离计算，算法与 redis geohash.c、geohash_helper.c 一致
+
+const GEO_STEP: u32 = 26;
+const GEO_LAT_MIN: f64 = -85.05112878;
+const GEO_LAT_MAX: f64 = 85.05112878;
+const GEO_LONG_MIN: f64 = -180.0;
+const GEO_LONG_MAX: f64 = 180.0;
+const EARTH_RADIUS_IN_METERS: f64 = 6372797.560856;
+
+// 自动识别 geo 索引时 score 的范围，排除毫秒时间戳等常见的整数 score
+const GEO_SCORE_DETECT_MIN: f64 = (1u64 << 45) as f64;
+const GEO_SCORE_DETECT_MAX: f64 = (1u64 << 52) as f64;
+
+// 取出交错编码中偶数位（纬度）或奇数位（经度）
+fn squeeze(bits: u64) -> u64 {
+    let mut r = 0;
+    for i in 0..GEO_STEP {
+        r |= ((bits >> (2 * i)) & 1) << i;
+    }
+    r
+}
+
+// geohash score 解码为格子中心的经纬度，返回 (经度, 纬度)
+pub fn geohash_decode(score: f64) -> (f64, f64) {
+    let bits = score as u64;
+    let lat_bits = squeeze(bits);
+    let long_bits = squeeze(bits >> 1);
+    let cells = (1u64 << GEO_STEP) as f64;
+
+    let lat_scale = GEO_LAT_MAX - GEO_LAT_MIN;
+    let lat_min = GEO_LAT_MIN + (lat_bits as f64 / cells) * lat_scale;
+    let lat_max = GEO_LAT_MIN + ((lat_bits + 1) as f64 / cells) * lat_scale;
+    let long_scale = GEO_LONG_MAX - GEO_LONG_MIN;
+    let long_min = GEO_LONG_MIN + (long_bits as f64 / cells) * long_scale;
+    let long_max = GEO_LONG_MIN + ((long_bits + 1) as f64 / cells) * long_scale;
+
+    let longitude = ((long_min + long_max) / 2.0).clamp(GEO_LONG_MIN, GEO_LONG_MAX);
+    let latitude = ((lat_min + lat_max) / 2.0).clamp(GEO_LAT_MIN, GEO_LAT_MAX);
+    (longitude, latitude)
+}
+
+// 两点间的球面距离，单位米
+pub fn geo_distance(lon1: f64, lat1: f64, lon2: f64, lat2: f64) -> f64 {
+    let (lat1r, lon1r) = (lat1.to_radians(), lon1.to_radians());
+    let (lat2r, lon2r) = (lat2.to_radians(), lon2.to_radians());
+    let u = ((lat2r - lat1r) / 2.0).sin();
+    let v = ((lon2r - lon1r) / 2.0).sin();
+    let a = u * u + lat1r.cos() * lat2r.cos() * v * v;
+    2.0 * EARTH_RADIUS_IN_METERS * a.sqrt().asin()
+}
+
+// score 是否像 geohash：52 位以内的整数，且不小于 2^45
+pub fn is_geohash_score(score: f64) -> bool {
+    score.fract() == 0.0 && (GEO_SCORE_DETECT_MIN..GEO_SCORE_DETECT_MAX).contains(&score)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    //cargo test util::geo::test::test_geohash_decode --  --nocapture
+    #[test]
+    fn test_geohash_decode() {
+        // GEOADD Sicily 13.361389 38.115556 Palermo 15.087269 37.502669 Catania
+        let (lon, lat) = geohash_decode(3479099956230698.0);
+        assert!((lon - 13.361389338970184).abs() < 1e-9);
+        assert!((lat - 38.1155563954963).abs() < 1e-9);
+
+        let (lon2, lat2) = geohash_decode(3479447370796909.0);
+        // GEODIST Sicily Palermo Catania
+        assert!((geo_distance(lon, lat, lon2, lat2) - 166274.1516).abs() < 0.01);
+
+        assert!(is_geohash_score(3479099956230698.0));
+        assert!(!is_geohash_score(1700000000000.0));
+        assert!(!is_geohash_score(3479099956230698.5));
+    }
+}
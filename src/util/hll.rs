@@ -0,0 +1,150 @@
+// Redis HyperLogLog 的解析与基数估算，算法与 redis hyperloglog.c 一致
+// 头部 16 字节："HYLL"、编码(1)、保留(3)、缓存的基数(8)
+
+pub const HLL_MAGIC: &[u8] = b"HYLL";
+const HLL_HDR_SIZE: usize = 16;
+const HLL_P: usize = 14;
+const HLL_Q: usize = 64 - HLL_P;
+const HLL_REGISTERS: usize = 1 << HLL_P;
+const HLL_BITS: usize = 6;
+const HLL_DENSE: u8 = 0;
+const HLL_SPARSE: u8 = 1;
+const HLL_ALPHA_INF: f64 = 0.721_347_520_444_481_7;
+
+// value 是否为 HyperLogLog
+pub fn is_hll(value: &[u8]) -> bool {
+    value.len() >= HLL_HDR_SIZE && value.starts_with(HLL_MAGIC)
+}
+
+// 各 register 值出现的次数，格式错误时返回 None
+fn register_histogram(value: &[u8]) -> Option<[u32; 64]> {
+    let mut histo = [0u32; 64];
+    let body = &value[HLL_HDR_SIZE..];
+    match value[4] {
+        HLL_DENSE => {
+            if body.len() < (HLL_REGISTERS * HLL_BITS + 7) / 8 {
+                return None;
+            }
+            for i in 0..HLL_REGISTERS {
+                let bit = i * HLL_BITS;
+                let (b, fb) = (bit / 8, bit % 8);
+                let hi = body.get(b + 1).copied().unwrap_or(0) as u16;
+                let reg = ((body[b] as u16 >> fb) | (hi << (8 - fb))) & 63;
+                histo[reg as usize] += 1;
+            }
+        }
+        HLL_SPARSE => {
+            let mut idx = 0;
+            let mut pos = 0;
+            while pos < body.len() {
+                let op = body[pos];
+                let (reg, len) = match op >> 6 {
+                    // ZERO: 00xxxxxx
+                    0 => (0, (op & 0x3f) as usize + 1),
+                    // XZERO: 01xxxxxx yyyyyyyy
+                    1 => {
+                        pos += 1;
+                        let low = *body.get(pos)? as usize;
+                        (0, (((op & 0x3f) as usize) << 8 | low) + 1)
+                    }
+                    // VAL: 1vvvvvxx
+                    _ => ((((op >> 2) & 0x1f) + 1) as usize, (op & 0x3) as usize + 1),
+                };
+                histo[reg] += len as u32;
+                idx += len;
+                pos += 1;
+            }
+            if idx != HLL_REGISTERS {
+                return None;
+            }
+        }
+        _ => return None,
+    }
+    Some(histo)
+}
+
+fn hll_sigma(mut x: f64) -> f64 {
+    if x == 1.0 {
+        return f64::INFINITY;
+    }
+    let mut y = 1.0;
+    let mut z = x;
+    loop {
+        x *= x;
+        let z_prime = z;
+        z += x * y;
+        y += y;
+        if z_prime == z {
+            return z;
+        }
+    }
+}
+
+fn hll_tau(mut x: f64) -> f64 {
+    if x == 0.0 || x == 1.0 {
+        return 0.0;
+    }
+    let mut y = 1.0;
+    let mut z = 1.0 - x;
+    loop {
+        x = x.sqrt();
+        let z_prime = z;
+        y *= 0.5;
+        z -= (1.0 - x).powi(2) * y;
+        if z_prime == z {
+            return z / 3.0;
+        }
+    }
+}
+
+// 估算基数，与 PFCOUNT 结果相同，格式错误时返回 None
+pub fn hll_count(value: &[u8]) -> Option<u64> {
+    if !is_hll(value) {
+        return None;
+    }
+    let histo = register_histogram(value)?;
+    let m = HLL_REGISTERS as f64;
+    let mut z = m * hll_tau((m - histo[HLL_Q + 1] as f64) / m);
+    for j in (1..=HLL_Q).rev() {
+        z += histo[j] as f64;
+        z *= 0.5;
+    }
+    z += m * hll_sigma(histo[0] as f64 / m);
+    Some((HLL_ALPHA_INF * m * m / z).round() as u64)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn sparse(ops: &[u8]) -> Vec<u8> {
+        let mut v = HLL_MAGIC.to_vec();
+        v.extend_from_slice(&[HLL_SPARSE, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        v.extend_from_slice(ops);
+        v
+    }
+
+    //cargo test util::hll::test::test_hll_count --  --nocapture
+    #[test]
+    fn test_hll_count() {
+        // 空的稀疏 HLL：XZERO 16384
+        let empty = sparse(&[0x7f, 0xff]);
+        assert_eq!(hll_count(&empty), Some(0));
+
+        // 一个 register 为 1：XZERO 100、VAL(1,1)、XZERO 16283
+        let one = sparse(&[0x40, 99, 0x80, 0x7f, 0x9a]);
+        assert_eq!(hll_count(&one), Some(1));
+
+        // 相同 register 的稠密编码
+        let mut dense = HLL_MAGIC.to_vec();
+        dense.extend_from_slice(&[HLL_DENSE; 12]);
+        let mut registers = vec![0u8; (HLL_REGISTERS * HLL_BITS + 7) / 8];
+        // register 100 起始于 bit 600，即第 75 字节的最低位
+        registers[75] = 1;
+        dense.extend_from_slice(&registers);
+        assert_eq!(hll_count(&dense), hll_count(&one));
+
+        assert_eq!(hll_count(b"HYLL"), None);
+        assert_eq!(hll_count(&sparse(&[0x7f, 0xfe])), None);
+    }
+}
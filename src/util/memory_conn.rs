@@ -0,0 +1,551 @@
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use redis::{ConnectionLike, ErrorKind, RedisError, RedisResult, Value};
+use serde::{Deserialize, Serialize};
+
+use super::{geohash_decode, hll_count, RedisKeyType};
+
+// 内存中的 redis value，用于 RDB、AOF、快照等离线数据源
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum MemValue {
+    String(Vec<u8>),
+    List(VecDeque<Vec<u8>>),
+    Set(BTreeSet<Vec<u8>>),
+    ZSet(BTreeMap<Vec<u8>, f64>),
+    Hash(BTreeMap<Vec<u8>, Vec<u8>>),
+}
+
+impl MemValue {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            MemValue::String(_) => "string",
+            MemValue::List(_) => "list",
+            MemValue::Set(_) => "set",
+            MemValue::ZSet(_) => "zset",
+            MemValue::Hash(_) => "hash",
+        }
+    }
+
+    pub fn key_type(&self) -> RedisKeyType {
+        match self {
+            MemValue::String(_) => RedisKeyType::TypeString,
+            MemValue::List(_) => RedisKeyType::TypeList,
+            MemValue::Set(_) => RedisKeyType::TypeSet,
+            MemValue::ZSet(_) => RedisKeyType::TypeZSet,
+            MemValue::Hash(_) => RedisKeyType::TypeHash,
+        }
+    }
+
+    // 元素与值的字节数之和，作为 MEMORY USAGE 的近似值
+    pub fn mem_size(&self) -> usize {
+        match self {
+            MemValue::String(s) => s.len(),
+            MemValue::List(l) => l.iter().map(|v| v.len()).sum(),
+            MemValue::Set(s) => s.iter().map(|m| m.len()).sum(),
+            MemValue::ZSet(z) => z.keys().map(|m| m.len() + 8).sum(),
+            MemValue::Hash(h) => h.iter().map(|(f, v)| f.len() + v.len()).sum(),
+        }
+    }
+
+    // zset 按 score、member 排序，redis 的 score 不会是 NaN
+    pub fn zset_sorted(zset: &BTreeMap<Vec<u8>, f64>) -> Vec<(&Vec<u8>, f64)> {
+        let mut members: Vec<(&Vec<u8>, f64)> = zset.iter().map(|(m, s)| (m, *s)).collect();
+        members.sort_by(|a, b| {
+            a.1.partial_cmp(&b.1)
+                .unwrap_or(Ordering::Equal)
+                .then_with(|| a.0.cmp(b.0))
+        });
+        members
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MemEntry {
+    pub value: MemValue,
+    // 过期时间，unix 毫秒时间戳
+    pub expire_at_ms: Option<i64>,
+}
+
+pub fn now_ms() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or(0)
+}
+
+// 单个 DB 的内存 keyspace，实现 ConnectionLike，Comparer 可像访问 redis 一样读取
+// 仅支持校验用到的只读命令，SCAN 类命令一次返回全部元素
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MemoryConnection {
+    pub entries: BTreeMap<Vec<u8>, MemEntry>,
+    // 判断过期与计算 TTL 使用的固定时间，unix 毫秒时间戳，None 时使用当前时间
+    #[serde(skip)]
+    pub clock_ms: Option<i64>,
+}
+
+fn wrong_type() -> RedisError {
+    RedisError::from((
+        ErrorKind::ResponseError,
+        "WRONGTYPE Operation against a key holding the wrong kind of value",
+    ))
+}
+
+fn arg_err(cmd: &str) -> RedisError {
+    RedisError::from((
+        ErrorKind::ResponseError,
+        "wrong number of arguments",
+        cmd.to_string(),
+    ))
+}
+
+fn parse_int(arg: &[u8]) -> RedisResult<i64> {
+    std::str::from_utf8(arg)
+        .ok()
+        .and_then(|s| s.parse::<i64>().ok())
+        .ok_or_else(|| {
+            RedisError::from((
+                ErrorKind::ResponseError,
+                "value is not an integer or out of range",
+            ))
+        })
+}
+
+pub fn format_score(score: f64) -> String {
+    if score == f64::INFINITY {
+        return "inf".to_string();
+    }
+    if score == f64::NEG_INFINITY {
+        return "-inf".to_string();
+    }
+    score.to_string()
+}
+
+// LRANGE、GETRANGE 语义的下标区间，负数从尾部计算，区间为空时返回 None
+fn range_bounds(len: usize, start: i64, stop: i64) -> Option<(usize, usize)> {
+    let len = len as i64;
+    let s = if start < 0 {
+        (len + start).max(0)
+    } else {
+        start
+    };
+    let e = if stop < 0 {
+        len + stop
+    } else {
+        stop.min(len - 1)
+    };
+    if len == 0 || s > e || s >= len {
+        return None;
+    }
+    Some((s as usize, e as usize))
+}
+
+fn data(v: &[u8]) -> Value {
+    Value::Data(v.to_vec())
+}
+
+fn scan_reply(items: Vec<Value>) -> Value {
+    Value::Bulk(vec![Value::Data(b"0".to_vec()), Value::Bulk(items)])
+}
+
+impl MemoryConnection {
+    pub fn insert(&mut self, key: Vec<u8>, value: MemValue, expire_at_ms: Option<i64>) {
+        self.entries.insert(
+            key,
+            MemEntry {
+                value,
+                expire_at_ms,
+            },
+        );
+    }
+
+    fn now(&self) -> i64 {
+        self.clock_ms.unwrap_or_else(now_ms)
+    }
+
+    // 已过期的 key 视为不存在
+    pub fn get(&self, key: &[u8]) -> Option<&MemEntry> {
+        let entry = self.entries.get(key)?;
+        match entry.expire_at_ms {
+            Some(at) if at <= self.now() => None,
+            _ => Some(entry),
+        }
+    }
+
+    pub fn keys(&self) -> Vec<Vec<u8>> {
+        self.entries
+            .keys()
+            .filter(|k| self.get(k).is_some())
+            .cloned()
+            .collect()
+    }
+
+    fn value(&self, key: &[u8]) -> Option<&MemValue> {
+        self.get(key).map(|e| &e.value)
+    }
+
+    pub fn exec(&mut self, args: &[Vec<u8>]) -> RedisResult<Value> {
+        let name = match args.first() {
+            Some(n) => String::from_utf8_lossy(n).to_lowercase(),
+            None => return Err(arg_err("")),
+        };
+        let key = args.get(1).map(|k| k.as_slice()).unwrap_or_default();
+        if args.len() < 2 && !matches!(name.as_str(), "ping" | "dbsize") {
+            return Err(arg_err(&name));
+        }
+
+        match name.as_str() {
+            "ping" => Ok(Value::Status("PONG".to_string())),
+            "select" => Ok(Value::Okay),
+            "dbsize" => Ok(Value::Int(self.keys().len() as i64)),
+            "exists" => Ok(Value::Int(
+                args[1..].iter().filter(|k| self.get(k).is_some()).count() as i64,
+            )),
+            "type" => Ok(Value::Status(
+                self.value(key)
+                    .map(|v| v.type_name())
+                    .unwrap_or("none")
+                    .to_string(),
+            )),
+            "ttl" | "pttl" => {
+                let ms = match self.get(key) {
+                    None => return Ok(Value::Int(-2)),
+                    Some(MemEntry {
+                        expire_at_ms: None, ..
+                    }) => return Ok(Value::Int(-1)),
+                    Some(MemEntry {
+                        expire_at_ms: Some(at),
+                        ..
+                    }) => at - self.now(),
+                };
+                match name.as_str() {
+                    "ttl" => Ok(Value::Int((ms + 500) / 1000)),
+                    _ => Ok(Value::Int(ms)),
+                }
+            }
+            "pfcount" => {
+                let s = match self.value(key) {
+                    None => return Ok(Value::Int(0)),
+                    Some(MemValue::String(s)) => s,
+                    Some(_) => return Err(wrong_type()),
+                };
+                match hll_count(s) {
+                    Some(count) => Ok(Value::Int(count as i64)),
+                    None => Err(RedisError::from((
+                        ErrorKind::ResponseError,
+                        "WRONGTYPE Key is not a valid HyperLogLog string value.",
+                    ))),
+                }
+            }
+            "memory" => {
+                if !key.eq_ignore_ascii_case(b"usage") {
+                    return Err(arg_err(&name));
+                }
+                let k = args.get(2).ok_or_else(|| arg_err(&name))?;
+                match self.value(k) {
+                    Some(v) => Ok(Value::Int((k.len() + v.mem_size()) as i64)),
+                    None => Ok(Value::Nil),
+                }
+            }
+            "scan" => Ok(scan_reply(
+                self.keys().into_iter().map(Value::Data).collect(),
+            )),
+            "get" | "strlen" | "getrange" => {
+                let s = match self.value(key) {
+                    None => {
+                        return match name.as_str() {
+                            "get" => Ok(Value::Nil),
+                            "strlen" => Ok(Value::Int(0)),
+                            _ => Ok(data(b"")),
+                        }
+                    }
+                    Some(MemValue::String(s)) => s,
+                    Some(_) => return Err(wrong_type()),
+                };
+                match name.as_str() {
+                    "get" => Ok(data(s)),
+                    "strlen" => Ok(Value::Int(s.len() as i64)),
+                    _ => {
+                        if args.len() < 4 {
+                            return Err(arg_err(&name));
+                        }
+                        let (start, stop) = (parse_int(&args[2])?, parse_int(&args[3])?);
+                        match range_bounds(s.len(), start, stop) {
+                            Some((b, e)) => Ok(data(&s[b..=e])),
+                            None => Ok(data(b"")),
+                        }
+                    }
+                }
+            }
+            "llen" | "lrange" | "lindex" => {
+                let empty = VecDeque::new();
+                let list = match self.value(key) {
+                    None => &empty,
+                    Some(MemValue::List(l)) => l,
+                    Some(_) => return Err(wrong_type()),
+                };
+                match name.as_str() {
+                    "llen" => Ok(Value::Int(list.len() as i64)),
+                    "lindex" => {
+                        let i = parse_int(args.get(2).ok_or_else(|| arg_err(&name))?)?;
+                        match range_bounds(list.len(), i, i) {
+                            Some((b, _)) => Ok(data(&list[b])),
+                            None => Ok(Value::Nil),
+                        }
+                    }
+                    _ => {
+                        if args.len() < 4 {
+                            return Err(arg_err(&name));
+                        }
+                        let (start, stop) = (parse_int(&args[2])?, parse_int(&args[3])?);
+                        match range_bounds(list.len(), start, stop) {
+                            Some((b, e)) => {
+                                Ok(Value::Bulk(list.range(b..=e).map(|v| data(v)).collect()))
+                            }
+                            None => Ok(Value::Bulk(vec![])),
+                        }
+                    }
+                }
+            }
+            "scard" | "sismember" | "smembers" | "sscan" => {
+                let empty = BTreeSet::new();
+                let set = match self.value(key) {
+                    None => &empty,
+                    Some(MemValue::Set(s)) => s,
+                    Some(_) => return Err(wrong_type()),
+                };
+                match name.as_str() {
+                    "scard" => Ok(Value::Int(set.len() as i64)),
+                    "sismember" => {
+                        let member = args.get(2).ok_or_else(|| arg_err(&name))?;
+                        Ok(Value::Int(set.contains(member) as i64))
+                    }
+                    "smembers" => Ok(Value::Bulk(set.iter().map(|m| data(m)).collect())),
+                    _ => Ok(scan_reply(set.iter().map(|m| data(m)).collect())),
+                }
+            }
+            "zcard" | "zscore" | "zrange" | "zscan" | "geopos" => {
+                let empty = BTreeMap::new();
+                let zset = match self.value(key) {
+                    None => &empty,
+                    Some(MemValue::ZSet(z)) => z,
+                    Some(_) => return Err(wrong_type()),
+                };
+                match name.as_str() {
+                    "zcard" => Ok(Value::Int(zset.len() as i64)),
+                    "zscore" => {
+                        let member = args.get(2).ok_or_else(|| arg_err(&name))?;
+                        match zset.get(member) {
+                            Some(score) => Ok(data(format_score(*score).as_bytes())),
+                            None => Ok(Value::Nil),
+                        }
+                    }
+                    "geopos" => Ok(Value::Bulk(
+                        args[2..]
+                            .iter()
+                            .map(|m| match zset.get(m) {
+                                Some(score) => {
+                                    let (lon, lat) = geohash_decode(*score);
+                                    Value::Bulk(vec![
+                                        data(lon.to_string().as_bytes()),
+                                        data(lat.to_string().as_bytes()),
+                                    ])
+                                }
+                                None => Value::Nil,
+                            })
+                            .collect(),
+                    )),
+                    "zscan" => {
+                        let mut items = vec![];
+                        for (m, s) in zset {
+                            items.push(data(m));
+                            items.push(data(format_score(*s).as_bytes()));
+                        }
+                        Ok(scan_reply(items))
+                    }
+                    _ => {
+                        if args.len() < 4 {
+                            return Err(arg_err(&name));
+                        }
+                        let (start, stop) = (parse_int(&args[2])?, parse_int(&args[3])?);
+                        let withscores = args
+                            .get(4)
+                            .map(|a| a.eq_ignore_ascii_case(b"withscores"))
+                            .unwrap_or(false);
+                        let sorted = MemValue::zset_sorted(zset);
+                        let mut items = vec![];
+                        if let Some((b, e)) = range_bounds(sorted.len(), start, stop) {
+                            for (m, s) in &sorted[b..=e] {
+                                items.push(data(m));
+                                if withscores {
+                                    items.push(data(format_score(*s).as_bytes()));
+                                }
+                            }
+                        }
+                        Ok(Value::Bulk(items))
+                    }
+                }
+            }
+            "hlen" | "hget" | "hexists" | "hgetall" | "hscan" => {
+                let empty = BTreeMap::new();
+                let hash = match self.value(key) {
+                    None => &empty,
+                    Some(MemValue::Hash(h)) => h,
+                    Some(_) => return Err(wrong_type()),
+                };
+                match name.as_str() {
+                    "hlen" => Ok(Value::Int(hash.len() as i64)),
+                    "hget" => {
+                        let field = args.get(2).ok_or_else(|| arg_err(&name))?;
+                        match hash.get(field) {
+                            Some(v) => Ok(data(v)),
+                            None => Ok(Value::Nil),
+                        }
+                    }
+                    "hexists" => {
+                        let field = args.get(2).ok_or_else(|| arg_err(&name))?;
+                        Ok(Value::Int(hash.contains_key(field) as i64))
+                    }
+                    _ => {
+                        let mut items = vec![];
+                        for (f, v) in hash {
+                            items.push(data(f));
+                            items.push(data(v));
+                        }
+                        match name.as_str() {
+                            "hgetall" => Ok(Value::Bulk(items)),
+                            _ => Ok(scan_reply(items)),
+                        }
+                    }
+                }
+            }
+            _ => Err(RedisError::from((
+                ErrorKind::ResponseError,
+                "unsupported command",
+                name,
+            ))),
+        }
+    }
+}
+
+// 解析一条 RESP 数组格式的命令，返回参数与消耗的字节数
+pub fn parse_packed_command(buf: &[u8]) -> RedisResult<(Vec<Vec<u8>>, usize)> {
+    let invalid = || RedisError::from((ErrorKind::ResponseError, "invalid packed command"));
+    let read_line = |pos: usize| -> RedisResult<(&[u8], usize)> {
+        let rest = buf.get(pos..).ok_or_else(invalid)?;
+        let end = rest
+            .windows(2)
+            .position(|w| w == b"\r\n")
+            .ok_or_else(invalid)?;
+        Ok((&rest[..end], pos + end + 2))
+    };
+
+    let (line, mut pos) = read_line(0)?;
+    if line.first() != Some(&b'*') {
+        return Err(invalid());
+    }
+    let count = parse_int(&line[1..])?;
+    let mut args = vec![];
+    for _ in 0..count {
+        let (line, next) = read_line(pos)?;
+        if line.first() != Some(&b'$') {
+            return Err(invalid());
+        }
+        let len = parse_int(&line[1..])? as usize;
+        let arg = buf.get(next..next + len).ok_or_else(invalid)?;
+        args.push(arg.to_vec());
+        pos = next + len + 2;
+    }
+    Ok((args, pos))
+}
+
+impl ConnectionLike for MemoryConnection {
+    fn req_packed_command(&mut self, cmd: &[u8]) -> RedisResult<Value> {
+        let (args, _) = parse_packed_command(cmd)?;
+        self.exec(&args)
+    }
+
+    fn req_packed_commands(
+        &mut self,
+        cmd: &[u8],
+        offset: usize,
+        count: usize,
+    ) -> RedisResult<Vec<Value>> {
+        let mut values = vec![];
+        let mut pos = 0;
+        while pos < cmd.len() {
+            let (args, used) = parse_packed_command(&cmd[pos..])?;
+            values.push(self.exec(&args)?);
+            pos += used;
+        }
+        Ok(values.into_iter().skip(offset).take(count).collect())
+    }
+
+    fn get_db(&self) -> i64 {
+        0
+    }
+
+    fn check_connection(&mut self) -> bool {
+        true
+    }
+
+    fn is_open(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::util::{key_type, lrange, zscore, RedisKeyType};
+
+    //cargo test util::memory_conn::test::test_memory_connection --  --nocapture
+    #[test]
+    fn test_memory_connection() {
+        let mut conn = MemoryConnection::default();
+        conn.insert(b"s".to_vec(), MemValue::String(b"hello".to_vec()), None);
+        conn.insert(
+            b"l".to_vec(),
+            MemValue::List(vec![b"a".to_vec(), b"b".to_vec(), b"c".to_vec()].into()),
+            Some(now_ms() + 100_000),
+        );
+        let mut zset = BTreeMap::new();
+        zset.insert(b"m1".to_vec(), 2.5);
+        zset.insert(b"m0".to_vec(), 2.5);
+        conn.insert(b"z".to_vec(), MemValue::ZSet(zset), None);
+        conn.insert(
+            b"expired".to_vec(),
+            MemValue::String(vec![]),
+            Some(now_ms() - 1),
+        );
+
+        assert_eq!(key_type("l", &mut conn).unwrap(), RedisKeyType::TypeList);
+        assert_eq!(lrange("l", 1, -1, &mut conn).unwrap(), vec!["b", "c"]);
+        assert_eq!(zscore("z", "m1", &mut conn).unwrap(), Some(2.5));
+        let ttl: i64 = redis::cmd("ttl").arg("l").query(&mut conn).unwrap();
+        assert_eq!(ttl, 100);
+        let range: String = redis::cmd("getrange")
+            .arg("s")
+            .arg(1)
+            .arg(-2)
+            .query(&mut conn)
+            .unwrap();
+        assert_eq!(range, "ell");
+        let ranked: Vec<(String, f64)> = redis::cmd("zrange")
+            .arg("z")
+            .arg(0)
+            .arg(-1)
+            .arg("withscores")
+            .query(&mut conn)
+            .unwrap();
+        assert_eq!(ranked[0].0, "m0");
+        let exists: bool = redis::cmd("exists")
+            .arg("expired")
+            .query(&mut conn)
+            .unwrap();
+        assert!(!exists);
+        let keys: Vec<String> = crate::util::scan(&mut conn).unwrap().collect();
+        assert_eq!(keys.len(), 3);
+    }
+}
@@ -1,8 +1,16 @@
+mod geo;
+mod hash;
+mod hll;
+mod memory_conn;
 mod random;
 mod redis_meta;
 mod redis_util;
 mod yaml_util;
 
+pub use geo::{geo_distance, geohash_decode, is_geohash_score};
+pub use hash::fnv1a_64;
+pub use hll::{hll_count, is_hll};
+pub use memory_conn::{format_score, now_ms, MemEntry, MemValue, MemoryConnection};
 pub use random::{rand_lettter_number_string, rand_string};
 pub use redis_meta::RedisKey;
 pub use redis_meta::RedisKeyType;
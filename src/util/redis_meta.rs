@@ -11,6 +11,8 @@ pub enum RedisKeyType {
     TypeSet,
     TypeZSet,
     TypeHash,
+    // 模块类型或其他未知类型，保存 TYPE 返回的类型名，如 ReJSON-RL、MBbloom--
+    TypeOther(String),
 }
 
 impl FromRedisValue for RedisKeyType {
@@ -22,14 +24,12 @@ impl FromRedisValue for RedisKeyType {
                 "set" => Ok(RedisKeyType::TypeSet),
                 "zset" => Ok(RedisKeyType::TypeZSet),
                 "hash" => Ok(RedisKeyType::TypeHash),
-                _ => Err(RedisError::from((
+                "none" => Err(RedisError::from((
                     ErrorKind::TypeError,
-                    "Response was of incompatible type",
-                    format!(
-                        "{:?} (response was {:?})",
-                        "Response type not string compatible.", val
-                    ),
+                    "Key not exists",
+                    format!("{:?}", val),
                 ))),
+                other => Ok(RedisKeyType::TypeOther(other.to_string())),
             },
             _ => Err(RedisError::from((
                 ErrorKind::TypeError,
@@ -61,6 +61,9 @@ impl Display for RedisKeyType {
             RedisKeyType::TypeHash => {
                 write!(f, "hash")
             }
+            RedisKeyType::TypeOther(name) => {
+                write!(f, "{}", name)
+            }
         }
     }
 }
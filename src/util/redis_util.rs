@@ -1,10 +1,13 @@
 use anyhow::{anyhow, Result};
 
-use redis::{ConnectionLike, Iter};
+use rand::seq::SliceRandom;
+use redis::{ConnectionAddr, ConnectionInfo, ConnectionLike, Iter, RedisConnectionInfo};
+use redis::{ErrorKind, RedisError, TlsCertificates};
 use redis::{FromRedisValue, RedisResult, ToRedisArgs, Value};
 use std::collections::HashMap;
 use std::fmt::{Display, Formatter};
 use std::str::from_utf8;
+use std::time::Duration;
 
 use crate::util::RedisKeyType;
 
@@ -20,54 +23,243 @@ impl RedisClientWithDB {
     // 返回RedisConnection ， 单实例返回 select db 的 connection
     pub fn get_redis_connection(&self) -> RedisResult<RedisConnection> {
         return match &self.client {
-            RedisClient::Single(sc) => {
-                let mut conn = sc.get_connection()?;
+            RedisClient::Single(sc, timeouts) => {
+                let mut conn = timeouts.single_connection(sc)?;
                 conn.req_command(redis::cmd("select").arg(self.db))?;
                 let r_conn = RedisConnection::Single(conn);
                 Ok(r_conn)
             }
-            RedisClient::Cluster(cc) => {
-                let conn = cc.get_connection()?;
+            RedisClient::Cluster(cc, timeouts) => {
+                let conn = timeouts.cluster_connection(cc)?;
                 let r_conn = RedisConnection::Cluster(conn);
                 Ok(r_conn)
             }
+            RedisClient::Sentinel(sc, timeouts) => {
+                let mut conn = timeouts.single_connection(&sc.node_client()?)?;
+                conn.req_command(redis::cmd("select").arg(self.db))?;
+                Ok(RedisConnection::Single(conn))
+            }
+        };
+    }
+}
+
+// 连接超时与读超时，None 表示不限制
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ConnectionTimeouts {
+    pub connect: Option<Duration>,
+    pub read: Option<Duration>,
+}
+
+impl ConnectionTimeouts {
+    pub fn single_connection(&self, client: &redis::Client) -> RedisResult<redis::Connection> {
+        let conn = match self.connect {
+            Some(t) => client.get_connection_with_timeout(t)?,
+            None => client.get_connection()?,
+        };
+        if self.read.is_some() {
+            conn.set_read_timeout(self.read)?;
+        }
+        Ok(conn)
+    }
+
+    // cluster 的连接超时只能在创建 client 时设置，由 cluster_connection 建立连接时生效
+    pub fn cluster_client(
+        &self,
+        mut builder: redis::cluster::ClusterClientBuilder,
+    ) -> RedisResult<redis::cluster::ClusterClient> {
+        if let Some(t) = self.connect {
+            builder = builder.connection_timeout(t);
+        }
+        if let Some(t) = self.read {
+            builder = builder.response_timeout(t);
+        }
+        builder.build()
+    }
+
+    // client 需由 cluster_client 创建，连接各节点时使用其中的连接超时
+    pub fn cluster_connection(
+        &self,
+        client: &redis::cluster::ClusterClient,
+    ) -> RedisResult<redis::cluster::ClusterConnection> {
+        let conn = client.get_connection()?;
+        if self.read.is_some() {
+            conn.set_read_timeout(self.read)?;
+        }
+        Ok(conn)
+    }
+}
+
+// Sentinel 管理的实例，每次创建 client 时通过 sentinel 解析当前 master，以便跟随故障转移
+#[derive(Clone)]
+pub struct SentinelClient {
+    pub sentinels: Vec<redis::Client>,
+    pub master_name: String,
+    // 数据节点的认证信息与 db
+    pub node_redis: RedisConnectionInfo,
+    // 数据节点是否启用 tls，以及是否跳过证书校验
+    pub node_tls: Option<bool>,
+    pub node_certs: Option<TlsCertificates>,
+    // 为 true 时优先连接健康的 replica，无可用 replica 时连接 master
+    pub read_from_replica: bool,
+}
+
+impl SentinelClient {
+    // 依次询问 sentinel，返回第一个成功解析的 master 地址
+    pub fn master_addr(&self) -> RedisResult<(String, u16)> {
+        let mut last_err = RedisError::from((ErrorKind::InvalidClientConfig, "no sentinel"));
+        for sentinel in &self.sentinels {
+            let addr: RedisResult<Option<(String, u16)>> =
+                sentinel.get_connection().and_then(|mut c| {
+                    redis::cmd("SENTINEL")
+                        .arg("get-master-addr-by-name")
+                        .arg(self.master_name.as_str())
+                        .query(&mut c)
+                });
+            match addr {
+                Ok(Some(addr)) => return Ok(addr),
+                Ok(None) => {
+                    last_err = RedisError::from((
+                        ErrorKind::ResponseError,
+                        "sentinel master not found",
+                        self.master_name.clone(),
+                    ))
+                }
+                Err(e) => last_err = e,
+            }
+        }
+        Err(last_err)
+    }
+
+    // 返回 sentinel 记录的健康 replica 地址，排除主观/客观下线及断开的节点
+    pub fn replica_addrs(&self) -> RedisResult<Vec<(String, u16)>> {
+        let mut last_err = RedisError::from((ErrorKind::InvalidClientConfig, "no sentinel"));
+        for sentinel in &self.sentinels {
+            let replicas: RedisResult<Vec<HashMap<String, String>>> =
+                sentinel.get_connection().and_then(|mut c| {
+                    redis::cmd("SENTINEL")
+                        .arg("replicas")
+                        .arg(self.master_name.as_str())
+                        .query(&mut c)
+                });
+            match replicas {
+                Ok(replicas) => return Ok(healthy_replica_addrs(replicas)),
+                Err(e) => last_err = e,
+            }
+        }
+        Err(last_err)
+    }
+
+    pub fn client_for(&self, host: String, port: u16) -> RedisResult<redis::Client> {
+        let addr = match self.node_tls {
+            Some(insecure) => ConnectionAddr::TcpTls {
+                host,
+                port,
+                insecure,
+                tls_params: None,
+            },
+            None => ConnectionAddr::Tcp(host, port),
         };
+        let info = ConnectionInfo {
+            addr,
+            redis: self.node_redis.clone(),
+        };
+        match &self.node_certs {
+            Some(certs) if self.node_tls.is_some() => {
+                redis::Client::build_with_tls(info, certs.clone())
+            }
+            _ => redis::Client::open(info),
+        }
+    }
+
+    pub fn master_client(&self) -> RedisResult<redis::Client> {
+        let (host, port) = self.master_addr()?;
+        self.client_for(host, port)
+    }
+
+    // 数据访问使用的 client，根据 read_from_replica 选择 replica 或 master
+    pub fn node_client(&self) -> RedisResult<redis::Client> {
+        if self.read_from_replica {
+            match self.replica_addrs() {
+                Ok(replicas) => {
+                    if let Some((host, port)) = replicas.choose(&mut rand::thread_rng()) {
+                        return self.client_for(host.clone(), *port);
+                    }
+                }
+                Err(e) => log::error!("{}", e),
+            }
+        }
+        self.master_client()
     }
 }
 
+fn healthy_replica_addrs(replicas: Vec<HashMap<String, String>>) -> Vec<(String, u16)> {
+    let mut addrs = vec![];
+    for replica in replicas {
+        let flags = replica.get("flags").cloned().unwrap_or_default();
+        if flags.contains("s_down") || flags.contains("o_down") || flags.contains("disconnected") {
+            continue;
+        }
+        if let (Some(ip), Some(port)) = (replica.get("ip"), replica.get("port")) {
+            if let Ok(p) = port.parse::<u16>() {
+                addrs.push((ip.clone(), p));
+            }
+        }
+    }
+    addrs
+}
+
 #[derive(Clone)]
 pub enum RedisClient {
-    Single(redis::Client),
-    Cluster(redis::cluster::ClusterClient),
+    Single(redis::Client, ConnectionTimeouts),
+    Cluster(redis::cluster::ClusterClient, ConnectionTimeouts),
+    Sentinel(SentinelClient, ConnectionTimeouts),
 }
 
 impl RedisClient {
     pub fn to_redis_client(&self) -> Result<redis::Client> {
         return match self {
-            RedisClient::Single(sc) => Ok(sc.clone()),
-            RedisClient::Cluster(_) => Err(anyhow!("not single redis client")),
+            RedisClient::Single(sc, _) => Ok(sc.clone()),
+            RedisClient::Cluster(..) => Err(anyhow!("not single redis client")),
+            RedisClient::Sentinel(sc, _) => Ok(sc.master_client()?),
         };
     }
 
     pub fn to_cluster_client(&self) -> Result<redis::cluster::ClusterClient> {
         return match self {
-            RedisClient::Single(_) => Err(anyhow!("not single redis client")),
-            RedisClient::Cluster(cc) => Ok(cc.clone()),
+            RedisClient::Single(..) | RedisClient::Sentinel(..) => {
+                Err(anyhow!("not cluster redis client"))
+            }
+            RedisClient::Cluster(cc, _) => Ok(cc.clone()),
         };
     }
 
     pub fn get_redis_connection(&self) -> RedisResult<RedisConnection> {
         return match self {
-            RedisClient::Single(s) => {
-                let conn = s.get_connection()?;
+            RedisClient::Single(s, timeouts) => {
+                let conn = timeouts.single_connection(s)?;
                 Ok(RedisConnection::Single(conn))
             }
-            RedisClient::Cluster(c) => {
-                let conn = c.get_connection()?;
+            RedisClient::Cluster(c, timeouts) => {
+                let conn = timeouts.cluster_connection(c)?;
                 Ok(RedisConnection::Cluster(conn))
             }
+            RedisClient::Sentinel(sc, timeouts) => {
+                let conn = timeouts.single_connection(&sc.node_client()?)?;
+                Ok(RedisConnection::Single(conn))
+            }
         };
     }
+
+    // 用于写入数据，sentinel 模式忽略 read_from_replica 始终连接 master
+    pub fn get_master_connection(&self) -> RedisResult<RedisConnection> {
+        match self {
+            RedisClient::Sentinel(sc, timeouts) => {
+                let conn = timeouts.single_connection(&sc.master_client()?)?;
+                Ok(RedisConnection::Single(conn))
+            }
+            _ => self.get_redis_connection(),
+        }
+    }
 }
 
 pub enum RedisConnection {
@@ -278,6 +470,65 @@ pub fn info(
     Ok(info_map)
 }
 
+// 通过 cluster 中任一节点获取全部负责 slot 的 master，conn 需直连单个节点
+pub fn cluster_masters(
+    conn: &mut dyn redis::ConnectionLike,
+) -> RedisResult<Vec<(String, u16, Vec<u16>)>> {
+    let nodes: String = redis::cmd("cluster").arg("nodes").query(conn)?;
+    Ok(parse_cluster_masters(&nodes))
+}
+
+// 解析 CLUSTER NODES 中负责 slot 的 master 地址及其 slot 区间
+// 跳过已下线或没有地址的节点，忽略迁移中的 [slot->-node] 条目
+pub fn parse_cluster_masters(nodes: &str) -> Vec<(String, u16, Vec<u16>)> {
+    let mut masters = vec![];
+    for line in nodes.lines() {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() < 9 {
+            continue;
+        }
+        let flags: Vec<&str> = fields[2].split(',').collect();
+        if !flags.contains(&"master") || flags.contains(&"fail") || flags.contains(&"noaddr") {
+            continue;
+        }
+        // ip:port@cport 或 ip:port@cport,hostname
+        let addr = fields[1].split(['@', ',']).next().unwrap_or_default();
+        let (host, port) = match addr.rsplit_once(':') {
+            Some((h, p)) if !h.is_empty() => match p.parse::<u16>() {
+                Ok(p) => (h.to_string(), p),
+                Err(_) => continue,
+            },
+            _ => continue,
+        };
+
+        let mut slots = vec![];
+        for range in &fields[8..] {
+            if range.starts_with('[') {
+                continue;
+            }
+            let (start, end) = match range.split_once('-') {
+                Some((s, e)) => (s.parse::<u16>(), e.parse::<u16>()),
+                None => (range.parse::<u16>(), range.parse::<u16>()),
+            };
+            if let (Ok(s), Ok(e)) = (start, end) {
+                slots.extend(s..=e);
+            }
+        }
+        if !slots.is_empty() {
+            masters.push((host, port, slots));
+        }
+    }
+    masters
+}
+
+pub fn count_keys_in_slot(slot: u16, conn: &mut dyn redis::ConnectionLike) -> RedisResult<usize> {
+    let count: usize = redis::cmd("cluster")
+        .arg("countkeysinslot")
+        .arg(slot)
+        .query(conn)?;
+    Ok(count)
+}
+
 pub fn scan<T>(con: &mut dyn redis::ConnectionLike) -> RedisResult<Iter<'_, T>>
 where
     T: FromRedisValue,
@@ -287,6 +538,16 @@ where
     c.iter(con)
 }
 
+// SCAN 每次返回约 count 个 key，控制单次回复的大小与服务端阻塞时间
+pub fn scan_count<T>(count: usize, con: &mut dyn redis::ConnectionLike) -> RedisResult<Iter<'_, T>>
+where
+    T: FromRedisValue,
+{
+    let mut c = redis::cmd("SCAN");
+    c.cursor_arg(0).arg("COUNT").arg(count.max(1));
+    c.iter(con)
+}
+
 // 获取key类型
 pub fn key_type<T>(key: T, con: &mut dyn redis::ConnectionLike) -> RedisResult<RedisKeyType>
 where
@@ -377,13 +638,36 @@ where
     Ok(size)
 }
 
-//zscore
-pub fn zscore<T>(key: T, member: T, conn: &mut dyn redis::ConnectionLike) -> RedisResult<f64>
+//zscore，member 不存在时返回 None
+pub fn zscore<T>(
+    key: T,
+    member: T,
+    conn: &mut dyn redis::ConnectionLike,
+) -> RedisResult<Option<f64>>
 where
     T: ToRedisArgs,
 {
-    let size: f64 = redis::cmd("zscore").arg(key).arg(member).query(conn)?;
-    Ok(size)
+    let score: Option<f64> = redis::cmd("zscore").arg(key).arg(member).query(conn)?;
+    Ok(score)
+}
+
+//zrange withscores
+pub fn zrange_withscores<T>(
+    key: T,
+    start: isize,
+    stop: isize,
+    conn: &mut dyn redis::ConnectionLike,
+) -> RedisResult<Vec<(String, f64)>>
+where
+    T: ToRedisArgs,
+{
+    let members: Vec<(String, f64)> = redis::cmd("zrange")
+        .arg(key)
+        .arg(start)
+        .arg(stop)
+        .arg("withscores")
+        .query(conn)?;
+    Ok(members)
 }
 
 // hlen
@@ -530,8 +814,19 @@ pub fn key_type_pipline(
                         vec_rediskeys.push(rediskey);
                     }
                 }
-
-                _ => {}
+                // key 已不存在
+                "none" => {}
+                // 模块类型等未知类型，按 DUMP 比较
+                other => {
+                    let key = keys.get(i);
+                    if let Some(k) = key {
+                        let rediskey = RedisKey {
+                            key_name: k.to_string(),
+                            key_type: RedisKeyType::TypeOther(other.to_string()),
+                        };
+                        vec_rediskeys.push(rediskey);
+                    }
+                }
             }
         }
     }
@@ -545,6 +840,24 @@ mod test {
 
     static S_URL: &str = "redis://:redistest0102@114.67.76.82:16377/?timeout=1s";
 
+    //cargo test util::redis_util::test::test_parse_cluster_masters --  --nocapture
+    #[test]
+    fn test_parse_cluster_masters() {
+        let nodes = "07c37dfeb235213a872192d90877d0cd55635b91 127.0.0.1:30004@31004 slave e7d1eecce10fd6bb5eb35b9f99a514335d9ba9ca 0 1426238317239 4 connected
+67ed2db8d677e59ec4a4cefb06858cf2a1a89fa1 127.0.0.1:30002@31002,node2 master - 0 1426238316232 2 connected 5461-5462
+e7d1eecce10fd6bb5eb35b9f99a514335d9ba9ca 127.0.0.1:30001@31001 myself,master - 0 0 1 connected 0-2 7 [8->-67ed2db8d677e59ec4a4cefb06858cf2a1a89fa1]
+292f8b365bb7edb5e285caf0b7e6ddc7265d2f4f 127.0.0.1:30003@31003 master,fail - 0 0 3 connected 10923-16383
+";
+        assert_eq!(
+            parse_cluster_masters(nodes),
+            vec![
+                ("127.0.0.1".to_string(), 30002, vec![5461, 5462]),
+                ("127.0.0.1".to_string(), 30001, vec![0, 1, 2, 7]),
+            ]
+        );
+        assert!(parse_cluster_masters("").is_empty());
+    }
+
     //cargo test util::redis_util::test::test_info --  --nocapture
     #[test]
     fn test_info() {
@@ -557,6 +870,32 @@ mod test {
         }
     }
 
+    //cargo test util::redis_util::test::test_healthy_replica_addrs --  --nocapture
+    #[test]
+    fn test_healthy_replica_addrs() {
+        let replica = |ip: &str, port: &str, flags: &str| {
+            let mut m = HashMap::new();
+            m.insert("ip".to_string(), ip.to_string());
+            m.insert("port".to_string(), port.to_string());
+            m.insert("flags".to_string(), flags.to_string());
+            m
+        };
+        let replicas = vec![
+            replica("10.0.0.1", "6379", "slave"),
+            replica("10.0.0.2", "6379", "s_down,slave"),
+            replica("10.0.0.3", "6380", "slave,disconnected"),
+            replica("10.0.0.4", "6381", "slave"),
+        ];
+        let addrs = healthy_replica_addrs(replicas);
+        assert_eq!(
+            addrs,
+            vec![
+                ("10.0.0.1".to_string(), 6379),
+                ("10.0.0.4".to_string(), 6381)
+            ]
+        );
+    }
+
     //cargo test util::redis_util::test::test_key_type_pipline --  --nocapture
     #[test]
     fn test_key_type_pipline() {